@@ -0,0 +1,223 @@
+use crate::bindings::Action;
+use std::time::{Duration, Instant};
+
+/// How long a movement key is held before it starts repeating, in milliseconds.
+pub const DEFAULT_DAS_MS: u64 = 170;
+/// How often a held movement key repeats once it has started, in milliseconds.
+pub const DEFAULT_ARR_MS: u64 = 30;
+
+/// Most terminals only report key presses, never releases, but they repeat a held key every few tens of
+/// milliseconds. On those a key counts as released once no repeat has arrived for this long.
+pub(crate) const RELEASE_GAP: Duration = Duration::from_millis(100);
+/// Terminals wait a while before they start repeating a held key. Presses of the same key this close
+/// together are taken to be one hold, so the delayed auto shift counts from the first of them.
+const TERMINAL_REPEAT_DELAY: Duration = Duration::from_millis(750);
+
+/// Delayed auto shift: works out when a held movement key should move the piece again. Until the terminal
+/// is seen to report a key going up, a key is treated as held for as long as its repeats keep arriving;
+/// after that, a key is held from its press until its release.
+pub struct AutoShift {
+    das: Duration,
+    arr: Duration,
+    held: Option<HeldKey>,
+    /// Whether a release has been reported, so the terminal can be trusted to report every one.
+    releases_reported: bool,
+}
+
+struct HeldKey {
+    action: Action,
+    pressed: Instant,
+    last_seen: Instant,
+    /// Whether the terminal is currently repeating the key.
+    repeating: bool,
+    next_shift: Instant,
+}
+
+impl AutoShift {
+    pub fn new(das_ms: u64, arr_ms: u64) -> AutoShift {
+        AutoShift {
+            das: Duration::from_millis(das_ms),
+            arr: Duration::from_millis(arr_ms),
+            held: None,
+            releases_reported: false,
+        }
+    }
+
+    /// Reads the `das` and `arr` values, in milliseconds, from the `[handling]` section of the config file.
+    /// Values that aren't given keep their defaults.
+    pub fn from_config(config: &toml::Table) -> Result<AutoShift, String> {
+        let handling = match config.get("handling") {
+            Some(toml::Value::Table(handling)) => handling,
+            Some(_) => return Err(String::from("'handling' must be a table")),
+            None => return Ok(AutoShift::default()),
+        };
+
+        let millis = |name: &str, default: u64| match handling.get(name) {
+            Some(toml::Value::Integer(ms)) if *ms >= 0 => Ok(*ms as u64),
+            Some(_) => Err(format!("'{}' in [handling] must be a number of milliseconds", name)),
+            None => Ok(default),
+        };
+
+        Ok(AutoShift::new(millis("das", DEFAULT_DAS_MS)?, millis("arr", DEFAULT_ARR_MS)?))
+    }
+
+    pub fn das_ms(&self) -> u64 {
+        self.das.as_millis() as u64
+    }
+
+    pub fn arr_ms(&self) -> u64 {
+        self.arr.as_millis() as u64
+    }
+
+    /// Records a press of a movement key. Returns true if this is a new press that should move the piece,
+    /// or false if it is the terminal repeating a key that is already held, whose moves come from
+    /// `due_shifts` instead.
+    pub fn press(&mut self, action: Action, now: Instant) -> bool {
+        if self.releases_reported {
+            if self.held.as_ref().is_some_and(|held| held.action == action) {
+                return false;
+            }
+            self.held = Some(HeldKey {
+                action,
+                pressed: now,
+                last_seen: now,
+                repeating: true,
+                next_shift: now + self.das,
+            });
+            return true;
+        }
+
+        if let Some(held) = self.held.as_mut().filter(|held| held.action == action) {
+            let gap = now.saturating_duration_since(held.last_seen);
+            held.last_seen = now;
+
+            if gap <= RELEASE_GAP {
+                if !held.repeating {
+                    held.repeating = true;
+                    held.next_shift = now.max(held.pressed + self.das);
+                }
+                return false;
+            }
+            if gap <= TERMINAL_REPEAT_DELAY {
+                held.repeating = false;
+                return true;
+            }
+        }
+
+        self.held = Some(HeldKey {
+            action,
+            pressed: now,
+            last_seen: now,
+            repeating: false,
+            next_shift: now,
+        });
+        true
+    }
+
+    /// Records a movement key going up, on a terminal that reports it. From then on keys are held until
+    /// released, rather than until their repeats stop.
+    pub fn release(&mut self, action: Action) {
+        self.releases_reported = true;
+        if self.held.as_ref().is_some_and(|held| held.action == action) {
+            self.held = None;
+        }
+    }
+
+    /// Tells the auto shift a new piece has come in. A movement key still held keeps its delayed auto shift
+    /// charged if `keep_charge` is set, moving the new piece straight away; otherwise the new piece waits
+    /// out the delay again before it starts moving.
+    pub fn piece_spawned(&mut self, now: Instant, keep_charge: bool) {
+        if let Some(held) = self.held.as_mut().filter(|held| held.repeating) {
+            held.next_shift = if keep_charge { now } else { now + self.das };
+        }
+    }
+
+    /// Returns the held movement action and how many times it should move the piece by `now`, if a key is
+    /// being held past its delayed auto shift.
+    pub fn due_shifts(&mut self, now: Instant) -> Option<(Action, u32)> {
+        let held = self.held.as_mut().filter(|held| held.repeating)?;
+        if !self.releases_reported && now.saturating_duration_since(held.last_seen) > RELEASE_GAP {
+            held.repeating = false;
+            return None;
+        }
+        if now < held.next_shift {
+            return None;
+        }
+
+        // With no repeat delay the piece goes straight to the wall; the caller stops once it can't move.
+        if self.arr.is_zero() {
+            held.next_shift = now;
+            return Some((held.action, u32::MAX));
+        }
+
+        let shifts = ((now - held.next_shift).as_nanos() / self.arr.as_nanos()) as u32 + 1;
+        held.next_shift += self.arr * shifts;
+        Some((held.action, shifts))
+    }
+}
+
+impl Default for AutoShift {
+    fn default() -> AutoShift {
+        AutoShift::new(DEFAULT_DAS_MS, DEFAULT_ARR_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an auto shift with the right key held past its first repeat at `start`.
+    fn held_right(start: Instant) -> AutoShift {
+        let mut auto_shift = AutoShift::default();
+        assert!(auto_shift.press(Action::MoveRight, start));
+        assert!(!auto_shift.press(Action::MoveRight, start + Duration::from_millis(50)));
+        auto_shift
+    }
+
+    #[test]
+    fn held_key_shifts_once_its_delay_is_up() {
+        let start = Instant::now();
+        let mut auto_shift = held_right(start);
+        let das = Duration::from_millis(DEFAULT_DAS_MS);
+        for ms in [100, 150] {
+            auto_shift.press(Action::MoveRight, start + Duration::from_millis(ms));
+        }
+        assert_eq!(auto_shift.due_shifts(start + das - Duration::from_millis(1)), None);
+        assert_eq!(auto_shift.due_shifts(start + das), Some((Action::MoveRight, 1)));
+    }
+
+    #[test]
+    fn charged_key_shifts_a_new_piece_straight_away() {
+        let start = Instant::now();
+        let spawn = start + Duration::from_millis(60);
+        let mut auto_shift = held_right(start);
+        auto_shift.piece_spawned(spawn, true);
+        assert_eq!(auto_shift.due_shifts(spawn), Some((Action::MoveRight, 1)));
+    }
+
+    #[test]
+    fn once_releases_are_reported_a_key_shifts_until_it_is_released() {
+        let start = Instant::now();
+        let das = Duration::from_millis(DEFAULT_DAS_MS);
+        let arr = Duration::from_millis(DEFAULT_ARR_MS);
+        let mut auto_shift = AutoShift::default();
+        auto_shift.release(Action::MoveLeft);
+        assert!(auto_shift.press(Action::MoveRight, start));
+        // No repeats come, but the key is still down until its release says otherwise.
+        assert_eq!(auto_shift.due_shifts(start + das - Duration::from_millis(1)), None);
+        assert_eq!(auto_shift.due_shifts(start + das + arr * 10), Some((Action::MoveRight, 11)));
+        assert!(!auto_shift.press(Action::MoveRight, start + das + arr * 10));
+        auto_shift.release(Action::MoveRight);
+        assert_eq!(auto_shift.due_shifts(start + das + arr * 20), None);
+        assert!(auto_shift.press(Action::MoveRight, start + das + arr * 20));
+    }
+
+    #[test]
+    fn uncharged_key_waits_out_the_delay_again_for_a_new_piece() {
+        let start = Instant::now();
+        let spawn = start + Duration::from_millis(60);
+        let mut auto_shift = held_right(start);
+        auto_shift.piece_spawned(spawn, false);
+        assert_eq!(auto_shift.due_shifts(spawn + Duration::from_millis(50)), None);
+    }
+}
@@ -0,0 +1,295 @@
+/// A key read from the terminal.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    CtrlC,
+    F1,
+    Char(char),
+}
+
+/// The things a player can do with a key press during a game.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    /// Drops the piece as far as it can fall without locking it, so it can still be moved or rotated.
+    SonicDrop,
+    RotateCW,
+    RotateCCW,
+    Hold,
+    Pause,
+    ToggleGhost,
+    /// Shows or hides the grid in the board's empty cells.
+    ToggleGrid,
+    CycleTheme,
+    /// Shows the board for a moment in invisible mode.
+    Reveal,
+    /// Takes back the last action on the piece; only available in debug mode.
+    Undo,
+    /// Does nothing; lets a headless game step with gravity alone.
+    Noop,
+}
+
+impl Action {
+    const ALL: [Action; 14] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::SonicDrop,
+        Action::RotateCW,
+        Action::RotateCCW,
+        Action::Hold,
+        Action::Pause,
+        Action::ToggleGhost,
+        Action::ToggleGrid,
+        Action::CycleTheme,
+        Action::Reveal,
+        Action::Undo,
+    ];
+
+    /// The name used for this action in the `[keys]` section of the config file.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::SonicDrop => "sonic_drop",
+            Action::RotateCW => "rotate_cw",
+            Action::RotateCCW => "rotate_ccw",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::ToggleGhost => "toggle_ghost",
+            Action::ToggleGrid => "toggle_grid",
+            Action::CycleTheme => "cycle_theme",
+            Action::Reveal => "reveal",
+            Action::Undo => "undo",
+            Action::Noop => "noop",
+        }
+    }
+
+    /// Returns the action this one becomes with the controls mirrored: left and right movement swap, as do
+    /// the two rotations. Every other action stays the same.
+    pub fn mirrored(self) -> Action {
+        match self {
+            Action::MoveLeft => Action::MoveRight,
+            Action::MoveRight => Action::MoveLeft,
+            Action::RotateCW => Action::RotateCCW,
+            Action::RotateCCW => Action::RotateCW,
+            action => action,
+        }
+    }
+
+    /// Looks up an action by the name returned from `config_name`.
+    pub fn from_config_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.config_name() == name)
+    }
+}
+
+/// Maps each action to the keys that trigger it.
+#[derive(Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            bindings: vec![
+                (Action::MoveLeft, vec![Key::Left, Key::Char('a')]),
+                (Action::MoveRight, vec![Key::Right, Key::Char('d')]),
+                (Action::SoftDrop, vec![Key::Down, Key::Char('s')]),
+                (Action::HardDrop, vec![Key::Space]),
+                (Action::SonicDrop, vec![Key::Char('x')]),
+                (Action::RotateCW, vec![Key::Char('e')]),
+                (Action::RotateCCW, vec![Key::Up, Key::Char('w'), Key::Char('q')]),
+                (Action::Hold, vec![Key::Char('c')]),
+                (Action::Pause, vec![Key::Char('p')]),
+                (Action::ToggleGhost, vec![Key::Char('g')]),
+                (Action::ToggleGrid, vec![Key::Char('b')]),
+                (Action::CycleTheme, vec![Key::Char('t'), Key::Char('\t')]),
+                (Action::Reveal, vec![Key::Char('r')]),
+                (Action::Undo, vec![Key::Char('u')]),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    /// The keys for each of the two players sharing a keyboard in a versus game: the left player moves with
+    /// WASD and the right player with the arrow keys or IJKL. Both can pause, toggle the ghost piece or the
+    /// grid and change the theme, which do so for both games.
+    pub fn versus() -> [KeyBindings; 2] {
+        let shared = [
+            (Action::Pause, vec![Key::Char('p')]),
+            (Action::ToggleGhost, vec![Key::Char('g')]),
+            (Action::ToggleGrid, vec![Key::Char('b')]),
+            (Action::CycleTheme, vec![Key::Char('t'), Key::Char('\t')]),
+        ];
+        let left = vec![
+            (Action::MoveLeft, vec![Key::Char('a')]),
+            (Action::MoveRight, vec![Key::Char('d')]),
+            (Action::SoftDrop, vec![Key::Char('s')]),
+            (Action::HardDrop, vec![Key::Char('w')]),
+            (Action::RotateCW, vec![Key::Char('e')]),
+            (Action::RotateCCW, vec![Key::Char('q')]),
+            (Action::Hold, vec![Key::Char('c')]),
+        ];
+        let right = vec![
+            (Action::MoveLeft, vec![Key::Left, Key::Char('j')]),
+            (Action::MoveRight, vec![Key::Right, Key::Char('l')]),
+            (Action::SoftDrop, vec![Key::Down, Key::Char('k')]),
+            (Action::HardDrop, vec![Key::Space, Key::Char('i')]),
+            (Action::RotateCW, vec![Key::Up, Key::Char('o')]),
+            (Action::RotateCCW, vec![Key::Char('u')]),
+            (Action::Hold, vec![Key::Char('m')]),
+        ];
+        [left, right].map(|mut bindings| {
+            bindings.extend(shared.iter().cloned());
+            KeyBindings { bindings }
+        })
+    }
+
+    /// Builds bindings from the `[keys]` section of the config file, where each action maps to a key name
+    /// or a list of key names, e.g. `hard_drop = "space"` or `move_left = ["left", "h"]`. Actions missing
+    /// from the config keep their default keys.
+    pub fn from_config(config: &toml::Table) -> Result<KeyBindings, String> {
+        let mut bindings = KeyBindings::default();
+
+        let keys = match config.get("keys") {
+            Some(toml::Value::Table(keys)) => keys,
+            Some(_) => return Err(String::from("'keys' must be a table")),
+            None => return Ok(bindings),
+        };
+
+        for (name, value) in keys {
+            let action = Action::from_config_name(name)
+                .ok_or_else(|| format!("unknown action '{}' in [keys]", name))?;
+
+            let names = match value {
+                toml::Value::String(key) => vec![key.as_str()],
+                toml::Value::Array(keys) => keys
+                    .iter()
+                    .map(|k| k.as_str().ok_or_else(|| format!("keys for '{}' must be strings", name)))
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(format!("keys for '{}' must be a string or a list of strings", name)),
+            };
+
+            let keys = names
+                .into_iter()
+                .map(|k| parse_key(k).ok_or_else(|| format!("unknown key '{}' for '{}'", k, name)))
+                .collect::<Result<_, _>>()?;
+            bindings.set(action, keys);
+        }
+
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    /// Returns the action bound to the given key, if any.
+    pub fn action_for(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    fn set(&mut self, action: Action, keys: Vec<Key>) {
+        for binding in &mut self.bindings {
+            if binding.0 == action {
+                binding.1 = keys;
+                return;
+            }
+        }
+    }
+
+    /// Checks that no key is bound to more than one action, and that none is bound to a key the game
+    /// handles itself.
+    fn validate(&self) -> Result<(), String> {
+        for (action, keys) in &self.bindings {
+            if let Some(key) = keys.iter().find(|k| RESERVED_KEYS.contains(k)) {
+                return Err(format!("key {:?} for '{}' is reserved by the game", key, action.config_name()));
+            }
+        }
+        for (i, (action, keys)) in self.bindings.iter().enumerate() {
+            for (other, other_keys) in &self.bindings[i + 1..] {
+                if let Some(key) = keys.iter().find(|k| other_keys.contains(k)) {
+                    return Err(format!(
+                        "key {:?} is bound to both '{}' and '{}'",
+                        key,
+                        action.config_name(),
+                        other.config_name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The keys the game loop handles before looking up bindings: quitting on `z` or Ctrl-C, and saving the
+/// board on F1. Binding them to an action would never reach it.
+const RESERVED_KEYS: [Key; 3] = [Key::Char('z'), Key::CtrlC, Key::F1];
+
+/// Parses a key name from the config file: "left", "right", "up", "down", "space", "tab", "f1" or a single
+/// character.
+fn parse_key(name: &str) -> Option<Key> {
+    match name {
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "space" => Some(Key::Space),
+        "tab" => Some(Key::Char('\t')),
+        "f1" => Some(Key::F1),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Key::Char(c)),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings_from(keys: &str) -> Result<KeyBindings, String> {
+        KeyBindings::from_config(&format!("[keys]\n{}", keys).parse::<toml::Table>().unwrap())
+    }
+
+    #[test]
+    fn configured_keys_replace_the_defaults() {
+        let bindings = bindings_from("hold = \"v\"\nmove_left = [\"left\", \"h\"]").unwrap();
+        assert_eq!(bindings.action_for(Key::Char('v')), Some(Action::Hold));
+        assert_eq!(bindings.action_for(Key::Char('h')), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for(Key::Left), Some(Action::MoveLeft));
+    }
+
+    #[test]
+    fn a_key_bound_to_two_actions_is_rejected() {
+        let err = bindings_from("hold = \"v\"\nhard_drop = \"v\"").err().unwrap();
+        assert!(err.contains("bound to both"), "{}", err);
+        assert!(err.contains("'hold'") && err.contains("'hard_drop'"), "{}", err);
+    }
+
+    #[test]
+    fn keys_the_game_handles_itself_are_rejected() {
+        for key in ["z", "f1"] {
+            let err = bindings_from(&format!("hold = \"{}\"", key)).err().unwrap();
+            assert!(err.contains("reserved"), "{}", err);
+        }
+        let mut bindings = KeyBindings::default();
+        bindings.set(Action::Hold, vec![Key::CtrlC]);
+        assert!(bindings.validate().is_err());
+    }
+}
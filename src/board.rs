@@ -0,0 +1,661 @@
+use crate::display::Display;
+use crate::piece::{Piece, PieceType};
+use crate::theme::Theme;
+use crate::util::Color;
+use std::fmt;
+use std::str::FromStr;
+
+/// The standard board size.
+pub const BOARD_WIDTH: u32 = 10;
+pub const BOARD_HEIGHT: u32 = 20;
+pub const HIDDEN_ROWS: u32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The size of a board, counted in cells. The top `hidden_rows` of its `height` are above the visible
+/// playfield, where pieces spawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BoardConfig {
+    pub width: u32,
+    pub height: u32,
+    pub hidden_rows: u32,
+    /// How many rows, and pairs of columns, of the display each cell is drawn across: 1, or 2 for big
+    /// mode's blocks.
+    pub scale: u32,
+}
+
+impl BoardConfig {
+    /// Checks that every piece fits on the board: at least four columns, and four visible rows below the
+    /// hidden ones.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width < 4 {
+            return Err(format!("a board must be at least 4 columns wide, not {}", self.width));
+        }
+        if self.height < self.hidden_rows + 4 {
+            return Err(format!(
+                "a board must be at least {} rows high, not {}",
+                self.hidden_rows + 4,
+                self.height
+            ));
+        }
+        if self.scale == 0 {
+            return Err(String::from("a board can't be drawn at a scale of 0"));
+        }
+        Ok(())
+    }
+
+    /// Returns the columns and rows of the display the inside of the board is drawn across, borders left out.
+    pub fn display_size(&self) -> (u32, u32) {
+        (self.width * 2 * self.scale, self.height * self.scale)
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> BoardConfig {
+        BoardConfig {
+            width: BOARD_WIDTH,
+            height: BOARD_HEIGHT,
+            hidden_rows: HIDDEN_ROWS,
+            scale: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    config: BoardConfig,
+    pub(crate) cells: Vec<Vec<Option<Color>>>,
+}
+
+impl Board {
+    /// Creates an empty board of the standard size.
+    pub fn new() -> Board {
+        Board::with_config(BoardConfig::default())
+    }
+
+    /// Creates an empty board of the given size.
+    pub fn with_config(config: BoardConfig) -> Board {
+        Board {
+            config,
+            cells: vec![vec![None; config.width as usize]; config.height as usize],
+        }
+    }
+
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
+
+    pub fn width(&self) -> u32 {
+        self.config.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.config.height
+    }
+
+    pub fn hidden_rows(&self) -> u32 {
+        self.config.hidden_rows
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.config.scale
+    }
+
+    /// Changes how large the board is drawn, leaving its cells as they are.
+    pub(crate) fn set_scale(&mut self, scale: u32) {
+        self.config.scale = scale;
+    }
+
+    /// Returns the color of the locked cell at the given column and row, or `None` if it is empty or off
+    /// the board.
+    pub fn cell(&self, x: u32, y: u32) -> Option<Color> {
+        *self.cells.get(y as usize)?.get(x as usize)?
+    }
+
+    /// Draws the board's border and locked cells. With `show_hidden` the hidden rows are set apart from the
+    /// playfield: the border carries on up beside them, and they are drawn dimmed. With `hide_cells` only the
+    /// border is drawn, and the board looks empty whatever is locked in it. With `grid` the empty cells of the
+    /// visible rows are dotted in the theme's grid color, to help line pieces up with their columns.
+    pub fn render(&self, display: &mut Display, theme: &Theme, show_hidden: bool, hide_cells: bool, grid: bool) {
+        let (width, height) = self.config.display_size();
+        let (scale, hidden_height) = (self.scale(), self.hidden_rows() * self.scale());
+        for y in hidden_height..height {
+            display.set_text("|", 0, y, theme.border, theme.background);
+            display.set_text("|", width + 1, y, theme.border, theme.background);
+        }
+        if show_hidden {
+            for y in 0..hidden_height {
+                display.set_text(":", 0, y, theme.border.dim(), theme.background);
+                display.set_text(":", width + 1, y, theme.border.dim(), theme.background);
+            }
+        }
+        for x in 0..(width + 1) {
+            display.set_text("-", x, height, theme.border, theme.background);
+        }
+        for row in 0..self.height() {
+            for col in 0..self.width() {
+                let (x, y) = (1 + col * 2 * scale, row * scale);
+                // A hidden stack is dotted over with the rest, so the grid doesn't give its shape away.
+                let cell = self.cells[row as usize][col as usize].filter(|_| !hide_cells);
+                if grid && cell.is_none() && row >= self.hidden_rows() {
+                    draw_block(display, " .", x, y, scale, theme.grid, theme.background);
+                }
+                if let Some(color) = cell {
+                    let color = theme.piece_color(color);
+                    let color = if show_hidden && row < self.hidden_rows() { color.dim() } else { color };
+                    draw_block(display, "  ", x, y, scale, color, color);
+                }
+            }
+        }
+    }
+
+    /// Draws the board as plain text, one line per row from the top, for debugging. Filled cells show the
+    /// letter of the piece they came from and empty cells a '.'. If a falling piece is given, it is drawn
+    /// over the board at its origin in lowercase.
+    pub fn to_ascii(&self, falling: Option<(&Piece, Point)>) -> String {
+        let mut rows: Vec<Vec<char>> = self
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Some(color) => cell_letter(*color),
+                        None => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if let Some((piece, origin)) = falling {
+            let letter = cell_letter(piece.color).to_ascii_lowercase();
+            piece.each_point(&mut |row, col| {
+                if let Some((x, y)) = self.index(origin.x.saturating_add(col), origin.y.saturating_add(row)) {
+                    rows[y][x] = letter;
+                }
+            });
+        }
+
+        rows.iter().map(|row| row.iter().collect::<String>() + "\n").collect()
+    }
+
+    /// Writes the piece's cells into the board at the given origin. Cells falling outside the board are
+    /// skipped; returns false if any were.
+    pub fn lock_piece(&mut self, piece: &Piece, origin: Point) -> bool {
+        let mut in_bounds = true;
+        piece.each_point(&mut |row, col| {
+            match self.index(origin.x.saturating_add(col), origin.y.saturating_add(row)) {
+                Some((x, y)) => self.cells[y][x] = Some(piece.color),
+                None => in_bounds = false,
+            }
+        });
+        in_bounds
+    }
+
+    pub fn collision_test(&self, piece: &Piece, origin: Point) -> bool {
+        piece.any_point(&mut |row, col| self.is_blocked(origin.x.saturating_add(col), origin.y.saturating_add(row)))
+    }
+
+    /// Returns true if the given cell is outside the board or already filled.
+    pub fn is_blocked(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_none_or(|(x, y)| self.cells[y][x].is_some())
+    }
+
+    /// Returns the column and row indices of the given cell, or None if it's outside the board.
+    fn index(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        (x < self.width() as usize && y < self.height() as usize).then_some((x, y))
+    }
+
+    /// Pushes every row up by one, dropping the top row, and fills the new bottom row with garbage except for
+    /// a gap at `gap_column`. Returns false if the top row had cells in it, which are pushed off the board.
+    pub fn push_garbage(&mut self, gap_column: usize) -> bool {
+        let overflowed = self.cells[0].iter().any(|cell| cell.is_some());
+        self.cells.rotate_left(1);
+        let bottom = self.cells.last_mut().expect("boards have at least one row");
+        for (x, cell) in bottom.iter_mut().enumerate() {
+            *cell = if x == gap_column { None } else { Some(Color::Gray) };
+        }
+        !overflowed
+    }
+
+    /// Removes the bottom row, moving everything above it down a row.
+    pub fn remove_bottom_row(&mut self) {
+        self.cells.rotate_right(1);
+        self.cells[0].fill(None);
+    }
+
+    /// Returns how many rows still hold garbage, not counting complete rows about to be cleared.
+    pub fn garbage_rows(&self) -> u32 {
+        self.cells
+            .iter()
+            .filter(|row| row.contains(&None) && row.contains(&Some(Color::Gray)))
+            .count() as u32
+    }
+
+    /// Returns true if no cell of the board is filled.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|row| row.iter().all(|cell| cell.is_none()))
+    }
+
+    /// Returns the indices of the complete rows, top to bottom.
+    pub fn full_rows(&self) -> Vec<u32> {
+        (0..self.height())
+            .filter(|&row| !self.cells[row as usize].contains(&None))
+            .collect()
+    }
+
+    /// Returns how many complete rows the board has, without collecting them as `full_rows` does.
+    pub fn completed_lines(&self) -> u32 {
+        self.cells.iter().filter(|row| !row.contains(&None)).count() as u32
+    }
+
+    // The board features below are the classic ones bots weigh up placements with. They only look at the
+    // locked cells, and apart from `column_heights` none of them allocate, since a bot calls them for every
+    // placement it considers.
+
+    /// Returns the height of the stack in the given column: how many rows up from the floor its highest
+    /// filled cell is, or 0 if the column is empty.
+    pub fn column_height(&self, x: u32) -> u32 {
+        self.cells
+            .iter()
+            .position(|row| row.get(x as usize).is_some_and(|cell| cell.is_some()))
+            .map_or(0, |top| self.height() - top as u32)
+    }
+
+    /// Returns the height of every column, left to right.
+    pub fn column_heights(&self) -> Vec<u32> {
+        (0..self.width()).map(|x| self.column_height(x)).collect()
+    }
+
+    /// Returns the sum of the column heights.
+    pub fn aggregate_height(&self) -> u32 {
+        (0..self.width()).map(|x| self.column_height(x)).sum()
+    }
+
+    /// Returns how many empty cells have a filled cell somewhere above them in the same column.
+    pub fn hole_count(&self) -> u32 {
+        (0..self.width() as usize)
+            .map(|x| {
+                self.cells
+                    .iter()
+                    .skip_while(|row| row[x].is_none())
+                    .filter(|row| row[x].is_none())
+                    .count() as u32
+            })
+            .sum()
+    }
+
+    /// Returns the sum of the differences in height between each pair of neighbouring columns.
+    pub fn bumpiness(&self) -> u32 {
+        (1..self.width())
+            .map(|x| self.column_height(x - 1).abs_diff(self.column_height(x)))
+            .sum()
+    }
+
+    /// Clears the board of any complete lines, shifting down rows to take their place.
+    /// Returns the indices the cleared rows had before clearing, top to bottom.
+    pub fn clear_lines(&mut self) -> Vec<u32> {
+        let full_rows = self.full_rows();
+
+        // Compact the surviving rows towards the bottom: `write` is the lowest row not yet filled, and only
+        // rows with a gap in them are copied down into it.
+        let mut write = self.cells.len();
+        for read in (0..self.cells.len()).rev() {
+            if self.cells[read].contains(&None) {
+                write -= 1;
+                self.cells.swap(write, read);
+            }
+        }
+        for row in &mut self.cells[..write] {
+            row.fill(None);
+        }
+
+        full_rows
+    }
+
+    /// Lets every filled cell fall on its own as far as it can, closing the gaps left in each column. Returns
+    /// true if any cell moved.
+    pub fn drop_cells(&mut self) -> bool {
+        let mut moved = false;
+        for x in 0..self.width() as usize {
+            // As in `clear_lines`, `write` is the lowest cell of the column not yet filled.
+            let mut write = self.cells.len();
+            for read in (0..self.cells.len()).rev() {
+                if self.cells[read][x].is_some() {
+                    write -= 1;
+                    if write != read {
+                        self.cells[write][x] = self.cells[read][x].take();
+                        moved = true;
+                    }
+                }
+            }
+        }
+        moved
+    }
+
+    /// Lets each group of filled cells joined by their sides fall as far as it can in one piece, until every
+    /// group rests on the floor or on another group. Returns true if any group moved.
+    pub fn drop_groups(&mut self) -> bool {
+        let mut moved = false;
+        loop {
+            let mut fell = false;
+            for group in self.groups() {
+                // A group can fall a row if every cell under it is empty or part of the group itself.
+                let can_fall = group.iter().all(|&(x, y)| {
+                    y + 1 < self.cells.len() && (self.cells[y + 1][x].is_none() || group.contains(&(x, y + 1)))
+                });
+                if !can_fall {
+                    continue;
+                }
+                // Moving the lowest cells first keeps each one from landing on a cell yet to move.
+                for &(x, y) in group.iter().rev() {
+                    self.cells[y + 1][x] = self.cells[y][x].take();
+                }
+                fell = true;
+            }
+            if !fell {
+                return moved;
+            }
+            moved = true;
+        }
+    }
+
+    /// Returns the groups of filled cells joined by their sides, as the column and row of each cell, sorted
+    /// top to bottom.
+    fn groups(&self) -> Vec<Vec<(usize, usize)>> {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let mut seen = vec![vec![false; width]; height];
+        let mut groups = Vec::new();
+        for start_y in 0..height {
+            for start_x in 0..width {
+                if seen[start_y][start_x] || self.cells[start_y][start_x].is_none() {
+                    continue;
+                }
+                seen[start_y][start_x] = true;
+                let mut group = vec![(start_x, start_y)];
+                let mut i = 0;
+                while let Some(&(x, y)) = group.get(i) {
+                    i += 1;
+                    let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+                    for (nx, ny) in neighbours {
+                        if nx < width && ny < height && !seen[ny][nx] && self.cells[ny][nx].is_some() {
+                            seen[ny][nx] = true;
+                            group.push((nx, ny));
+                        }
+                    }
+                }
+                group.sort_unstable_by_key(|&(x, y)| (y, x));
+                groups.push(group);
+            }
+        }
+        groups
+    }
+}
+
+/// Shows the board as `to_ascii` draws it without a falling piece, which `from_str` reads back.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_ascii(None))
+    }
+}
+
+/// Reads a board drawn the way `to_ascii` draws one: a line per row from the top, including the hidden rows,
+/// with a '.' for each empty cell, a piece letter for a cell of that piece's color, in either case, and a '#'
+/// for garbage. The board is as wide and as high as the drawing, with the standard number of hidden rows.
+impl FromStr for Board {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Board, String> {
+        let cells = s
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(x, c)| match c {
+                        '.' => Ok(None),
+                        _ => letter_cell(c)
+                            .map(Some)
+                            .ok_or_else(|| format!("row {}, column {}: unknown cell '{}'", y + 1, x + 1, c)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some(width) = cells.first().map(Vec::len) else {
+            return Err(String::from("the board has no rows"));
+        };
+        if let Some(y) = cells.iter().position(|row| row.len() != width) {
+            return Err(format!(
+                "row {} is {} cells wide, but the first row is {}",
+                y + 1,
+                cells[y].len(),
+                width
+            ));
+        }
+        let config = BoardConfig {
+            width: width as u32,
+            height: cells.len() as u32,
+            ..BoardConfig::default()
+        };
+        config.validate()?;
+        Ok(Board { config, cells })
+    }
+}
+
+/// Draws one cell of a board drawn at `scale`, with its top-left corner at the given display position: the
+/// two characters of `text` repeated across `scale` times, on each of `scale` rows.
+pub(crate) fn draw_block(
+    display: &mut Display,
+    text: &str,
+    x: u32,
+    y: u32,
+    scale: u32,
+    fg_color: Color,
+    bg_color: Color,
+) {
+    let line = text.repeat(scale as usize);
+    for row in 0..scale {
+        display.set_text(&line, x, y + row, fg_color, bg_color);
+    }
+}
+
+/// Returns the letter of the piece whose guideline color a cell has, or '#' for any other color.
+fn cell_letter(color: Color) -> char {
+    let kind = match color {
+        Color::Cyan => PieceType::I,
+        Color::Yellow => PieceType::O,
+        Color::Purple => PieceType::T,
+        Color::Green => PieceType::S,
+        Color::Red => PieceType::Z,
+        Color::Blue => PieceType::J,
+        Color::Orange => PieceType::L,
+        _ => return '#',
+    };
+    kind.name().chars().next().unwrap_or('#')
+}
+
+/// Returns the color of a cell drawn with the given letter by `cell_letter`, ignoring its case.
+fn letter_cell(letter: char) -> Option<Color> {
+    if letter == '#' {
+        return Some(Color::Gray);
+    }
+    PieceType::ALL
+        .iter()
+        .find(|kind| kind.name().starts_with(letter.to_ascii_uppercase()))
+        .map(|&kind| Piece::of_type(kind).color)
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Direction;
+
+    #[test]
+    fn locking_a_piece_straddling_the_top_row_keeps_the_cells_on_the_board() {
+        // Standing upright with its grid two rows above the board, the I reaches down into rows 0 and 1.
+        let mut piece = Piece::new_i();
+        piece.rotate(Direction::Right);
+        let mut board = Board::new();
+        assert!(!board.lock_piece(&piece, Point { x: 0, y: -2 }));
+        let filled: Vec<_> = (0..board.height()).filter(|&y| board.cell(2, y).is_some()).collect();
+        assert_eq!(filled, vec![0, 1]);
+    }
+
+    #[test]
+    fn locking_a_piece_inside_the_board_writes_every_cell() {
+        let mut board = Board::new();
+        assert!(board.lock_piece(&Piece::new_i(), Point { x: 0, y: -1 }));
+        assert!((0..4).all(|x| board.cell(x, 0) == Some(Color::Cyan)));
+    }
+
+    /// Reads a board from rows given top to bottom.
+    fn board(rows: &[&str]) -> Board {
+        rows.join("\n").parse().unwrap()
+    }
+
+    #[test]
+    fn clearing_interleaved_full_rows_keeps_the_row_between() {
+        let mut cleared = board(&["....", "....", "....", "OOOO", "T..T", "IIII"]);
+        assert_eq!(cleared.clear_lines(), vec![3, 5]);
+        assert_eq!(cleared, board(&["....", "....", "....", "....", "....", "T..T"]));
+    }
+
+    #[test]
+    fn clearing_only_the_bottom_row_moves_the_stack_down() {
+        let mut cleared = board(&["....", "....", "....", ".S..", "SS..", "IIII"]);
+        assert_eq!(cleared.clear_lines(), vec![5]);
+        assert_eq!(cleared, board(&["....", "....", "....", "....", ".S..", "SS.."]));
+    }
+
+    #[test]
+    fn clearing_four_stacked_full_rows() {
+        let mut cleared = board(&["....", "J...", "IIII", "IIII", "IIII", "IIII"]);
+        assert_eq!(cleared.clear_lines(), vec![2, 3, 4, 5]);
+        assert_eq!(cleared, board(&["....", "....", "....", "....", "....", "J..."]));
+    }
+
+    #[test]
+    fn clearing_an_empty_board_does_nothing() {
+        let mut cleared = Board::new();
+        assert!(cleared.clear_lines().is_empty());
+        assert_eq!(cleared, Board::new());
+    }
+
+    #[test]
+    fn board_is_empty_until_a_cell_is_filled() {
+        let mut empty = Board::new();
+        assert!(empty.is_empty());
+        empty.cells[5][3] = Some(Color::Gray);
+        assert!(!empty.is_empty());
+    }
+
+    #[test]
+    fn garbage_pushes_the_stack_up_with_a_gap_in_the_new_row() {
+        let mut pushed = board(&["....", "....", "....", "....", ".T..", "TTT."]);
+        assert!(pushed.push_garbage(2));
+        assert_eq!(pushed, board(&["....", "....", "....", ".T..", "TTT.", "##.#"]));
+        assert_eq!(pushed.garbage_rows(), 1);
+    }
+
+    #[test]
+    fn garbage_pushing_cells_off_the_top_reports_it() {
+        let mut pushed = board(&["..O.", "....", "....", "....", "....", "...."]);
+        assert!(!pushed.push_garbage(0));
+        assert_eq!(pushed, board(&["....", "....", "....", "....", "....", ".###"]));
+    }
+
+    #[test]
+    fn board_features_of_a_hand_built_stack() {
+        let stack = board(&["....", "....", ".#..", "##.#", "#..#", "####"]);
+        assert_eq!(stack.column_heights(), vec![3, 4, 1, 3]);
+        assert_eq!(stack.aggregate_height(), 11);
+        assert_eq!(stack.hole_count(), 1);
+        assert_eq!(stack.bumpiness(), 6);
+        assert_eq!(stack.completed_lines(), 1);
+    }
+
+    #[test]
+    fn board_features_of_an_empty_board_are_zero() {
+        let empty = Board::new();
+        assert!(empty.column_heights().iter().all(|&height| height == 0));
+        assert_eq!(empty.aggregate_height(), 0);
+        assert_eq!(empty.hole_count(), 0);
+        assert_eq!(empty.bumpiness(), 0);
+        assert_eq!(empty.completed_lines(), 0);
+    }
+
+    #[test]
+    fn covered_gaps_each_count_as_a_hole() {
+        let stack = board(&["....", "#...", "....", "#...", "....", "..#."]);
+        assert_eq!(stack.hole_count(), 3);
+        assert_eq!(stack.column_heights(), vec![5, 0, 1, 0]);
+    }
+
+    #[test]
+    fn positions_far_off_the_board_collide_without_panicking() {
+        let empty = Board::new();
+        let piece = Piece::new_t();
+        for (x, y) in [(i32::MIN, 0), (i32::MAX, 0), (0, i32::MIN), (0, i32::MAX), (-1, 5), (8, 5), (3, -1)] {
+            assert!(empty.collision_test(&piece, Point { x, y }), "T at ({}, {})", x, y);
+        }
+        assert!(!empty.collision_test(&piece, Point { x: 3, y: 0 }));
+    }
+
+    #[test]
+    fn cells_outside_the_board_are_blocked() {
+        let empty = Board::new();
+        assert!(empty.is_blocked(-1, 0));
+        assert!(empty.is_blocked(0, -1));
+        assert!(empty.is_blocked(BOARD_WIDTH as i32, 0));
+        assert!(empty.is_blocked(0, BOARD_HEIGHT as i32));
+        assert!(!empty.is_blocked(0, 0));
+        assert_eq!(empty.cell(BOARD_WIDTH, 0), None);
+    }
+
+    #[test]
+    fn locking_far_off_the_board_writes_nothing() {
+        let mut empty = Board::new();
+        assert!(!empty.lock_piece(&Piece::new_o(), Point { x: i32::MAX, y: i32::MIN }));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn grid_dots_the_empty_visible_cells_in_the_theme_grid_color() {
+        let theme = Theme { grid: Color::Pink, ..Theme::GUIDELINE };
+        let mut stack = Board::new();
+        stack.cells[10][0] = Some(Color::Red);
+        let (width, height) = stack.config().display_size();
+        let mut display = Display::new(width + 10, height + 1);
+        stack.render(&mut display, &theme, false, false, true);
+
+        assert_eq!(display.char_at(2, 5), ('.', Color::Pink));
+        assert_eq!(display.char_at(1, 10).1, theme.z);
+        assert_eq!(display.char_at(2, 10).0, ' ');
+        // The hidden rows and everything right of the border are left alone.
+        assert_eq!(display.char_at(2, HIDDEN_ROWS - 1).0, ' ');
+        assert_eq!(display.char_at(width + 1, 5).0, '|');
+        assert_eq!(display.char_at(width + 2, 5).0, ' ');
+    }
+
+    #[test]
+    fn no_grid_leaves_the_empty_cells_blank() {
+        let board = Board::new();
+        let (width, height) = board.config().display_size();
+        let mut display = Display::new(width + 2, height + 1);
+        board.render(&mut display, &Theme::GUIDELINE, false, false, false);
+        assert_eq!(display.char_at(2, 5).0, ' ');
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+/// Measures how long a game has been played, leaving out the time spent paused. The clock doesn't run until
+/// it is started, and once stopped it holds its time for good. Every call is given the current time, so a
+/// game on a pinned clock measures the same times every run.
+#[derive(Debug, Default, Clone)]
+pub struct GameClock {
+    started: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+    stopped: bool,
+}
+
+impl GameClock {
+    pub fn new() -> GameClock {
+        GameClock::default()
+    }
+
+    /// Starts the clock, unless it has been started already.
+    pub fn start(&mut self, now: Instant) {
+        if self.started.is_none() && !self.stopped {
+            self.started = Some(now);
+        }
+    }
+
+    /// Stops the clock running until it is resumed. A clock that hasn't started has nothing to pause.
+    pub fn pause(&mut self, now: Instant) {
+        if self.started.is_some() && self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Sets the clock running again after a pause, unless it has been stopped.
+    pub fn resume(&mut self, now: Instant) {
+        if self.stopped {
+            return;
+        }
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += now.saturating_duration_since(paused_at);
+        }
+    }
+
+    /// Stops the clock for good, so it keeps the time it has reached.
+    pub fn stop(&mut self, now: Instant) {
+        self.pause(now);
+        self.stopped = true;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some() && self.paused_at.is_none()
+    }
+
+    /// Returns how long the clock has run by `now`, or zero if it hasn't started.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        let Some(started) = self.started else {
+            return Duration::ZERO;
+        };
+        let end = self.paused_at.unwrap_or(now);
+        end.saturating_duration_since(started).saturating_sub(self.paused_for)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn elapsed_time_leaves_out_each_pause() {
+        let start = Instant::now();
+        let mut clock = GameClock::new();
+        clock.start(start);
+        clock.pause(start + secs(10));
+        clock.resume(start + secs(15));
+        clock.pause(start + secs(20));
+        assert_eq!(clock.elapsed(start + secs(100)), secs(15));
+        clock.resume(start + secs(30));
+        assert_eq!(clock.elapsed(start + secs(40)), secs(25));
+    }
+
+    #[test]
+    fn pausing_twice_counts_from_the_first_pause() {
+        let start = Instant::now();
+        let mut clock = GameClock::new();
+        clock.start(start);
+        clock.pause(start + secs(5));
+        clock.pause(start + secs(8));
+        clock.resume(start + secs(10));
+        assert_eq!(clock.elapsed(start + secs(12)), secs(7));
+    }
+
+    #[test]
+    fn clock_that_hasnt_started_reads_zero() {
+        let start = Instant::now();
+        let mut clock = GameClock::new();
+        clock.pause(start);
+        clock.resume(start + secs(5));
+        assert!(!clock.is_running());
+        assert_eq!(clock.elapsed(start + secs(10)), Duration::ZERO);
+    }
+
+    #[test]
+    fn stopped_clock_keeps_its_time() {
+        let start = Instant::now();
+        let mut clock = GameClock::new();
+        clock.start(start);
+        clock.stop(start + secs(3));
+        clock.resume(start + secs(4));
+        clock.start(start + secs(5));
+        assert!(!clock.is_running());
+        assert_eq!(clock.elapsed(start + secs(60)), secs(3));
+    }
+}
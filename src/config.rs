@@ -0,0 +1,57 @@
+use crate::theme::Theme;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Returns the directory holding the game's config and save files, inside the user's config directory.
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("tetrust"))
+}
+
+/// Reads `config.toml` from the config directory. A missing file gives an empty config.
+pub fn load_config() -> Result<toml::Table, String> {
+    let path = match config_dir() {
+        Some(dir) => dir.join("config.toml"),
+        None => return Ok(toml::Table::new()),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => contents
+            .parse()
+            .map_err(|e| format!("invalid config file {}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(format!("could not read config file {}: {}", path.display(), e)),
+    }
+}
+
+/// Returns the theme last played with, as saved by `save_theme`. A missing file or unknown name gives `None`.
+pub fn load_theme() -> Option<Theme> {
+    let contents = fs::read_to_string(config_dir()?.join("theme")).ok()?;
+    Theme::by_name(contents.trim())
+}
+
+/// Saves the name of the theme played with to `theme` in the config directory, so the next game starts in it.
+pub fn save_theme(theme: &Theme) -> io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("theme"), format!("{}\n", theme.name))
+}
+
+/// Appends a line to the named file in the config directory, creating both if need be. With no config
+/// directory there is nowhere to write, and nothing is done.
+pub fn append_line(file_name: &str, line: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(dir.join(file_name))?;
+    writeln!(file, "{}", line)
+}
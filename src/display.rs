@@ -1,17 +1,32 @@
 use crate::util::Color;
 use std::io::{self, Write};
 
-const ESC: &'static str = "\x1b";
+const ESC: &str = "\x1b";
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 struct Pixel {
     c: char,
     fg_color: Color,
     bg_color: Color,
 }
 
+/// Draws text to the terminal through a buffer. Each frame is drawn into the back buffer; rendering then
+/// writes out only the cells that differ from the front buffer, which holds what is already on screen.
 pub struct Display {
     buffer: Vec<Vec<Pixel>>,
+    front: Vec<Vec<Pixel>>,
+    /// Whether the screen has to be cleared and drawn in full on the next render, because what's on it
+    /// can't be trusted to match the front buffer.
+    full_redraw: bool,
+    /// How much of the buffer fits in the terminal; anything beyond it is left out when rendering.
+    visible_width: u32,
+    visible_height: u32,
+    /// How far right everything drawn with `set_text` is moved, so the same layout can be drawn side by side.
+    origin_x: u32,
+    /// How many colors the terminal shows, which decides how colors are sent to it.
+    color_depth: ColorDepth,
+    /// Whether rendering is kept to the buffers, with nothing written to the terminal.
+    headless: bool,
 }
 
 impl Display {
@@ -26,55 +41,135 @@ impl Display {
         }
 
         Display {
-            buffer: rows
+            front: rows.clone(),
+            buffer: rows,
+            full_redraw: true,
+            visible_width: width,
+            visible_height: height,
+            origin_x: 0,
+            color_depth: ColorDepth::detect(),
+            headless: false,
         }
     }
 
+    /// Creates a display that draws into its buffers as any other does, but never writes to the terminal, so
+    /// a game can be run without one.
+    pub fn headless(width: u32, height: u32) -> Display {
+        Display { headless: true, ..Display::new(width, height) }
+    }
+
+    /// Chooses between drawing in 24-bit color, from the 256-color palette or from the 16 standard colors,
+    /// overriding what the terminal was detected to support.
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth;
+        self.full_redraw = true;
+    }
+
+    /// Limits rendering to the given terminal size, so that rows too wide for the terminal don't wrap
+    /// onto the next line.
+    pub fn set_visible_size(&mut self, width: u32, height: u32) {
+        self.visible_width = width;
+        self.visible_height = height;
+        // Resizing can leave the terminal's contents rewrapped or cut off.
+        self.full_redraw = true;
+    }
+
+    /// Moves everything drawn from now on `x` columns to the right.
+    pub fn set_origin(&mut self, x: u32) {
+        self.origin_x = x;
+    }
+
+    pub fn visible_width(&self) -> u32 {
+        self.visible_width
+    }
+
+    pub fn visible_height(&self) -> u32 {
+        self.visible_height
+    }
+
+    /// Writes the back buffer to the terminal. Only cells that changed since the last render are written,
+    /// unless the whole screen needs redrawing.
     pub fn render(&mut self) {
-        self.clear_screen();
-        self.set_cursor_pos(0, 0);
+        let full_redraw = std::mem::replace(&mut self.full_redraw, false);
+        if self.headless {
+            self.front.clone_from(&self.buffer);
+            return;
+        }
+        if full_redraw {
+            self.clear_screen();
+        }
 
         let mut writer = io::stdout();
-        let mut fg_color = Color::Black;
-        let mut bg_color = Color::Black;
+        // The colors and cursor position the terminal is known to have, if any.
+        let mut fg_color = None;
+        let mut bg_color = None;
+        let mut cursor = None;
 
-        let mut y = 0;
+        for y in 0..self.buffer.len().min(self.visible_height as usize) {
+            for x in 0..self.buffer[y].len().min(self.visible_width as usize) {
+                let pixel = self.buffer[y][x];
+                if !full_redraw && pixel == self.front[y][x] {
+                    continue;
+                }
 
-        for row in &self.buffer {
-            for pixel in row {
-                if pixel.fg_color != fg_color {
-                    fg_color = pixel.fg_color;
+                if cursor != Some((x, y)) {
+                    self.set_cursor_pos(x as u32, y as u32);
+                }
+                if fg_color != Some(pixel.fg_color) {
+                    fg_color = Some(pixel.fg_color);
                     self.set_fg_color(pixel.fg_color);
-                 }
-                if pixel.bg_color != bg_color {
-                    bg_color = pixel.bg_color;
+                }
+                if bg_color != Some(pixel.bg_color) {
+                    bg_color = Some(pixel.bg_color);
                     self.set_bg_color(pixel.bg_color);
-                 }
+                }
 
                 let bytes = [pixel.c as u8];
                 assert!(writer.write_all(&bytes).is_ok());
+                cursor = Some((x + 1, y));
+                self.front[y][x] = pixel;
             }
-            y += 1;
-            self.set_cursor_pos(0, y);
         }
 
         assert!(writer.flush().is_ok());
     }
 
     pub fn set_text(&mut self, text: & str, x: u32, y: u32, fg_color: Color, bg_color: Color) {
-        let row = &mut self.buffer[y as usize];
-        let mut i = 0;
+        // Text running off the edge of the buffer is cut off.
+        let row = match self.buffer.get_mut(y as usize) {
+            Some(row) => row,
+            None => return,
+        };
 
-        for c in text.chars() {
-            let cell = &mut row[(x + i) as usize];
+        let x = x.saturating_add(self.origin_x);
+        for (i, c) in text.chars().enumerate() {
+            let cell = match row.get_mut(x as usize + i) {
+                Some(cell) => cell,
+                None => break,
+            };
             cell.c = c;
             cell.fg_color = fg_color;
             cell.bg_color = bg_color;
-            i += 1;
         }
     }
 
+    /// Returns what the back buffer holds, a line of text per row, leaving out the colors.
+    #[cfg(test)]
+    pub(crate) fn text(&self) -> String {
+        self.buffer.iter().map(|row| row.iter().map(|pixel| pixel.c).collect::<String>() + "\n").collect()
+    }
+
+    /// Returns the character at the given position of the back buffer, with its foreground color.
+    #[cfg(test)]
+    pub(crate) fn char_at(&self, x: u32, y: u32) -> (char, Color) {
+        let pixel = self.buffer[y as usize][x as usize];
+        (pixel.c, pixel.fg_color)
+    }
+
     pub fn clear_screen(&self) {
+        if self.headless {
+            return;
+        }
         let mut writer = io::stdout();
         assert!(writer.write_all(self.esc("2J").as_bytes()).is_ok());
         assert!(writer.flush().is_ok());
@@ -103,14 +198,39 @@ impl Display {
     }
 
     fn set_fg_color(&self, color: Color) {
-        self.print(&self.esc(&format!("38;5;{}m", self.get_color_code(color))));
+        self.print(&self.esc(&format!("{}m", self.color_parameters(color, false))));
     }
 
     fn set_bg_color(&self, color: Color) {
-        self.print(&self.esc(&format!("48;5;{}m", self.get_color_code(color))));
+        self.print(&self.esc(&format!("{}m", self.color_parameters(color, true))));
     }
 
-    fn get_color_code(&self, color: Color) -> i32 {
+    /// Returns the parameters of the escape sequence setting the foreground or background color: its red,
+    /// green and blue components on a truecolor terminal, its number in the 256-color palette, or the code of
+    /// the closest of the 16 standard colors on a terminal showing no more than those.
+    fn color_parameters(&self, color: Color, background: bool) -> String {
+        let select = if background { 48 } else { 38 };
+        match (self.color_depth, color) {
+            (ColorDepth::TrueColor, Color::Rgb(r, g, b)) => format!("{};2;{};{};{}", select, r, g, b),
+            (ColorDepth::TrueColor, color) => {
+                let (r, g, b) = palette_rgb(self.get_color_code(color));
+                format!("{};2;{};{};{}", select, r, g, b)
+            }
+            (ColorDepth::Palette, color) => format!("{};5;{}", select, self.get_color_code(color)),
+            (ColorDepth::Standard, color) => {
+                let (r, g, b) = palette_rgb(self.get_color_code(color));
+                let code = nearest_standard_color_code(r, g, b);
+                let base = if background { 40 } else { 30 };
+                if code < 8 {
+                    (base + code as u32).to_string()
+                } else {
+                    (base + 60 + (code - 8) as u32).to_string()
+                }
+            }
+        }
+    }
+
+    fn get_color_code(&self, color: Color) -> u8 {
         match color {
             Color::Cyan => 44,
             Color::Purple => 90,
@@ -118,7 +238,224 @@ impl Display {
             Color::Red => 9,
             Color::Blue => 21,
             Color::Orange => 202,
+            Color::Yellow => 226,
+            Color::White => 231,
+            Color::LightGray => 250,
+            Color::Gray => 244,
+            Color::Magenta => 201,
+            Color::SkyBlue => 117,
+            Color::Teal => 36,
+            Color::Vermilion => 166,
+            Color::Pink => 175,
+            Color::DarkCyan => 30,
+            Color::DarkPurple => 54,
+            Color::DarkGreen => 22,
+            Color::DarkRed => 88,
+            Color::DarkBlue => 18,
+            Color::DarkOrange => 130,
+            Color::DarkYellow => 100,
+            Color::DarkGray => 238,
+            Color::DarkMagenta => 127,
+            Color::DarkSkyBlue => 67,
+            Color::DarkTeal => 23,
+            Color::DarkVermilion => 94,
+            Color::DarkPink => 132,
+            Color::Rgb(r, g, b) => nearest_color_code(r, g, b),
             Color::Black => 0
         }
     }
 }
+
+/// How many colors a terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// Any 24-bit color, sent as its red, green and blue components.
+    TrueColor,
+    /// The 256-color palette.
+    Palette,
+    /// Only the 16 standard colors, which every color terminal shows.
+    Standard,
+}
+
+impl ColorDepth {
+    /// Reads what the terminal supports from the environment: 24-bit color if `COLORTERM` says so, the
+    /// 256-color palette if `COLORTERM` is set at all or `TERM` names a 256-color terminal, and otherwise
+    /// only the 16 standard colors.
+    fn detect() -> ColorDepth {
+        let colorterm = std::env::var("COLORTERM").ok();
+        let term = std::env::var("TERM").ok();
+        ColorDepth::from_env(colorterm.as_deref(), term.as_deref())
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> ColorDepth {
+        match colorterm {
+            Some("truecolor") | Some("24bit") => ColorDepth::TrueColor,
+            Some(_) => ColorDepth::Palette,
+            None if term.is_some_and(|term| term.contains("256color")) => ColorDepth::Palette,
+            None => ColorDepth::Standard,
+        }
+    }
+}
+
+/// The levels each component steps through in the 6x6x6 color cube making up codes 16 to 231 of the
+/// 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The colors of the 16 standard codes at the start of the 256-color palette, as xterm shows them.
+const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (128, 0, 0),
+    (0, 128, 0),
+    (128, 128, 0),
+    (0, 0, 128),
+    (128, 0, 128),
+    (0, 128, 128),
+    (192, 192, 192),
+    (128, 128, 128),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (0, 0, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Returns the red, green and blue components of a code in the 256-color palette: one of the 16 standard
+/// colors, a color from the 6x6x6 cube, or one of the 24 grays that end the palette.
+pub fn palette_rgb(code: u8) -> (u8, u8, u8) {
+    match code {
+        0..=15 => STANDARD_COLORS[code as usize],
+        16..=231 => {
+            let i = code - 16;
+            (CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize])
+        }
+        _ => {
+            let level = 8 + 10 * (code - 232);
+            (level, level, level)
+        }
+    }
+}
+
+/// Returns the code of the color in the 256-color palette closest to the given one, for terminals without
+/// 24-bit color. The nearest colors in the cube and among the grays are compared, and the closer one is
+/// chosen; the standard colors are left out, since terminals commonly change them.
+pub fn nearest_color_code(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+
+    let average = (r as u16 + g as u16 + b as u16) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    let distance = |code: u8| {
+        let (pr, pg, pb) = palette_rgb(code);
+        [(pr, r), (pg, g), (pb, b)]
+            .iter()
+            .map(|&(p, c)| (p.abs_diff(c) as u32).pow(2))
+            .sum::<u32>()
+    };
+    if distance(gray) < distance(cube) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Returns the code of the standard color closest to the given one, from 0 to 15, for terminals showing
+/// no more than the 16 standard colors.
+pub fn nearest_standard_color_code(r: u8, g: u8, b: u8) -> u8 {
+    (0..STANDARD_COLORS.len())
+        .min_by_key(|&code| {
+            let (sr, sg, sb) = STANDARD_COLORS[code];
+            [(sr, r), (sg, g), (sb, b)]
+                .iter()
+                .map(|&(s, c)| (s.abs_diff(c) as u32).pow(2))
+                .sum::<u32>()
+        })
+        .unwrap_or(0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns how bright a color is as the 256-color palette draws it.
+    fn brightness(display: &Display, color: Color) -> u32 {
+        let (r, g, b) = palette_rgb(display.get_color_code(color));
+        r as u32 + g as u32 + b as u32
+    }
+
+    #[test]
+    fn dimmed_colors_are_drawn_darker() {
+        let display = Display::new(1, 1);
+        assert_ne!(Color::Red.dim(), Color::Red);
+        assert_ne!(display.get_color_code(Color::Red.dim()), display.get_color_code(Color::Red));
+        for color in [Color::Cyan, Color::Purple, Color::Green, Color::Red, Color::Blue, Color::Orange, Color::Yellow] {
+            assert!(brightness(&display, color.dim()) < brightness(&display, color), "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn nearest_color_code_picks_the_closest_cube_color_or_gray() {
+        assert_eq!(nearest_color_code(255, 0, 0), 196);
+        assert_eq!(nearest_color_code(0, 0, 0), 16);
+        assert_eq!(nearest_color_code(250, 5, 100), 197);
+        assert_eq!(nearest_color_code(128, 128, 128), 244);
+    }
+
+    #[test]
+    fn palette_colors_quantize_to_themselves() {
+        for code in 16..=255 {
+            let (r, g, b) = palette_rgb(code);
+            assert_eq!(palette_rgb(nearest_color_code(r, g, b)), (r, g, b), "code {}", code);
+        }
+    }
+
+    #[test]
+    fn colors_are_sent_as_rgb_or_palette_codes() {
+        let mut display = Display::new(1, 1);
+        display.set_color_depth(ColorDepth::TrueColor);
+        assert_eq!(display.color_parameters(Color::Rgb(1, 2, 3), false), "38;2;1;2;3");
+        assert_eq!(display.color_parameters(Color::Red, true), "48;2;255;0;0");
+        display.set_color_depth(ColorDepth::Palette);
+        assert_eq!(display.color_parameters(Color::Rgb(255, 0, 0), false), "38;5;196");
+        assert_eq!(display.color_parameters(Color::Red, true), "48;5;9");
+    }
+
+    #[test]
+    fn colors_fall_back_to_the_standard_codes_without_a_palette() {
+        let mut display = Display::new(1, 1);
+        display.set_color_depth(ColorDepth::Standard);
+        assert_eq!(display.color_parameters(Color::Black, false), "30");
+        assert_eq!(display.color_parameters(Color::Red, false), "91");
+        assert_eq!(display.color_parameters(Color::Red, true), "101");
+        assert_eq!(display.color_parameters(Color::DarkRed, false), "31");
+        assert_eq!(display.color_parameters(Color::Rgb(250, 250, 250), true), "107");
+    }
+
+    #[test]
+    fn nearest_standard_color_code_picks_the_closest_of_the_16() {
+        for code in 0..16 {
+            let (r, g, b) = STANDARD_COLORS[code as usize];
+            assert_eq!(nearest_standard_color_code(r, g, b), code);
+        }
+        assert_eq!(nearest_standard_color_code(10, 10, 10), 0);
+        assert_eq!(nearest_standard_color_code(0, 0, 150), 4);
+        assert_eq!(nearest_standard_color_code(230, 30, 20), 9);
+        assert_eq!(nearest_standard_color_code(150, 140, 130), 8);
+    }
+
+    #[test]
+    fn color_depth_is_read_from_colorterm_then_term() {
+        assert_eq!(ColorDepth::from_env(Some("truecolor"), None), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env(Some("24bit"), Some("linux")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env(Some("yes"), Some("linux")), ColorDepth::Palette);
+        assert_eq!(ColorDepth::from_env(None, Some("xterm-256color")), ColorDepth::Palette);
+        assert_eq!(ColorDepth::from_env(None, Some("linux")), ColorDepth::Standard);
+        assert_eq!(ColorDepth::from_env(None, None), ColorDepth::Standard);
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt;
+use std::io;
+
+/// Why the game had to stop running, as opposed to the player losing or quitting it.
+#[derive(Debug)]
+pub enum TetrustError {
+    /// The terminal couldn't be switched into raw mode.
+    Terminal(String),
+    /// Reading key presses from standard input failed.
+    Input(io::Error),
+    /// Every thread feeding the game loop stopped, so no more updates can arrive.
+    Disconnected,
+}
+
+impl fmt::Display for TetrustError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TetrustError::Terminal(msg) => write!(f, "terminal error: {}", msg),
+            TetrustError::Input(err) => write!(f, "could not read from standard in: {}", err),
+            TetrustError::Disconnected => write!(f, "the game stopped receiving updates"),
+        }
+    }
+}
+
+impl std::error::Error for TetrustError {}
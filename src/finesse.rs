@@ -0,0 +1,85 @@
+use crate::board::{Board, BoardConfig, Point};
+use crate::game::spawn_position;
+use crate::piece::Piece;
+use crate::rotation::{Kicks, RotationSystem};
+use crate::util::Direction;
+use std::collections::VecDeque;
+
+/// Returns the fewest inputs that bring a freshly spawned piece of the placed piece's type over the columns
+/// it was placed in, turned the way it was placed, ready to drop. Each tap of a movement key, each movement
+/// key held until the piece reaches a wall, and each rotation counts as one input. The search is run on an
+/// empty board at the spawn height, so it ignores the stack. Returns `None` if the placement can't be reached
+/// from above this way, as with a tuck or a spin under an overhang.
+pub(crate) fn optimal_inputs(
+    config: BoardConfig,
+    rotation_system: RotationSystem,
+    placed: &Piece,
+    position: Point,
+) -> Option<u32> {
+    let board = Board::with_config(config);
+    let kicks = rotation_system.kicks();
+    let target = footprint(placed, position);
+    let mut spawned = Piece::of_type(placed.kind());
+    let spawn = spawn_position(placed.kind(), config.width);
+    kicks.orient_for_spawn(&mut spawned);
+
+    let mut seen = vec![(spawned.rotation, spawn)];
+    let mut queue = VecDeque::from([(spawned, spawn, 0)]);
+    while let Some((piece, position, inputs)) = queue.pop_front() {
+        if footprint(&piece, position) == target {
+            return Some(inputs);
+        }
+        for (next, next_position) in moves(&board, kicks, &piece, position) {
+            if !seen.contains(&(next.rotation, next_position)) {
+                seen.push((next.rotation, next_position));
+                queue.push_back((next, next_position, inputs + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Returns where a single input can take the piece: a step or a slide to the wall either way, or a turn
+/// either way with the rotation system's wall kicks.
+fn moves(board: &Board, kicks: &dyn Kicks, piece: &Piece, position: Point) -> Vec<(Piece, Point)> {
+    let fits = |piece: &Piece, position: Point| !board.collision_test(piece, position);
+    let mut moves = Vec::new();
+    for dx in [-1, 1] {
+        let step = |position: Point| Point {
+            x: position.x + dx,
+            y: position.y,
+        };
+        if !fits(piece, step(position)) {
+            continue;
+        }
+        moves.push((piece.clone(), step(position)));
+        let mut slid = step(position);
+        while fits(piece, step(slid)) {
+            slid = step(slid);
+        }
+        moves.push((piece.clone(), slid));
+    }
+    for direction in [Direction::Left, Direction::Right] {
+        let mut turned = piece.clone();
+        turned.rotate(direction);
+        let kicked = kicks.kick_offsets(piece, direction).into_iter().map(|(x, y)| Point {
+            x: position.x + x,
+            y: position.y + y,
+        });
+        if let Some(kicked) = kicked.into_iter().find(|&kicked| fits(&turned, kicked)) {
+            moves.push((turned, kicked));
+        }
+    }
+    moves
+}
+
+/// Returns the columns of the piece's cells at the given origin, paired with their rows counted from the
+/// piece's top row, so that two placements compare equal whatever height they were dropped to.
+fn footprint(piece: &Piece, origin: Point) -> Vec<(i32, i32)> {
+    let mut cells = Vec::new();
+    piece.each_point(&mut |row, col| cells.push((origin.x + col, row)));
+    let top = cells.iter().map(|&(_, row)| row).min().unwrap_or(0);
+    let mut cells: Vec<_> = cells.into_iter().map(|(x, row)| (x, row - top)).collect();
+    cells.sort_unstable();
+    cells
+}
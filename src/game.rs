@@ -0,0 +1,3362 @@
+use crate::autoshift::{AutoShift, RELEASE_GAP};
+use crate::bindings::{Action, Key, KeyBindings};
+use crate::board::{draw_block, Board, BoardConfig, Point};
+use crate::clock::GameClock;
+use crate::display::Display;
+use crate::finesse;
+use crate::highscore::ScoreEntry;
+use crate::piece::{Piece, PieceBag, PieceType, Randomizer};
+use crate::rotation::RotationSystem;
+use crate::sound::{NoSound, SoundSink};
+use crate::stats::{InputLatency, Stats};
+use crate::theme::{PieceColors, Theme};
+use crate::util::{Color, Direction};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How often the game ticks, in milliseconds. Gravity is applied once per tick.
+pub const TICK_MS: u64 = 16;
+
+/// How long a piece may rest on the stack before it locks, in milliseconds.
+const LOCK_DELAY_MS: u64 = 500;
+/// How many times moving or rotating a resting piece may restart its lock delay, unless changed with
+/// `Game::set_lock_reset_limit`.
+pub const LOCK_RESET_LIMIT: u32 = 15;
+/// Points awarded for each row the player soft drops a piece.
+const SOFT_DROP_POINTS: u32 = 1;
+/// How many times faster than gravity a piece falls while the soft drop key is held.
+const SOFT_DROP_FACTOR: f64 = 20.0;
+/// Points awarded for each row a piece travels when hard dropped.
+const HARD_DROP_POINTS: u32 = 2;
+/// How many upcoming pieces are shown in the sidebar, unless changed with `Game::set_previews`.
+pub const NEXT_PIECE_COUNT: usize = 3;
+/// The most upcoming pieces the sidebar has room for.
+pub const MAX_NEXT_PIECES: usize = 5;
+/// The size in cells of the boxes the next and held pieces are shown in, which fits any piece lying flat.
+const PREVIEW_BOX_SIZE: (u32, u32) = (4, 2);
+/// Points awarded per consecutive line-clearing placement in a combo.
+const COMBO_POINTS: u32 = 50;
+/// Lines that must be cleared for each level up.
+const LINES_PER_LEVEL: u32 = 10;
+/// The level a game stops rising at, unless changed with `Game::set_max_level`.
+pub const MAX_LEVEL: u32 = 30;
+/// The highest level a game can be started on.
+pub const MAX_START_LEVEL: u32 = 15;
+/// How long cleared lines flash before the rows above fall into their place, in milliseconds.
+pub const LINE_CLEAR_DELAY_MS: u64 = 150;
+/// How long each flash of a clearing line lasts, in milliseconds.
+const LINE_CLEAR_FLASH_MS: u64 = 50;
+/// How long the message announcing a perfect clear stays up, in milliseconds.
+const ALL_CLEAR_MESSAGE_MS: u64 = 1500;
+/// How long the points scored by a clear float over the board, in milliseconds. They fade for the last half.
+const SCORE_POPUP_MS: u64 = 1000;
+/// How many of the latest score popups are shown at once.
+const MAX_SCORE_POPUPS: usize = 3;
+/// How long the finesse line stays red after a piece is placed with more inputs than it needed, in
+/// milliseconds.
+const FINESSE_FAULT_MS: u64 = 1000;
+/// How long the board stays in sight when revealed in invisible mode, in milliseconds.
+const REVEAL_MS: u64 = 1000;
+/// How long after revealing the board in invisible mode it can be revealed again, in milliseconds.
+const REVEAL_COOLDOWN_MS: u64 = 10_000;
+/// How long the cells of a piece that just locked flash white, in milliseconds.
+const LOCK_FLASH_MS: u64 = 100;
+/// How long the trail left by a hard drop stays on screen, in milliseconds. It fades for the last half.
+const DROP_TRAIL_MS: u64 = 200;
+/// How many actions back can be undone.
+const UNDO_HISTORY: usize = 50;
+
+/// Whether a locked T piece was spun into place.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// The ways a game can end.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum GameOver {
+    /// Locked cells reached the top of the board.
+    TopOut,
+    /// A piece locked entirely within the hidden rows above the visible board.
+    LockOut,
+    /// A new piece could not be placed without overlapping the stack.
+    BlockOut,
+    /// The game mode's line goal was reached.
+    Victory,
+}
+
+impl fmt::Display for GameOver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameOver::TopOut => write!(f, "Top out"),
+            GameOver::LockOut => write!(f, "Lock out"),
+            GameOver::BlockOut => write!(f, "Block out"),
+            GameOver::Victory => write!(f, "Victory"),
+        }
+    }
+}
+
+/// How a game is won, if it can be.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum GameMode {
+    /// Play until topping out.
+    #[default]
+    Endless,
+    /// Play until clearing `goal` lines, for score.
+    Marathon { goal: u32 },
+    /// Clear `goal` lines as fast as possible.
+    Sprint { goal: u32 },
+    /// Dig through `rows` rows of garbage, starting at the bottom of the board, in as few pieces and as
+    /// little time as possible.
+    Cheese { rows: u32 },
+    /// Practice without pressure: the speed stays at the start level and the game never ends. Whenever the
+    /// next piece can't come in, or the stack reaches into the hidden rows, the bottom row of the board is
+    /// removed, moving the stack down a row, until there is room again.
+    Zen,
+}
+
+impl GameMode {
+    pub const MARATHON_GOAL: u32 = 150;
+    pub const SPRINT_GOAL: u32 = 40;
+    pub const CHEESE_ROWS: u32 = 10;
+
+    /// Looks up a mode by name, with the given goal or the mode's usual one.
+    pub fn from_name(name: &str, goal: Option<u32>) -> Option<GameMode> {
+        match name {
+            "endless" => Some(GameMode::Endless),
+            "marathon" => Some(GameMode::Marathon {
+                goal: goal.unwrap_or(GameMode::MARATHON_GOAL),
+            }),
+            "sprint" => Some(GameMode::Sprint {
+                goal: goal.unwrap_or(GameMode::SPRINT_GOAL),
+            }),
+            "cheese" => Some(GameMode::Cheese {
+                rows: goal.unwrap_or(GameMode::CHEESE_ROWS),
+            }),
+            "zen" => Some(GameMode::Zen),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Endless => "endless",
+            GameMode::Marathon { .. } => "marathon",
+            GameMode::Sprint { .. } => "sprint",
+            GameMode::Cheese { .. } => "cheese",
+            GameMode::Zen => "zen",
+        }
+    }
+
+    /// Returns what has to be cleared to win the game, if anything: the number of lines, or for a cheese
+    /// race the rows of garbage.
+    pub fn goal(self) -> Option<u32> {
+        match self {
+            GameMode::Endless | GameMode::Zen => None,
+            GameMode::Marathon { goal } | GameMode::Sprint { goal } => Some(goal),
+            GameMode::Cheese { rows } => Some(rows),
+        }
+    }
+}
+
+/// When the player may swap the current piece into the hold slot.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum HoldPolicy {
+    /// As often as they like, even several times for one piece.
+    Unlimited,
+    /// Once for each piece placed.
+    #[default]
+    OncePerPiece,
+    /// Once for each piece placed, and no more than the given number of times a game.
+    Limited(u32),
+    /// Never; the hold slot isn't shown.
+    Disabled,
+}
+
+impl HoldPolicy {
+    /// Looks up a policy by name: `unlimited`, `once`, `off`, or a number of holds allowed each game.
+    pub fn from_name(name: &str) -> Option<HoldPolicy> {
+        match name {
+            "unlimited" => Some(HoldPolicy::Unlimited),
+            "once" => Some(HoldPolicy::OncePerPiece),
+            "off" => Some(HoldPolicy::Disabled),
+            _ => name.parse().ok().map(HoldPolicy::Limited),
+        }
+    }
+}
+
+impl fmt::Display for HoldPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HoldPolicy::Unlimited => write!(f, "unlimited"),
+            HoldPolicy::OncePerPiece => write!(f, "once"),
+            HoldPolicy::Limited(holds) => write!(f, "{}", holds),
+            HoldPolicy::Disabled => write!(f, "off"),
+        }
+    }
+}
+
+/// What happens to the cells left above a line clear once the cleared rows are gone.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum ClearGravity {
+    /// The rows above move down by as many rows as were cleared below them, leaving any overhangs in place.
+    #[default]
+    Naive,
+    /// Each group of cells joined by their sides falls as one until it lands, which can complete more rows.
+    Sticky,
+    /// Every cell falls on its own as far as it can, which can complete more rows.
+    Cascade,
+}
+
+impl ClearGravity {
+    pub const ALL: [ClearGravity; 3] = [ClearGravity::Naive, ClearGravity::Sticky, ClearGravity::Cascade];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClearGravity::Naive => "naive",
+            ClearGravity::Sticky => "sticky",
+            ClearGravity::Cascade => "cascade",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ClearGravity> {
+        ClearGravity::ALL.iter().copied().find(|gravity| gravity.name() == name)
+    }
+}
+
+/// How the drop keys behave: how far each press of the soft drop key moves the piece, and whether the hard
+/// drop key can be used at all.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct DropHandling {
+    /// How many rows a press of the soft drop key moves the piece. A held key falls at the same speed
+    /// whatever this is.
+    pub soft_drop_step: u32,
+    /// Whether the hard drop key drops and locks the piece. When it's off the key does nothing.
+    pub hard_drop_enabled: bool,
+}
+
+impl DropHandling {
+    /// Reads the `soft_drop_step` and `hard_drop` values from the `[handling]` section of the config file.
+    /// Values that aren't given keep their defaults.
+    pub fn from_config(config: &toml::Table) -> Result<DropHandling, String> {
+        let handling = match config.get("handling") {
+            Some(toml::Value::Table(handling)) => handling,
+            Some(_) => return Err(String::from("'handling' must be a table")),
+            None => return Ok(DropHandling::default()),
+        };
+
+        let defaults = DropHandling::default();
+        let soft_drop_step = match handling.get("soft_drop_step") {
+            Some(toml::Value::Integer(rows)) if *rows >= 1 => u32::try_from(*rows).unwrap_or(u32::MAX),
+            Some(_) => return Err(String::from("'soft_drop_step' in [handling] must be a number of rows, at least 1")),
+            None => defaults.soft_drop_step,
+        };
+        let hard_drop_enabled = match handling.get("hard_drop") {
+            Some(toml::Value::Boolean(enabled)) => *enabled,
+            Some(_) => return Err(String::from("'hard_drop' in [handling] must be true or false")),
+            None => defaults.hard_drop_enabled,
+        };
+        Ok(DropHandling { soft_drop_step, hard_drop_enabled })
+    }
+}
+
+impl Default for DropHandling {
+    fn default() -> DropHandling {
+        DropHandling { soft_drop_step: 1, hard_drop_enabled: true }
+    }
+}
+
+/// What happened during a call to `Game::step`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StepResult {
+    pub lines_cleared: u32,
+    /// Why the game ended, if the step ended it.
+    pub game_over: Option<GameOver>,
+    /// Points scored during the step.
+    pub reward: u64,
+}
+
+/// The points scored by a clear and what they were for, floating over the board where it happened.
+struct ScorePopup {
+    text: String,
+    row: u32,
+    until: Instant,
+}
+
+/// What an action on the piece can change, saved before it so that it can be undone.
+struct Snapshot {
+    board: Board,
+    piece_bag: PieceBag,
+    piece: Piece,
+    piece_position: Point,
+    held_piece: Option<Piece>,
+    hold_used: bool,
+    holds_used: u32,
+    score: u64,
+    level: u32,
+    duration: u64,
+    lines_cleared: u32,
+    b2b: bool,
+    combo: i32,
+    stats: Stats,
+}
+
+/// The streak a hard-dropped piece leaves through the rows it fell past, fading away behind it.
+struct DropTrail {
+    piece: Piece,
+    x: i32,
+    /// The row of the piece's origin before it dropped, and the row it landed on.
+    rows: (i32, i32),
+    until: Instant,
+}
+
+/// The cells of a piece that just locked, flashing to set it apart from a piece still falling.
+struct LockFlash {
+    cells: Vec<Point>,
+    until: Instant,
+}
+
+/// A piece that locked too recently for invisible mode to have hidden it yet.
+struct RecentLock {
+    piece: Piece,
+    position: Point,
+    until: Instant,
+}
+
+pub struct Game {
+    board: Board,
+    /// The layout the game started from, if it wasn't an empty board, which each restart starts from too.
+    start_board: Option<Board>,
+    piece_bag: PieceBag,
+    piece: Piece,
+    piece_position: Point,
+    /// The kick offset used by the current piece's last rotation, if rotating was the last thing it did.
+    last_kick: Option<(i32, i32)>,
+    held_piece: Option<Piece>,
+    hold_used: bool,
+    hold_policy: HoldPolicy,
+    /// How many times the player has held a piece this game.
+    holds_used: u32,
+    lock_delay: Duration,
+    lock_deadline: Option<Instant>,
+    lock_resets: u32,
+    /// The lowest row the current piece's grid has reached, below which it earns a fresh set of lock resets.
+    lowest_row: i32,
+    lock_reset_limit: u32,
+    clear_gravity: ClearGravity,
+    /// Whether a movement key held as a piece locks keeps shifting the next piece without waiting out the
+    /// delayed auto shift again.
+    das_charge: bool,
+    drop_handling: DropHandling,
+    rotation_system: RotationSystem,
+    /// Whether a piece locking entirely within the hidden rows ends the game.
+    lock_out_enabled: bool,
+    paused: bool,
+    show_ghost: bool,
+    /// Whether a faint grid is drawn in the board's empty cells.
+    show_grid: bool,
+    /// Whether the hidden rows the pieces spawn in are set apart from the playfield when drawn.
+    show_hidden: bool,
+    /// How many upcoming pieces are shown.
+    previews: usize,
+    /// Whether left and right movement and the two rotations are swapped, for left-handed play.
+    mirror_controls: bool,
+    /// Whether leaving the game over screen goes back to a menu rather than quitting.
+    quit_to_menu: bool,
+    /// Whether to count the inputs used to place each piece against the fewest it could have taken.
+    finesse: bool,
+    /// Inputs used on the current piece since it spawned: taps of the movement keys and rotations.
+    finesse_inputs: u32,
+    /// The inputs used on the last piece placed and the fewest it needed, if that could be worked out.
+    last_finesse: Option<(u32, u32)>,
+    /// Pieces placed with more inputs than they needed.
+    finesse_faults: u32,
+    /// When the finesse line stops showing the last placement as a fault.
+    finesse_fault_until: Option<Instant>,
+    /// Whether to show internals useful when debugging, such as the raw speed, and allow undoing.
+    debug: bool,
+    /// The last price read from the price feed and the percentage it moved from the one before.
+    price: Option<(f64, f64)>,
+    /// Whether the last attempt to read the price feed failed, so the price shown is no longer current.
+    price_failed: bool,
+    score: u64,
+    level: u32,
+    /// The level the game started on, which the level never falls below.
+    start_level: u32,
+    /// The level the level stops rising at.
+    max_level: u32,
+    lines_cleared: u32,
+    /// Whether the last line clear was a tetris or T-spin, so another one in a row scores back-to-back.
+    b2b: bool,
+    /// How many placements in a row have cleared lines, minus one; -1 when the last placement cleared none.
+    combo: i32,
+    /// How long a piece takes to fall one row, in milliseconds.
+    duration: u64,
+    /// How many rows the piece falls per tick, derived from `duration`. Values above 1.0 drop several rows
+    /// in a single tick, up to the full board height (20G).
+    gravity: f64,
+    /// Fractional rows of gravity accumulated since the piece last fell.
+    gravity_progress: f64,
+    /// Whether the soft drop key is being held, which most terminals only show by repeating it.
+    soft_dropping: bool,
+    /// Whether the terminal has reported a key going up, so a key can be taken to be held until released.
+    releases_reported: bool,
+    /// When the soft drop key was last pressed or repeated.
+    soft_drop_seen: Option<Instant>,
+    /// Fractional rows of held soft drop accumulated since the piece was last soft dropped.
+    soft_drop_progress: f64,
+    bindings: KeyBindings,
+    line_clear_delay: Duration,
+    /// How long the next piece takes to come in after one locks, on top of any line clear animation.
+    entry_delay: Duration,
+    /// The complete rows waiting to be cleared, if any, and when the next piece comes in, while the line
+    /// clear animation or the entry delay plays.
+    line_clear: Option<(Vec<u32>, Instant)>,
+    /// Gaps of garbage rows that arrived during the line clear animation, to push up once it's over.
+    pending_garbage: Vec<u32>,
+    /// A hold or rotation pressed before the next piece came in, to apply to it as it spawns.
+    initial_action: Option<Action>,
+    /// When the message announcing the last perfect clear comes down.
+    all_clear_until: Option<Instant>,
+    /// The latest clears' score popups that are still up, oldest first.
+    popups: Vec<ScorePopup>,
+    /// Whether to draw cosmetic effects, such as the trails left by hard drops.
+    effects: bool,
+    /// The trails of recent hard drops that are still fading.
+    trails: Vec<DropTrail>,
+    /// Whether the cells of each piece flash as it locks.
+    lock_flash: bool,
+    /// The pieces that locked recently enough to still be flashing.
+    lock_flashes: Vec<LockFlash>,
+    /// How long locked pieces stay in sight before they're hidden, if they are: the invisible mode, where
+    /// the stack is still there to land on and clear but can't be seen.
+    invisible: Option<Duration>,
+    /// The pieces invisible mode hasn't hidden yet.
+    recent_locks: Vec<RecentLock>,
+    /// Until when the board is shown in invisible mode, after being revealed.
+    reveal_until: Option<Instant>,
+    /// When the board can next be revealed in invisible mode.
+    reveal_ready_at: Option<Instant>,
+    /// The state before each of the latest actions on the piece, oldest first, for undoing them.
+    history: VecDeque<Snapshot>,
+    /// Rows of garbage the clears so far would send an opponent, not yet taken by `take_garbage_sent`.
+    garbage_sent: u32,
+    /// The seed the piece sequence was generated from.
+    seed: u64,
+    /// The time the game treats as the present, if set; otherwise the real time is used.
+    clock: Option<Instant>,
+    auto_shift: AutoShift,
+    theme: Theme,
+    /// Colors set for single pieces, kept in the theme through each change of it.
+    piece_colors: PieceColors,
+    stats: Stats,
+    /// Whether the sidebar leaves out the statistics, showing only the score, level and lines.
+    minimal_sidebar: bool,
+    /// How long key presses took to reach the game, shown in debug mode.
+    input_latency: InputLatency,
+    /// How long the game has been played, starting with the player's first move.
+    play_clock: GameClock,
+    mode: GameMode,
+    sound: Box<dyn SoundSink>,
+}
+
+impl Game {
+    /// Creates a game on a board of the given size.
+    pub fn new(config: BoardConfig) -> Game {
+        Game::new_seeded(config, rand::random())
+    }
+
+    /// Creates a game whose sequence of pieces is fully determined by the seed.
+    pub fn new_seeded(config: BoardConfig, seed: u64) -> Game {
+        let mut piece_bag = PieceBag::new_seeded(seed);
+        let piece = piece_bag.pop();
+
+        let mut game = Game {
+            board: Board::with_config(config),
+            start_board: None,
+            piece_bag,
+            piece,
+            piece_position: Point { x: 0, y: 0 },
+            last_kick: None,
+            held_piece: None,
+            hold_used: false,
+            hold_policy: HoldPolicy::default(),
+            holds_used: 0,
+            lock_delay: Duration::from_millis(LOCK_DELAY_MS),
+            lock_deadline: None,
+            lock_resets: 0,
+            lowest_row: 0,
+            lock_reset_limit: LOCK_RESET_LIMIT,
+            clear_gravity: ClearGravity::default(),
+            das_charge: false,
+            drop_handling: DropHandling::default(),
+            rotation_system: RotationSystem::default(),
+            lock_out_enabled: true,
+            paused: false,
+            show_ghost: true,
+            show_grid: false,
+            show_hidden: false,
+            previews: NEXT_PIECE_COUNT,
+            mirror_controls: false,
+            quit_to_menu: false,
+            finesse: false,
+            finesse_inputs: 0,
+            last_finesse: None,
+            finesse_faults: 0,
+            finesse_fault_until: None,
+            debug: false,
+            price: None,
+            price_failed: false,
+            score: 0,
+            level: 1,
+            start_level: 1,
+            max_level: MAX_LEVEL,
+            lines_cleared: 0,
+            b2b: false,
+            combo: -1,
+            duration: 0,
+            gravity: 0.0,
+            gravity_progress: 0.0,
+            soft_dropping: false,
+            releases_reported: false,
+            soft_drop_seen: None,
+            soft_drop_progress: 0.0,
+            bindings: KeyBindings::default(),
+            line_clear_delay: Duration::from_millis(LINE_CLEAR_DELAY_MS),
+            entry_delay: Duration::ZERO,
+            line_clear: None,
+            all_clear_until: None,
+            popups: Vec::new(),
+            effects: false,
+            lock_flash: true,
+            lock_flashes: Vec::new(),
+            trails: Vec::new(),
+            invisible: None,
+            recent_locks: Vec::new(),
+            reveal_until: None,
+            reveal_ready_at: None,
+            history: VecDeque::new(),
+            garbage_sent: 0,
+            initial_action: None,
+            pending_garbage: Vec::new(),
+            seed,
+            clock: None,
+            auto_shift: AutoShift::default(),
+            theme: Theme::default(),
+            piece_colors: PieceColors::default(),
+            stats: Stats::new(),
+            minimal_sidebar: false,
+            input_latency: InputLatency::new(),
+            play_clock: GameClock::new(),
+            mode: GameMode::default(),
+            sound: Box::new(NoSound),
+        };
+
+        game.set_duration(gravity_for_level(game.level));
+        // The board is empty, so the first piece always fits.
+        let _ = game.place_new_piece();
+        game
+    }
+
+    /// Returns the new position of the current piece if it were to be dropped, or None if it overlaps the
+    /// stack where it is and can't be placed at all.
+    fn find_dropped_position(&self) -> Option<Point> {
+        if self.board.collision_test(&self.piece, self.piece_position) {
+            return None;
+        }
+        let mut origin = self.piece_position;
+        while !self.board.collision_test(&self.piece, Point { x: origin.x, y: origin.y + 1 }) {
+            origin.y += 1;
+        }
+        Some(origin)
+    }
+
+    /// Draws the game to the display.
+    pub fn render(&self, display: &mut Display) {
+        let (width, height) = self.board.config().display_size();
+        let scale = self.board.scale();
+
+        // Render the board
+        let hide_stack = self.hides_stack();
+        self.board.render(display, &self.theme, self.show_hidden, hide_stack, self.show_grid);
+        if hide_stack {
+            for lock in self.recent_locks.iter().filter(|lock| self.now() < lock.until) {
+                let origin = Point {
+                    x: 1 + 2 * scale as i32 * lock.position.x,
+                    y: scale as i32 * lock.position.y,
+                };
+                self.render_piece(display, &lock.piece, origin, scale);
+            }
+        }
+        // Flash the pieces that just locked. Any of their cells in rows being cleared are drawn over by the
+        // line clear animation below.
+        for flash in self.lock_flashes.iter().filter(|flash| self.now() < flash.until) {
+            for cell in &flash.cells {
+                let (x, y) = (cell.x as u32, cell.y as u32);
+                draw_block(display, "  ", 1 + x * 2 * scale, y * scale, scale, Color::White, Color::White);
+            }
+        }
+
+        // Render the level
+        let left_margin = width + 5;
+        let level_line = format!("Level: {}", self.level);
+        display.set_text(&level_line, left_margin, 3, self.theme.text, self.theme.background);
+        let score_line = format!("Score: {}", self.score);
+        display.set_text(&score_line, left_margin, 4, self.theme.text, self.theme.background);
+        let speed_line = if self.debug {
+            format!("Speed: {} ({}ms)", format_speed(self.duration), self.duration)
+        } else {
+            format!("Speed: {}", format_speed(self.duration))
+        };
+        display.set_text(&speed_line, left_margin, 5, self.theme.text, self.theme.background);
+        let price_line = match self.price {
+            _ if self.price_failed => Some(String::from("Price: unavailable, retrying")),
+            Some((price, percentage_change)) => Some(format!("Price: {:.2} ({:+.2}%)", price, percentage_change)),
+            None => None,
+        };
+        if let Some(price_line) = price_line {
+            display.set_text(&price_line, left_margin, 2, self.theme.text, self.theme.background);
+        }
+
+        // Render the progress towards the mode's goal, with the time up top in a race
+        let goal_line = match self.mode {
+            GameMode::Marathon { goal } | GameMode::Sprint { goal } => {
+                Some(format!("Lines: {}/{}", self.lines_cleared, goal))
+            }
+            GameMode::Cheese { .. } => Some(format!("Garbage left: {}", self.board.garbage_rows())),
+            // The minimal sidebar has nowhere else to count the lines.
+            GameMode::Zen => Some(format!("Lines: {}", self.lines_cleared)),
+            GameMode::Endless if self.minimal_sidebar => Some(format!("Lines: {}", self.lines_cleared)),
+            GameMode::Endless => None,
+        };
+        if let Some(goal_line) = goal_line {
+            display.set_text(&goal_line, left_margin, 6, self.theme.text, self.theme.background);
+        }
+        if let GameMode::Sprint { .. } | GameMode::Cheese { .. } = self.mode {
+            let time_line = format!("Time: {}", format_time(self.play_time()));
+            display.set_text(&time_line, left_margin, 1, self.theme.text, self.theme.background);
+        }
+
+        let x = 1 + (2 * scale as i32 * self.piece_position.x);
+        let y = scale as i32 * self.piece_position.y;
+        if let Some((rows, until)) = &self.line_clear {
+            // Flash the lines being cleared; the piece that completed them is already part of the board
+            let remaining = until.saturating_duration_since(self.now()).as_millis() as u64;
+            let color = if (remaining / LINE_CLEAR_FLASH_MS) % 2 == 0 {
+                Color::White
+            } else {
+                self.theme.background
+            };
+            for &row in rows {
+                for y in row * scale..(row + 1) * scale {
+                    display.set_text(&" ".repeat(width as usize), 1, y, color, color);
+                }
+            }
+        } else {
+            self.render_trails(display);
+
+            // Render a ghost piece, underneath the falling piece where the two overlap
+            // unless the piece is stuck overlapping the stack, when there's nowhere for it to land
+            if self.show_ghost {
+                if let Some(ghost_position) = self.find_dropped_position() {
+                    self.render_ghost_piece(
+                        display,
+                        &self.piece,
+                        Point {
+                            x,
+                            y: scale as i32 * ghost_position.y,
+                        },
+                        scale,
+                    );
+                }
+            }
+
+            // Render the currently falling piece
+            self.render_piece(display, &self.piece, Point { x, y }, scale);
+        }
+
+        // Render the points scored by recent clears over the board, dimmed as they fade
+        let now = self.now();
+        for popup in self.popups.iter().filter(|popup| now < popup.until) {
+            let remaining = popup.until.saturating_duration_since(now).as_millis() as u64;
+            let color = if remaining > SCORE_POPUP_MS / 2 {
+                self.theme.text
+            } else {
+                Color::Gray
+            };
+            let x = 1 + width.saturating_sub(popup.text.len() as u32) / 2;
+            display.set_text(&popup.text, x, popup.row * scale, color, self.theme.background);
+        }
+
+        // Render the next pieces, in a column of boxes sharing their borders
+        if self.previews > 0 {
+            display.set_text("Next pieces:", left_margin, 7, self.theme.text, self.theme.background);
+        }
+        for (i, next_piece) in self.piece_bag.peek_n(self.previews).iter().enumerate() {
+            let box_origin = Point {
+                x: (left_margin as i32) + 2,
+                y: 9 + 3 * i as i32,
+            };
+            self.render_box(display, box_origin, PREVIEW_BOX_SIZE);
+            self.render_piece_in_box(display, next_piece, box_origin, PREVIEW_BOX_SIZE);
+        }
+
+        // Render the combo and back-to-back status
+        let hold_margin = left_margin + 16;
+        if self.combo > 0 {
+            let combo_line = format!("Combo: {}", self.combo);
+            display.set_text(&combo_line, hold_margin, 13, self.theme.text, self.theme.background);
+        }
+        if self.b2b {
+            display.set_text("Back-to-back", hold_margin, 14, self.theme.text, self.theme.background);
+        }
+        if self.all_clear_until.is_some_and(|until| self.now() < until) {
+            display.set_text("ALL CLEAR!", hold_margin, 15, self.theme.text, self.theme.background);
+        }
+
+        // Render the held piece, along with how many holds are left if they're limited
+        if self.hold_policy != HoldPolicy::Disabled {
+            display.set_text("Hold piece:", hold_margin, 7, self.theme.text, self.theme.background);
+            let box_origin = Point {
+                x: (hold_margin as i32) + 2,
+                y: 9,
+            };
+            self.render_box(display, box_origin, PREVIEW_BOX_SIZE);
+            if let Some(held_piece) = &self.held_piece {
+                self.render_piece_in_box(display, held_piece, box_origin, PREVIEW_BOX_SIZE);
+            }
+        }
+        if let HoldPolicy::Limited(holds) = self.hold_policy {
+            let holds_line = format!("Holds left: {}", holds.saturating_sub(self.holds_used));
+            display.set_text(&holds_line, hold_margin, 12, self.theme.text, self.theme.background);
+        }
+
+        // Render the statistics
+        let stats_margin = hold_margin + 16;
+        let now = self.now();
+        let play_time = self.play_clock.elapsed(now);
+        let elapsed = play_time.as_secs();
+        let stats_lines = [
+            format!("Time: {}:{:02}", elapsed / 60, elapsed % 60),
+            format!("Pieces: {}", self.stats.total_pieces()),
+            format!("PPS: {:.2}", self.stats.pieces_per_second(play_time)),
+        ];
+        if !self.minimal_sidebar {
+            for (i, line) in stats_lines.iter().enumerate() {
+                display.set_text(line, stats_margin, 3 + i as u32, self.theme.text, self.theme.background);
+            }
+            for (i, (kind, count)) in self.stats.counts().enumerate() {
+                let line = format!("{}: {}", kind.name(), count);
+                display.set_text(&line, stats_margin, 7 + i as u32, self.theme.text, self.theme.background);
+            }
+        }
+
+        // Render the inputs the last piece took against the fewest it needed, in red after a fault
+        if self.finesse {
+            let finesse_line = match self.last_finesse {
+                Some((inputs, optimal)) => format!("Finesse: {}/{}", inputs, optimal),
+                None => String::from("Finesse: -"),
+            };
+            let color = if self.finesse_fault_until.is_some_and(|until| now < until) {
+                Color::Red
+            } else {
+                self.theme.text
+            };
+            display.set_text(&finesse_line, stats_margin, 15, color, self.theme.background);
+            let faults_line = format!("Faults: {}", self.finesse_faults);
+            display.set_text(&faults_line, stats_margin, 16, self.theme.text, self.theme.background);
+        }
+
+        // Render how long until the board can be revealed again in invisible mode
+        if self.invisible.is_some() {
+            let reveal_line = match self.reveal_ready_at.filter(|&ready_at| now < ready_at) {
+                Some(ready_at) => format!("Reveal: {}s", ready_at.duration_since(now).as_secs_f64().ceil()),
+                None => String::from("Reveal: ready"),
+            };
+            display.set_text(&reveal_line, stats_margin, 18, self.theme.text, self.theme.background);
+        }
+
+        // Render how long key presses take to reach the game
+        if let Some(average) = self.input_latency.average().filter(|_| self.debug) {
+            let latency_lines = [("avg", average), ("max", self.input_latency.max())]
+                .map(|(name, latency)| format!("Input {}: {:.2}ms", name, latency.as_secs_f64() * 1000.0));
+            for (i, line) in latency_lines.iter().enumerate() {
+                display.set_text(line, stats_margin, 20 + i as u32, self.theme.text, self.theme.background);
+            }
+        }
+
+        if self.paused {
+            let text = "PAUSED";
+            let x = 1 + width.saturating_sub(text.len() as u32) / 2;
+            display.set_text(text, x, height / 2, self.theme.text, self.theme.background);
+        }
+    }
+
+    /// Draws the finished game with a box over the board showing why the game ended and the final stats,
+    /// along with the high score table. While `name_entry` is set the box prompts for a high score name.
+    /// A won sprint also shows how its time compares to `previous_best`.
+    pub fn render_game_over(
+        &self,
+        display: &mut Display,
+        reason: GameOver,
+        name_entry: Option<&str>,
+        highscores: &[ScoreEntry],
+        previous_best: Option<Duration>,
+    ) {
+        self.render(display);
+        // The stack is shown once the game is over, even in invisible mode.
+        self.board.render(display, &self.theme, self.show_hidden, false, self.show_grid);
+        let (width, height) = self.board.config().display_size();
+
+        let inner_width = (width - 2) as usize;
+        let mut lines = vec![
+            format!("{:^width$}", format!("{}!", reason), width = inner_width),
+            String::new(),
+            format!(" Score: {}", self.score),
+            format!(" Level: {}", self.level),
+            format!(" Lines: {}", self.lines_cleared),
+            format!(" Time: {}", format_time(self.play_time())),
+        ];
+        if let GameMode::Cheese { .. } = self.mode {
+            lines.push(format!(" Pieces: {}", self.stats.total_pieces()));
+        }
+        if let (GameOver::Victory, GameMode::Sprint { .. }) = (reason, self.mode) {
+            match previous_best {
+                Some(best) if best.as_millis() <= self.play_time().as_millis() => {
+                    lines.push(format!(" Best: {}", format_time(best)));
+                }
+                _ => lines.push(String::from(" New best time!")),
+            }
+        }
+        lines.push(String::new());
+        match name_entry {
+            Some(name) => {
+                lines.push(String::from(" New high score!"));
+                lines.push(format!(" Name: {:_<3}", name));
+            }
+            None => {
+                lines.push(String::from(" r: restart"));
+                lines.push(String::from(if self.quit_to_menu { " q: menu" } else { " q: quit" }));
+            }
+        }
+
+        let border = format!("+{}+", "-".repeat(inner_width));
+        // On a short board the box is pushed down so its top border stays on screen.
+        let top = (height.saturating_sub(lines.len() as u32) / 2).max(1);
+        display.set_text(&border, 1, top - 1, self.theme.border, self.theme.background);
+        for (i, line) in lines.iter().enumerate() {
+            let row = format!("|{:<width$}|", line, width = inner_width);
+            display.set_text(&row, 1, top + i as u32, self.theme.text, self.theme.background);
+        }
+        display.set_text(&border, 1, top + lines.len() as u32, self.theme.border, self.theme.background);
+
+        let left_margin = width + 53;
+        display.set_text("High scores:", left_margin, 3, self.theme.text, self.theme.background);
+        for (i, entry) in highscores.iter().enumerate() {
+            let line = format!("{:>2}. {} {:>8}", i + 1, entry.name, entry.score);
+            display.set_text(&line, left_margin, 5 + i as u32, self.theme.text, self.theme.background);
+        }
+        // The seed is shown so the same pieces can be played again with `--seed`.
+        let seed_line = format!("Seed: {}", self.seed);
+        let seed_row = 6 + highscores.len() as u32;
+        display.set_text(&seed_line, left_margin, seed_row, self.theme.text, self.theme.background);
+    }
+
+    /// Draws the trails of recent hard drops through the empty cells each piece fell past, in its color and
+    /// then dimmed as they fade. Cells of the stack and the hidden rows are left alone.
+    fn render_trails(&self, display: &mut Display) {
+        let now = self.now();
+        for trail in self.trails.iter().filter(|trail| now < trail.until) {
+            let remaining = trail.until.saturating_duration_since(now).as_millis() as u64;
+            let color = if remaining > DROP_TRAIL_MS / 2 {
+                self.theme.piece_color(trail.piece.color)
+            } else {
+                Color::Gray
+            };
+            let (start_row, end_row) = trail.rows;
+            let (hidden_rows, scale) = (self.board.hidden_rows() as i32, self.board.scale());
+            trail.piece.each_point(&mut |row, col| {
+                let Ok(x) = u32::try_from(trail.x + col) else {
+                    return;
+                };
+                for y in (start_row + row).max(hidden_rows)..end_row + row {
+                    let y = y as u32;
+                    if self.board.cell(x, y).is_none() {
+                        draw_block(display, "::", 1 + x * 2 * scale, y * scale, scale, color, self.theme.background);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Draws a piece as solid blocks in its color, with its top-left corner at the given display position,
+    /// each cell taking up `scale` rows and pairs of columns. Any part of it above or left of the display is
+    /// left out.
+    fn render_piece(&self, display: &mut Display, piece: &Piece, origin: Point, scale: u32) {
+        let color = self.theme.piece_color(piece.color);
+
+        piece.each_point(&mut |row, col| {
+            let Some((x, y)) = display_position(origin, row, col, scale) else {
+                return;
+            };
+            // The part of the piece still in the hidden rows is dimmed along with them when they're shown.
+            let color = if self.show_hidden && y < self.board.hidden_rows() * scale { color.dim() } else { color };
+            draw_block(display, "  ", x, y, scale, color, color);
+        });
+    }
+
+    /// Draws a piece centred in a box of `box_size` cells whose top-left cell is at `box_origin`. Only the
+    /// piece's filled cells are centred, not the grid it rotates in, so pieces with empty rows or columns in
+    /// their grid don't sit off to one side. Pieces with an odd width are centred to the nearest column.
+    fn render_piece_in_box(&self, display: &mut Display, piece: &Piece, box_origin: Point, box_size: (u32, u32)) {
+        let (mut min, mut max) = (Point { x: i32::MAX, y: i32::MAX }, Point { x: i32::MIN, y: i32::MIN });
+        piece.each_point(&mut |row, col| {
+            min = Point { x: min.x.min(col), y: min.y.min(row) };
+            max = Point { x: max.x.max(col), y: max.y.max(row) };
+        });
+        if min.x > max.x {
+            return;
+        }
+
+        // Offsets are worked out in display columns, two to a cell, so a three-wide piece in a four-wide box
+        // can sit half a cell in.
+        let (piece_width, piece_height) = (2 * (max.x - min.x + 1), max.y - min.y + 1);
+        let origin = Point {
+            x: box_origin.x + (2 * box_size.0 as i32 - piece_width) / 2 - 2 * min.x,
+            y: box_origin.y + (box_size.1 as i32 - piece_height) / 2 - min.y,
+        };
+        self.render_piece(display, piece, origin, 1);
+    }
+
+    /// Draws a border around a box of `box_size` cells whose top-left cell is at `box_origin`.
+    fn render_box(&self, display: &mut Display, box_origin: Point, box_size: (u32, u32)) {
+        let (Ok(x), Ok(y)) = (u32::try_from(box_origin.x - 1), u32::try_from(box_origin.y - 1)) else {
+            return;
+        };
+        let (width, height) = (box_size.0 * 2, box_size.1);
+        let edge = format!("+{}+", "-".repeat(width as usize));
+        display.set_text(&edge, x, y, self.theme.border, self.theme.background);
+        display.set_text(&edge, x, y + height + 1, self.theme.border, self.theme.background);
+        for row in 1..=height {
+            display.set_text("|", x, y + row, self.theme.border, self.theme.background);
+            display.set_text("|", x + width + 1, y + row, self.theme.border, self.theme.background);
+        }
+    }
+
+    /// Draws a piece as an outline of brackets in its color, so it reads as a ghost rather than a solid piece.
+    fn render_ghost_piece(&self, display: &mut Display, piece: &Piece, origin: Point, scale: u32) {
+        // The ghost is drawn in a darker shade of the piece's color, so the piece itself stands out.
+        let color = self.theme.piece_color(piece.color).dim();
+
+        piece.each_point(&mut |row, col| {
+            let Some((x, y)) = display_position(origin, row, col, scale) else {
+                return;
+            };
+            draw_block(display, "[]", x, y, scale, color, self.theme.background);
+        });
+    }
+
+    /// Moves the current piece in the specified direction. Returns true if the piece could be moved and
+    /// didn't collide.
+    fn move_piece(&mut self, x: i32, y: i32) -> bool {
+        let new_position = Point {
+            x: self.piece_position.x + x,
+            y: self.piece_position.y + y,
+        };
+        if self.board.collision_test(&self.piece, new_position) {
+            false
+        } else {
+            self.piece_position = new_position;
+            self.last_kick = None;
+            // Falling to a row it hasn't reached before earns the piece a fresh set of lock resets. Falling
+            // back to one after being kicked up doesn't, so a piece can't be kept in play forever.
+            if new_position.y > self.lowest_row {
+                self.lowest_row = new_position.y;
+                self.lock_resets = 0;
+            }
+            self.reset_lock_delay();
+            true
+        }
+    }
+
+    /// Rotates the current piece in the specified direction, trying each of the rotation system's wall kicks
+    /// in turn until one fits. Returns true if the piece could be rotated without any collisions.
+    fn rotate_piece(&mut self, direction: Direction) -> bool {
+        let mut new_piece = self.piece.clone();
+        new_piece.rotate(direction);
+
+        for (x, y) in self.rotation_system.kicks().kick_offsets(&self.piece, direction) {
+            let new_position = Point {
+                x: self.piece_position.x + x,
+                y: self.piece_position.y + y,
+            };
+            if !self.board.collision_test(&new_piece, new_position) {
+                self.piece = new_piece;
+                self.piece_position = new_position;
+                self.last_kick = Some((x, y));
+                self.reset_lock_delay();
+                self.sound.play_rotate();
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Positions the current piece at the top of the board, turned the way the rotation system spawns it,
+    /// first holding or rotating it if the player asked to while waiting for it. Fails with
+    /// `GameOver::BlockOut` if the piece can't be placed without any collisions.
+    fn place_new_piece(&mut self) -> Result<(), GameOver> {
+        self.piece_position = spawn_position(self.piece.kind(), self.board.width());
+        self.rotation_system.kicks().orient_for_spawn(&mut self.piece);
+        self.hold_used = false;
+        self.finesse_inputs = 0;
+        self.auto_shift.piece_spawned(self.now(), self.das_charge);
+        match self.initial_action.take() {
+            Some(Action::Hold) if self.can_hold() => return self.hold_piece(),
+            // A rotation that doesn't fit even with kicks leaves the piece as it spawned.
+            Some(Action::RotateCW) => {
+                self.finesse_inputs += 1;
+                self.rotate_piece(Direction::Right);
+            }
+            Some(Action::RotateCCW) => {
+                self.finesse_inputs += 1;
+                self.rotate_piece(Direction::Left);
+            }
+            _ => (),
+        }
+
+        self.make_room();
+        if self.board.collision_test(&self.piece, self.piece_position) {
+            return self.lose(GameOver::BlockOut);
+        }
+        self.last_kick = None;
+        self.lock_deadline = None;
+        self.lock_resets = 0;
+        self.lowest_row = self.piece_position.y;
+        Ok(())
+    }
+
+    /// Removes rows from the bottom of the board in zen mode until nothing is left in the hidden rows and the
+    /// current piece fits where it is, so the game goes on where it would otherwise be lost. Does nothing in
+    /// any other mode.
+    fn make_room(&mut self) {
+        if self.mode != GameMode::Zen {
+            return;
+        }
+        let hidden_rows = self.board.hidden_rows() as usize;
+        while !self.board.is_empty()
+            && (self.board.cells[..hidden_rows].iter().flatten().any(|cell| cell.is_some())
+                || self.board.collision_test(&self.piece, self.piece_position))
+        {
+            self.board.remove_bottom_row();
+            self.recent_locks.clear();
+            self.lock_flashes.clear();
+        }
+    }
+
+    /// Returns true if the hold policy allows the current piece to be held.
+    fn can_hold(&self) -> bool {
+        match self.hold_policy {
+            HoldPolicy::Unlimited => true,
+            HoldPolicy::OncePerPiece => !self.hold_used,
+            HoldPolicy::Limited(holds) => !self.hold_used && self.holds_used < holds,
+            HoldPolicy::Disabled => false,
+        }
+    }
+
+    /// Moves the current piece into the hold slot, replacing it with the previously held piece or, on the
+    /// first hold, the next piece from the bag. The hold policy decides whether holding is allowed. Fails if
+    /// the swapped-in piece could not be placed.
+    fn hold_piece(&mut self) -> Result<(), GameOver> {
+        if !self.can_hold() {
+            return Ok(());
+        }
+
+        let next_piece = match self.held_piece.take() {
+            Some(p) => p,
+            None => self.piece_bag.pop(),
+        };
+        // The held piece comes back the way it spawns, however it was turned when it was held.
+        let held = std::mem::replace(&mut self.piece, next_piece);
+        self.held_piece = Some(Piece::of_type(held.kind()));
+
+        self.place_new_piece()?;
+        self.hold_used = true;
+        self.holds_used += 1;
+
+        Ok(())
+    }
+
+    /// Returns true if every cell of the piece at the given origin lies within the hidden rows.
+    fn is_above_visible_rows(&self, piece: &Piece, origin: Point) -> bool {
+        !piece.any_point(&mut |row, _| origin.y + row >= self.board.hidden_rows() as i32)
+    }
+
+    /// Returns true if the current piece is resting on the stack or the floor and cannot move down.
+    fn is_grounded(&self) -> bool {
+        let below = Point {
+            x: self.piece_position.x,
+            y: self.piece_position.y + 1,
+        };
+        self.board.collision_test(&self.piece, below)
+    }
+
+    /// Updates a pending lock after the current piece has moved or rotated. A piece that is able to fall
+    /// again cancels the lock, while a piece still resting on the stack restarts the lock delay until
+    /// the lock reset limit is reached, after which it locks straight away.
+    fn reset_lock_delay(&mut self) {
+        if self.lock_deadline.is_none() {
+            return;
+        }
+
+        if !self.is_grounded() {
+            self.lock_deadline = None;
+        } else if self.lock_resets < self.lock_reset_limit {
+            self.lock_resets += 1;
+            self.lock_deadline = Some(self.now() + self.lock_delay);
+        } else {
+            self.lock_deadline = Some(self.now());
+        }
+    }
+
+    /// Advances the game by moving the current piece down one step, either by gravity or, when `is_gravity`
+    /// is false, by the player soft dropping it, which awards `SOFT_DROP_POINTS`. If the piece cannot move
+    /// down, its lock delay is started. Either way a piece reaching the floor never locks on the spot; it is
+    /// locked later by `lock_if_expired`.
+    pub fn advance_game(&mut self, is_gravity: bool) {
+        if self.line_clear.is_some() {
+            return;
+        }
+        if self.move_piece(0, 1) {
+            if !is_gravity {
+                self.award(SOFT_DROP_POINTS);
+            }
+        } else if self.lock_deadline.is_none() {
+            self.lock_deadline = Some(self.now() + self.lock_delay);
+        }
+    }
+
+    /// Soft drops the piece for a press of the soft drop key, by as many rows as the drop handling's step
+    /// allows. It stops early on reaching the stack, which starts the lock delay as a single row would.
+    fn soft_drop(&mut self) {
+        self.advance_game(false);
+        for _ in 1..self.drop_handling.soft_drop_step {
+            if self.line_clear.is_some() || self.is_grounded() {
+                break;
+            }
+            self.advance_game(false);
+        }
+    }
+
+    /// Soft drops the piece for a tick's worth of a held soft drop key, at `SOFT_DROP_FACTOR` times gravity,
+    /// until the key is released or, on a terminal that doesn't report releases, its repeats stop coming in.
+    fn soft_drop_held(&mut self) {
+        if !self.soft_dropping {
+            return;
+        }
+        let released = !self.releases_reported
+            && self.soft_drop_seen.is_none_or(|seen| self.now().saturating_duration_since(seen) > RELEASE_GAP);
+        if released {
+            self.soft_dropping = false;
+            self.soft_drop_progress = 0.0;
+            return;
+        }
+
+        self.soft_drop_progress += self.gravity * (SOFT_DROP_FACTOR - 1.0);
+        let rows = self.soft_drop_progress.floor().min(self.board.height() as f64);
+        self.soft_drop_progress -= self.soft_drop_progress.floor();
+        for _ in 0..rows as u32 {
+            if self.is_grounded() {
+                break;
+            }
+            self.advance_game(false);
+        }
+    }
+
+    /// Advances the game by one tick of `TICK_MS`, moving the piece for a held movement key, accumulating
+    /// gravity and dropping the current piece one row for each whole row accumulated. Fails if a piece whose
+    /// lock delay ran out ended the game.
+    pub fn tick(&mut self) -> Result<(), GameOver> {
+        // Gravity waits for the line clear animation and the entry delay to finish.
+        if let Some((_, until)) = self.line_clear {
+            if self.now() < until {
+                return Ok(());
+            }
+            return self.finish_line_clear();
+        }
+
+        if let Some((action, shifts)) = self.auto_shift.due_shifts(self.now()) {
+            let dx = if action == Action::MoveLeft { -1 } else { 1 };
+            for _ in 0..shifts {
+                if !self.move_piece(dx, 0) {
+                    break;
+                }
+            }
+        }
+
+        let now = self.now();
+        self.popups.retain(|popup| now < popup.until);
+        self.trails.retain(|trail| now < trail.until);
+        self.recent_locks.retain(|lock| now < lock.until);
+        self.lock_flashes.retain(|flash| now < flash.until);
+
+        self.gravity_progress += self.gravity;
+        let rows = self.gravity_progress.floor();
+        self.gravity_progress -= rows;
+
+        for _ in 0..rows as u32 {
+            self.advance_game(true);
+        }
+        self.soft_drop_held();
+        self.lock_if_expired()
+    }
+
+    /// Locks the current piece if its lock delay has run out, or cancels the lock if the piece is no longer
+    /// resting on anything. Fails if locking the piece ended the game.
+    pub fn lock_if_expired(&mut self) -> Result<(), GameOver> {
+        self.try_lock(false)
+    }
+
+    /// Locks the current piece once its lock delay has run out, or straight away regardless of any lock
+    /// delay when `force` is set. Fails if locking the piece ended the game.
+    fn try_lock(&mut self, force: bool) -> Result<(), GameOver> {
+        if force {
+            self.lock_deadline = None;
+            return self.lock_current_piece();
+        }
+
+        match self.lock_deadline {
+            Some(deadline) if self.now() >= deadline => {
+                if self.is_grounded() {
+                    self.lock_current_piece()
+                } else {
+                    self.lock_deadline = None;
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Locks the current piece into the board, clears any completed lines and sets up the game to drop the
+    /// next piece. Fails with the reason the game ended if the lock or the next piece's spawn loses the game.
+    fn lock_current_piece(&mut self) -> Result<(), GameOver> {
+        let t_spin = self.detect_t_spin();
+        // A piece sticking out past the top of the board has topped out the stack, unless in zen mode, where
+        // the rest of it locks and room is made for the next piece.
+        if !self.board.lock_piece(&self.piece, self.piece_position) && self.mode != GameMode::Zen {
+            return self.lose(GameOver::TopOut);
+        }
+        self.stats.record_lock(self.piece.kind());
+        if let Some(delay) = self.invisible {
+            self.recent_locks.push(RecentLock {
+                piece: self.piece.clone(),
+                position: self.piece_position,
+                until: self.now() + delay,
+            });
+        }
+        if self.lock_flash {
+            self.flash_lock();
+        }
+        if self.finesse {
+            self.check_finesse();
+        }
+        self.sound.play_lock();
+        if self.lock_out_enabled
+            && self.is_above_visible_rows(&self.piece, self.piece_position)
+            && self.mode != GameMode::Zen
+        {
+            return self.lose(GameOver::LockOut);
+        }
+
+        let rows = self.board.full_rows();
+        let lines = rows.len() as u32;
+        let mut points = match t_spin {
+            TSpin::None => score_for_clear(lines, self.level),
+            _ => score_for_t_spin(t_spin, lines, self.level),
+        };
+        let mut back_to_back = false;
+        if lines > 0 {
+            // Tetrises and T-spins are difficult clears, which score half as much again when back-to-back.
+            let difficult = lines >= 4 || t_spin != TSpin::None;
+            if difficult && self.b2b {
+                points = points * 3 / 2;
+                back_to_back = true;
+            }
+            self.b2b = difficult;
+            self.combo += 1;
+            points += COMBO_POINTS * (self.combo as u32) * self.level;
+        } else {
+            self.combo = -1;
+        }
+        self.award(points);
+        self.garbage_sent += garbage_for_clear(t_spin, lines, back_to_back);
+        if let Some(name) = clear_name(t_spin, lines) {
+            let prefix = if back_to_back { "B2B " } else { "" };
+            let row = rows.iter().copied().max().unwrap_or(self.piece_position.y.max(0) as u32);
+            self.show_popup(format!("+{} {}{}", points, prefix, name), row);
+        }
+
+        if lines > 0 {
+            self.add_cleared_lines(lines);
+        }
+        if self.has_won() {
+            self.clear_full_rows();
+            return self.end(GameOver::Victory);
+        }
+
+        // The complete rows flash for a moment before they're cleared, and the next piece comes in after the
+        // entry delay.
+        let delay = self.spawn_delay(lines > 0);
+        if !delay.is_zero() {
+            self.lock_deadline = None;
+            self.line_clear = Some((rows, self.now() + delay));
+            return Ok(());
+        }
+        self.finish_line_clear()
+    }
+
+    /// Returns how long the next piece takes to come in after a lock: the entry delay, after the line clear
+    /// animation if the lock cleared any lines.
+    fn spawn_delay(&self, clearing: bool) -> Duration {
+        let line_clear_delay = if clearing { self.line_clear_delay } else { Duration::ZERO };
+        line_clear_delay + self.entry_delay
+    }
+
+    /// Starts the just-locked current piece's cells flashing, leaving out any in the hidden rows.
+    fn flash_lock(&mut self) {
+        let (origin, hidden_rows) = (self.piece_position, self.board.hidden_rows() as i32);
+        let mut cells = Vec::new();
+        self.piece.each_point(&mut |row, col| {
+            if origin.y + row >= hidden_rows {
+                cells.push(Point { x: origin.x + col, y: origin.y + row });
+            }
+        });
+        self.lock_flashes.push(LockFlash {
+            cells,
+            until: self.now() + Duration::from_millis(LOCK_FLASH_MS),
+        });
+    }
+
+    /// Compares the inputs used to place the current piece with the fewest it could have been placed with,
+    /// counting a fault if it took more.
+    fn check_finesse(&mut self) {
+        let (config, position) = (self.board.config(), self.piece_position);
+        let optimal = finesse::optimal_inputs(config, self.rotation_system, &self.piece, position);
+        self.last_finesse = optimal.map(|optimal| (self.finesse_inputs, optimal));
+        if self.last_finesse.is_some_and(|(inputs, optimal)| inputs > optimal) {
+            self.finesse_faults += 1;
+            self.finesse_fault_until = Some(self.now() + Duration::from_millis(FINESSE_FAULT_MS));
+        }
+    }
+
+    /// Floats the given text over the board at a row, moving it up past any popups already showing there so
+    /// they don't overlap. Only the latest `MAX_SCORE_POPUPS` are kept.
+    fn show_popup(&mut self, text: String, mut row: u32) {
+        let now = self.now();
+        self.popups.retain(|popup| now < popup.until);
+        if self.popups.len() >= MAX_SCORE_POPUPS {
+            self.popups.remove(0);
+        }
+        while row > self.board.hidden_rows() && self.popups.iter().any(|popup| popup.row == row) {
+            row -= 1;
+        }
+        self.popups.push(ScorePopup {
+            text,
+            row,
+            until: now + Duration::from_millis(SCORE_POPUP_MS),
+        });
+    }
+
+    /// Returns true if the game mode's goal has been reached, counting any lines still waiting to be cleared.
+    fn has_won(&self) -> bool {
+        match self.mode {
+            GameMode::Endless | GameMode::Zen => false,
+            GameMode::Marathon { goal } | GameMode::Sprint { goal } => self.lines_cleared >= goal,
+            GameMode::Cheese { .. } => self.board.garbage_rows() == 0,
+        }
+    }
+
+    /// Ends the game for the given reason.
+    fn lose(&mut self, reason: GameOver) -> Result<(), GameOver> {
+        self.sound.play_gameover();
+        self.end(reason)
+    }
+
+    /// Ends the game for the given reason, stopping the clock so the final time stays on screen.
+    fn end(&mut self, reason: GameOver) -> Result<(), GameOver> {
+        self.play_clock.stop(self.now());
+        Err(reason)
+    }
+
+    /// Clears any complete lines and brings in the next piece, ending the line clear animation if one is
+    /// playing. Fails if the stack has grown past the top of the board.
+    fn finish_line_clear(&mut self) -> Result<(), GameOver> {
+        self.line_clear = None;
+        self.clear_full_rows();
+        // In zen mode, where the game can't be topped out, any rows pushed off the top are dropped and the next
+        // piece makes room for itself.
+        let zen = self.mode == GameMode::Zen;
+        for gap in std::mem::take(&mut self.pending_garbage) {
+            if !self.board.push_garbage(gap as usize) && !zen {
+                return self.lose(GameOver::TopOut);
+            }
+        }
+        if self.board.cells[0].iter().any(|cell| cell.is_some()) && !zen {
+            return self.lose(GameOver::TopOut);
+        }
+        self.piece = self.piece_bag.pop();
+
+        self.place_new_piece()
+    }
+
+    /// Adds points to the score, which stays at its highest rather than wrapping around.
+    fn award(&mut self, points: u32) {
+        self.score = self.score.saturating_add(points as u64);
+    }
+
+    /// Counts cleared lines towards the level, speeding the game up as it rises to the level cap.
+    fn add_cleared_lines(&mut self, lines: u32) {
+        self.sound.play_clear(lines);
+        self.lines_cleared += lines;
+        if self.mode == GameMode::Zen {
+            return;
+        }
+        let level = (self.lines_cleared / LINES_PER_LEVEL + 1).min(self.max_level).max(self.start_level);
+        // Only a new level changes the speed, so a clear keeps any speed set since the last one.
+        if level != self.level {
+            self.level = level;
+            self.set_duration(gravity_for_level(level));
+        }
+    }
+
+    /// Removes the complete rows from the board, awarding the perfect clear bonus if that leaves it empty.
+    /// Under sticky or cascade gravity the cells left above then fall, and any rows they complete are
+    /// cleared in a chain, each link scoring as a clear of its own.
+    fn clear_full_rows(&mut self) {
+        let mut lines = self.board.clear_lines().len() as u32;
+        // The rows the pieces still in sight were in have moved, so invisible mode hides them straight away.
+        if lines > 0 {
+            self.recent_locks.clear();
+            self.lock_flashes.clear();
+        }
+        let mut chain = 0;
+        while lines > 0 && self.settle_board() {
+            let rows = self.board.full_rows();
+            let Some(&row) = rows.last() else {
+                break;
+            };
+            chain += 1;
+            let chain_lines = self.board.clear_lines().len() as u32;
+            let points = score_for_clear(chain_lines, self.level);
+            self.award(points);
+            self.garbage_sent += garbage_for_clear(TSpin::None, chain_lines, false);
+            self.add_cleared_lines(chain_lines);
+            self.show_popup(format!("+{} Chain {}", points, chain), row);
+            lines += chain_lines;
+        }
+        if lines > 0 && self.board.is_empty() {
+            self.award(score_for_perfect_clear(lines, self.level));
+            self.all_clear_until = Some(self.now() + Duration::from_millis(ALL_CLEAR_MESSAGE_MS));
+        }
+    }
+
+    /// Lets the cells left above a line clear fall as the clear gravity has them. Returns true if any moved.
+    fn settle_board(&mut self) -> bool {
+        match self.clear_gravity {
+            ClearGravity::Naive => false,
+            ClearGravity::Sticky => self.board.drop_groups(),
+            ClearGravity::Cascade => self.board.drop_cells(),
+        }
+    }
+
+    /// Classifies the current piece's placement using the 3-corner rule: a T piece whose last move was a
+    /// rotation is spun in if at least three of the four cells diagonal to its center are blocked. It is a
+    /// full T-spin if both corners on the side the T points to are blocked, or if it got there with the
+    /// long (1, 2) kick, and a mini T-spin otherwise.
+    fn detect_t_spin(&self) -> TSpin {
+        let kick = match self.last_kick {
+            Some(kick) if self.piece.kind() == PieceType::T => kick,
+            _ => return TSpin::None,
+        };
+
+        let Point { x, y } = self.piece_position;
+        // Corners in clockwise order starting top-left, so the two front corners for rotation state r are
+        // corners r and r + 1.
+        let corners = [(x, y), (x + 2, y), (x + 2, y + 2), (x, y + 2)];
+        let blocked = corners.map(|(cx, cy)| self.board.is_blocked(cx, cy));
+        if blocked.iter().filter(|b| **b).count() < 3 {
+            return TSpin::None;
+        }
+
+        let rotation = self.piece.rotation;
+        let front_blocked = blocked[rotation] && blocked[(rotation + 1) % 4];
+        if front_blocked || (kick.0.abs() == 1 && kick.1.abs() == 2) {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        }
+    }
+
+    /// Drops the current piece to the lowest spot on the board where it fits without collisions and locks
+    /// it immediately, skipping any lock delay, awarding `HARD_DROP_POINTS` for each row travelled.
+    fn drop_piece(&mut self) -> Result<(), GameOver> {
+        let start_row = self.piece_position.y;
+        let mut rows = 0;
+        while self.move_piece(0, 1) {
+            rows += 1;
+        }
+        self.award(rows * HARD_DROP_POINTS);
+        if self.effects && rows > 0 {
+            self.trails.push(DropTrail {
+                piece: self.piece.clone(),
+                x: self.piece_position.x,
+                rows: (start_row, self.piece_position.y),
+                until: self.now() + Duration::from_millis(DROP_TRAIL_MS),
+            });
+        }
+        self.try_lock(true)
+    }
+
+    /// Drops the current piece to the lowest spot it fits in, like `drop_piece`, but leaves it there to be
+    /// moved or rotated, starting its lock delay instead of locking it. Each row travelled awards
+    /// `SOFT_DROP_POINTS`.
+    fn sonic_drop(&mut self) {
+        let mut rows = 0;
+        while self.move_piece(0, 1) {
+            rows += 1;
+        }
+        self.award(rows * SOFT_DROP_POINTS);
+        if self.lock_deadline.is_none() {
+            self.lock_deadline = Some(self.now() + self.lock_delay);
+        }
+    }
+
+    /// Pushes a row of garbage up from the bottom of the board with a gap in the given column, wrapping
+    /// round if it's past the edge. The falling piece is pushed up with the stack if it would otherwise
+    /// overlap it. Garbage arriving while paused is dropped, and garbage arriving during the line clear
+    /// animation waits until it's over. Fails if the garbage pushed the stack or the piece off the top.
+    pub fn push_garbage(&mut self, gap_column: u32) -> Result<(), GameOver> {
+        let gap = gap_column % self.board.width();
+        if self.paused {
+            return Ok(());
+        }
+        if self.line_clear.is_some() {
+            self.pending_garbage.push(gap);
+            return Ok(());
+        }
+
+        if !self.board.push_garbage(gap as usize) && self.mode != GameMode::Zen {
+            return self.lose(GameOver::TopOut);
+        }
+        self.recent_locks.clear();
+        self.lock_flashes.clear();
+        if self.board.collision_test(&self.piece, self.piece_position) {
+            // Zen mode makes room around the piece rather than pushing it up.
+            if self.mode == GameMode::Zen {
+                self.make_room();
+                return Ok(());
+            }
+            self.piece_position.y -= 1;
+            if self.board.collision_test(&self.piece, self.piece_position) {
+                return self.lose(GameOver::TopOut);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pauses or resumes the game. A pending lock delay or line clear animation starts over on resume so
+    /// the piece doesn't lock the moment play continues.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        let now = self.now();
+        if self.paused {
+            self.play_clock.pause(now);
+        } else {
+            self.play_clock.resume(now);
+        }
+        if !self.paused && self.lock_deadline.is_some() {
+            self.lock_deadline = Some(self.now() + self.lock_delay);
+        }
+        if !self.paused {
+            if let Some((rows, _)) = &self.line_clear {
+                let until = self.now() + self.spawn_delay(!rows.is_empty());
+                self.line_clear = Some((rows.clone(), until));
+            }
+        }
+    }
+
+    /// Applies a key press to the game using the action it is bound to. Fails if the resulting action ended
+    /// the game.
+    pub fn keypress(&mut self, key: Key) -> Result<(), GameOver> {
+        match self.action_for(key) {
+            Some(action) => self.perform(action),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies an action as if its key had been pressed. Fails if the action ended the game.
+    pub fn perform(&mut self, action: Action) -> Result<(), GameOver> {
+        match action {
+            // Repeats of a held movement key are paced by the auto shift on each tick instead.
+            Action::MoveLeft | Action::MoveRight if !self.paused => {
+                if self.auto_shift.press(action, self.now()) {
+                    self.apply_action(action)
+                } else {
+                    Ok(())
+                }
+            }
+            // A soft drop key being held drops the piece every tick instead. Until the terminal is seen to
+            // report releases, a key repeating quickly enough is taken to be held; after that, every key is
+            // held from its press.
+            Action::SoftDrop if !self.paused => {
+                let now = self.now();
+                let repeated = if self.releases_reported {
+                    self.soft_dropping
+                } else {
+                    self.soft_drop_seen.is_some_and(|seen| now.saturating_duration_since(seen) <= RELEASE_GAP)
+                };
+                self.soft_drop_seen = Some(now);
+                self.soft_dropping = repeated || self.releases_reported;
+                if repeated {
+                    Ok(())
+                } else {
+                    self.apply_action(action)
+                }
+            }
+            action => self.apply_action(action),
+        }
+    }
+
+    /// Lets go of the key for an action, on a terminal that reports keys going up. A movement key stops
+    /// shifting the piece and the soft drop key stops dropping it as soon as it's released, instead of once
+    /// its repeats stop.
+    pub fn release(&mut self, action: Action) {
+        self.releases_reported = true;
+        self.auto_shift.release(action);
+        if action == Action::SoftDrop {
+            self.soft_dropping = false;
+            self.soft_drop_progress = 0.0;
+        }
+    }
+
+    /// Performs a single player action. While the game is paused every action other than `Pause` is
+    /// ignored. Fails if the action ended the game.
+    fn apply_action(&mut self, action: Action) -> Result<(), GameOver> {
+        if action == Action::Pause {
+            self.toggle_pause();
+            return Ok(());
+        }
+        if self.paused {
+            return Ok(());
+        }
+        // Undoing is only allowed in debug mode, however the action got here.
+        if action == Action::Undo {
+            if self.debug {
+                self.undo();
+            }
+            return Ok(());
+        }
+        // A hold or rotation pressed during the line clear animation or entry delay is saved for the next
+        // piece. Without an entry delay any other key skips the animation, without acting on the piece still
+        // being cleared; an entry delay has to be waited out.
+        if self.line_clear.is_some() {
+            if let Action::Hold | Action::RotateCW | Action::RotateCCW = action {
+                self.initial_action = Some(action);
+                return Ok(());
+            }
+            if !self.entry_delay.is_zero() {
+                return Ok(());
+            }
+            return self.finish_line_clear();
+        }
+        let acts_on_piece = matches!(
+            action,
+            Action::MoveLeft
+                | Action::MoveRight
+                | Action::SoftDrop
+                | Action::RotateCCW
+                | Action::RotateCW
+                | Action::HardDrop
+                | Action::SonicDrop
+                | Action::Hold
+        );
+        if acts_on_piece {
+            // The clock starts with the first move of a piece, so a sprint isn't timed from the first frame.
+            self.play_clock.start(self.now());
+            self.save_snapshot();
+        }
+
+        if let Action::MoveLeft | Action::MoveRight | Action::RotateCCW | Action::RotateCW = action {
+            self.finesse_inputs += 1;
+        }
+
+        match action {
+            Action::MoveLeft => {
+                self.move_piece(-1, 0);
+            }
+            Action::MoveRight => {
+                self.move_piece(1, 0);
+            }
+            Action::SoftDrop => self.soft_drop(),
+            Action::RotateCCW => {
+                self.rotate_piece(Direction::Left);
+            }
+            Action::RotateCW => {
+                self.rotate_piece(Direction::Right);
+            }
+            Action::HardDrop if self.drop_handling.hard_drop_enabled => return self.drop_piece(),
+            Action::HardDrop => (),
+            Action::SonicDrop => self.sonic_drop(),
+            Action::Hold => return self.hold_piece(),
+            Action::ToggleGhost => self.show_ghost = !self.show_ghost,
+            Action::ToggleGrid => self.show_grid = !self.show_grid,
+            Action::CycleTheme => self.theme = self.piece_colors.apply(self.theme.next()),
+            Action::Reveal => self.reveal(),
+            Action::Pause | Action::Undo | Action::Noop => (),
+        };
+
+        Ok(())
+    }
+
+    /// Shows the board for `REVEAL_MS` in invisible mode, unless it was revealed less than
+    /// `REVEAL_COOLDOWN_MS` ago.
+    fn reveal(&mut self) {
+        let now = self.now();
+        if self.invisible.is_none() || self.reveal_ready_at.is_some_and(|ready_at| now < ready_at) {
+            return;
+        }
+        self.reveal_until = Some(now + Duration::from_millis(REVEAL_MS));
+        self.reveal_ready_at = Some(now + Duration::from_millis(REVEAL_COOLDOWN_MS));
+    }
+
+    /// Returns true if invisible mode is hiding the stack, as it does unless the board has just been revealed.
+    fn hides_stack(&self) -> bool {
+        self.invisible.is_some() && self.reveal_until.is_none_or(|until| self.now() >= until)
+    }
+
+    /// Saves the state an action on the piece is about to change, dropping the oldest saved state once
+    /// `UNDO_HISTORY` are kept.
+    fn save_snapshot(&mut self) {
+        if self.history.len() >= UNDO_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(Snapshot {
+            board: self.board.clone(),
+            piece_bag: self.piece_bag.clone(),
+            piece: self.piece.clone(),
+            piece_position: self.piece_position,
+            held_piece: self.held_piece.clone(),
+            hold_used: self.hold_used,
+            holds_used: self.holds_used,
+            score: self.score,
+            level: self.level,
+            duration: self.duration,
+            lines_cleared: self.lines_cleared,
+            b2b: self.b2b,
+            combo: self.combo,
+            stats: self.stats.clone(),
+        });
+    }
+
+    /// Puts the game back the way it was before the last action on the piece, if any are left to undo.
+    /// Anything still pending from after it, such as a lock or a line clear, is dropped.
+    fn undo(&mut self) {
+        let Some(snapshot) = self.history.pop_back() else {
+            return;
+        };
+        self.board = snapshot.board;
+        self.piece_bag = snapshot.piece_bag;
+        self.piece = snapshot.piece;
+        self.piece_position = snapshot.piece_position;
+        self.held_piece = snapshot.held_piece;
+        self.hold_used = snapshot.hold_used;
+        self.holds_used = snapshot.holds_used;
+        self.score = snapshot.score;
+        self.level = snapshot.level;
+        // Levelling up speeds the game up, so undoing it slows it back down.
+        self.set_duration(snapshot.duration);
+        self.lines_cleared = snapshot.lines_cleared;
+        self.b2b = snapshot.b2b;
+        self.combo = snapshot.combo;
+        self.stats = snapshot.stats;
+        self.lock_deadline = None;
+        self.lock_resets = 0;
+        self.lowest_row = self.piece_position.y;
+        self.last_kick = None;
+        self.line_clear = None;
+        self.initial_action = None;
+    }
+
+    /// Advances a headless game by one step: moves the game's clock on by `TICK_MS`, performs the action,
+    /// then lets gravity move the piece down a row, locking it if its lock delay has run out. Needs no
+    /// display or threads, so bots can drive the game directly. Time moves only with the steps, so the
+    /// same actions on the same seed always play out the same way, however fast they are made; the real
+    /// clock is read only for the first step of a game given no time with `set_clock`.
+    pub fn step(&mut self, action: Action) -> StepResult {
+        self.clock = Some(self.now() + Duration::from_millis(TICK_MS));
+        let score = self.score;
+        let lines_cleared = self.lines_cleared;
+
+        let result = self.apply_action(action).and_then(|_| {
+            if !self.paused {
+                self.advance_game(true);
+            }
+            self.lock_if_expired()
+        });
+
+        StepResult {
+            lines_cleared: self.lines_cleared - lines_cleared,
+            game_over: result.err(),
+            reward: self.score.saturating_sub(score),
+        }
+    }
+
+    /// Returns a copy of the locked cells on the board, excluding the falling piece.
+    pub fn board_snapshot(&self) -> Vec<Vec<Option<Color>>> {
+        self.board.cells.clone()
+    }
+
+    /// Draws the board and the falling piece as plain text for debugging, as `Board::to_ascii` does,
+    /// under a line giving the score, level and lines cleared.
+    pub fn to_ascii(&self) -> String {
+        let falling = match self.line_clear {
+            // The next piece isn't in play until the cleared rows are gone.
+            Some(_) => None,
+            None => Some((&self.piece, self.piece_position)),
+        };
+        format!(
+            "score {} level {} lines {}\n{}",
+            self.score,
+            self.level,
+            self.lines_cleared,
+            self.board.to_ascii(falling)
+        )
+    }
+
+    /// Returns the falling piece.
+    pub fn current_piece(&self) -> &Piece {
+        &self.piece
+    }
+
+    /// Returns the position of the falling piece's top-left corner on the board.
+    pub fn current_position(&self) -> Point {
+        self.piece_position
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn start_board(&self) -> Option<&Board> {
+        self.start_board.as_ref()
+    }
+
+    /// Starts the game from the given layout instead of an empty board, as each restart will too. The board
+    /// must be the size the game was created with.
+    pub fn set_board(&mut self, mut board: Board) {
+        board.set_scale(self.board.scale());
+        debug_assert_eq!(board.config(), self.board.config());
+        self.board = board.clone();
+        self.start_board = Some(board);
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    /// Returns the rows of garbage the clears since the last call would send an opponent.
+    pub fn take_garbage_sent(&mut self) -> u32 {
+        std::mem::take(&mut self.garbage_sent)
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn start_level(&self) -> u32 {
+        self.start_level
+    }
+
+    pub fn max_level(&self) -> u32 {
+        self.max_level
+    }
+
+    /// Stops the level rising past `level`, keeping the gravity from getting any faster. A game started on a
+    /// higher level stays on it.
+    pub fn set_max_level(&mut self, level: u32) {
+        self.max_level = level.max(1);
+    }
+
+    /// Starts the game on the given level, between 1 and `MAX_START_LEVEL`, with that level's gravity.
+    pub fn set_start_level(&mut self, level: u32) {
+        self.start_level = level.clamp(1, MAX_START_LEVEL);
+        self.level = self.level.max(self.start_level);
+        self.set_duration(gravity_for_level(self.level));
+    }
+
+    pub fn lines_cleared(&self) -> u32 {
+        self.lines_cleared
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns when the current piece will lock if it stays resting where it is.
+    pub fn lock_deadline(&self) -> Option<Instant> {
+        self.lock_deadline
+    }
+
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    pub fn set_bindings(&mut self, bindings: KeyBindings) {
+        self.bindings = bindings;
+    }
+
+    /// Returns the action a key press performs: the one it is bound to, mirrored if the controls are.
+    /// Undoing is only available in debug mode.
+    pub fn action_for(&self, key: Key) -> Option<Action> {
+        let action = self.bindings.action_for(key).filter(|&action| action != Action::Undo || self.debug)?;
+        Some(if self.mirror_controls { action.mirrored() } else { action })
+    }
+
+    pub fn mirror_controls(&self) -> bool {
+        self.mirror_controls
+    }
+
+    /// Swaps left and right movement and the two rotation directions, on top of the key bindings.
+    pub fn set_mirror_controls(&mut self, mirror_controls: bool) {
+        self.mirror_controls = mirror_controls;
+    }
+
+    /// Returns the inputs used to place the last piece and the fewest it could have taken, if those have
+    /// been counted and could be worked out.
+    pub fn last_finesse(&self) -> Option<(u32, u32)> {
+        self.last_finesse
+    }
+
+    pub fn finesse_faults(&self) -> u32 {
+        self.finesse_faults
+    }
+
+    /// Draws a faint grid in the board's empty cells, or leaves them blank.
+    pub fn set_show_grid(&mut self, show_grid: bool) {
+        self.show_grid = show_grid;
+    }
+
+    /// Sets the hidden rows apart from the playfield when drawing the board, with the border carried on up
+    /// beside them and their contents dimmed.
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+    }
+
+    /// Says that the game was started from a menu, so the game over screen offers to go back to it.
+    pub fn set_quit_to_menu(&mut self, quit_to_menu: bool) {
+        self.quit_to_menu = quit_to_menu;
+    }
+
+    /// Shows the given number of upcoming pieces, up to `MAX_NEXT_PIECES`; none hides the next pieces
+    /// altogether.
+    pub fn set_previews(&mut self, previews: usize) {
+        self.previews = previews.min(MAX_NEXT_PIECES);
+    }
+
+    /// Leaves the piece counts and pace out of the sidebar, showing only the score, level and lines along
+    /// with any hold or preview boxes.
+    pub fn set_minimal_sidebar(&mut self, minimal_sidebar: bool) {
+        self.minimal_sidebar = minimal_sidebar;
+    }
+
+    /// Hides each locked piece once it has been in the stack for `delay`, though it is still there to land on
+    /// and clear; `None` shows the stack as usual.
+    pub fn set_invisible(&mut self, delay: Option<Duration>) {
+        self.invisible = delay;
+    }
+
+    /// Turns the flash of each piece's cells as it locks on or off.
+    pub fn set_lock_flash(&mut self, lock_flash: bool) {
+        self.lock_flash = lock_flash;
+    }
+
+    /// Turns cosmetic effects, such as the trails left by hard drops, on or off.
+    pub fn set_effects(&mut self, effects: bool) {
+        self.effects = effects;
+    }
+
+    /// Turns on counting the inputs used to place each piece against the fewest it needed, for practising
+    /// finesse.
+    pub fn set_finesse(&mut self, finesse: bool) {
+        self.finesse = finesse;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Fixes the time the game treats as the present until the next call, so that applying the same
+    /// updates at the same times always plays out the same way.
+    pub fn set_clock(&mut self, now: Instant) {
+        self.clock = Some(now);
+    }
+
+    fn now(&self) -> Instant {
+        self.clock.unwrap_or_else(Instant::now)
+    }
+
+    pub fn randomizer(&self) -> Randomizer {
+        self.piece_bag.randomizer()
+    }
+
+    /// Switches to a sequence of pieces from the given randomizer, still determined by the game's seed. The
+    /// current piece is replaced with the first of the new sequence, so this is meant for before play starts.
+    pub fn set_randomizer(&mut self, randomizer: Randomizer) {
+        self.piece_bag = PieceBag::with_randomizer(self.seed, randomizer);
+        self.piece = self.piece_bag.pop();
+        let _ = self.place_new_piece();
+    }
+
+    pub fn rotation_system(&self) -> RotationSystem {
+        self.rotation_system
+    }
+
+    /// Sets the rotation system pieces turn and spawn by. The current piece is placed again to face the way
+    /// the new system spawns it, so this is meant for before play starts.
+    pub fn set_rotation_system(&mut self, rotation_system: RotationSystem) {
+        self.rotation_system = rotation_system;
+        let _ = self.place_new_piece();
+    }
+
+    pub fn hold_policy(&self) -> HoldPolicy {
+        self.hold_policy
+    }
+
+    pub fn set_hold_policy(&mut self, hold_policy: HoldPolicy) {
+        self.hold_policy = hold_policy;
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Sets how the game is won. A cheese race fills the bottom of the board with its garbage, each row's
+    /// gap in a different column from the one below so no single piece clears more than one of them.
+    pub fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+        if let GameMode::Cheese { rows } = mode {
+            // The garbage comes from the game's seed, so a recorded game can be replayed.
+            let rng = &mut StdRng::seed_from_u64(self.seed);
+            let rows = rows.min(self.board.height() - self.board.hidden_rows() - 4);
+            let width = self.board.width() as usize;
+            let mut gap = rng.gen_range(0..width);
+            for _ in 0..rows {
+                self.board.push_garbage(gap);
+                gap = (gap + rng.gen_range(1..width)) % width;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn play_clock(&self) -> &GameClock {
+        &self.play_clock
+    }
+
+    /// Returns how long the game has been played, not counting pauses.
+    pub fn play_time(&self) -> Duration {
+        self.play_clock.elapsed(self.now())
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Counts a key press that took `latency` from being read to being applied, for debug mode to show.
+    pub fn record_input_latency(&mut self, latency: Duration) {
+        self.input_latency.record(latency);
+    }
+
+    /// Shows or hides internals useful when debugging, such as the raw speed in milliseconds per row, and
+    /// allows or forbids undoing actions.
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    /// Shows the price that set the game's speed, and the percentage it moved by, beside the board.
+    pub fn set_price(&mut self, price: f64, percentage_change: f64) {
+        self.price = Some((price, percentage_change));
+        self.price_failed = false;
+    }
+
+    /// Shows that the price feed couldn't be read, until the next price comes in.
+    pub fn set_price_failed(&mut self) {
+        self.price_failed = true;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = self.piece_colors.apply(theme);
+    }
+
+    /// Draws the pieces given colors in them rather than the theme's, through any change of theme.
+    pub fn set_piece_colors(&mut self, piece_colors: PieceColors) {
+        self.piece_colors = piece_colors;
+        self.theme = piece_colors.apply(self.theme);
+    }
+
+    pub fn line_clear_delay_ms(&self) -> u64 {
+        self.line_clear_delay.as_millis() as u64
+    }
+
+    /// Sets how long cleared lines flash before they're removed, in milliseconds. Zero clears them at once.
+    pub fn set_line_clear_delay(&mut self, delay_ms: u64) {
+        self.line_clear_delay = Duration::from_millis(delay_ms);
+    }
+
+    pub fn entry_delay_ms(&self) -> u64 {
+        self.entry_delay.as_millis() as u64
+    }
+
+    /// Sets how long the next piece takes to come in after one locks, in milliseconds, as in classic games.
+    /// Gravity waits meanwhile, and a hold or rotation pressed is applied to the piece as it spawns.
+    pub fn set_entry_delay(&mut self, delay_ms: u64) {
+        self.entry_delay = Duration::from_millis(delay_ms);
+    }
+
+    pub fn lock_reset_limit(&self) -> u32 {
+        self.lock_reset_limit
+    }
+
+    /// Sets how many times moving or rotating a piece resting on the stack may restart its lock delay
+    /// before it locks regardless. The count starts over whenever the piece falls a row.
+    pub fn set_lock_reset_limit(&mut self, limit: u32) {
+        self.lock_reset_limit = limit;
+    }
+
+    /// Sets where the game's sound effects are played.
+    pub fn set_sound(&mut self, sound: Box<dyn SoundSink>) {
+        self.sound = sound;
+    }
+
+    pub fn clear_gravity(&self) -> ClearGravity {
+        self.clear_gravity
+    }
+
+    pub fn set_clear_gravity(&mut self, clear_gravity: ClearGravity) {
+        self.clear_gravity = clear_gravity;
+    }
+
+    pub fn das_charge(&self) -> bool {
+        self.das_charge
+    }
+
+    /// Sets whether a movement key held through a piece's lock shifts the next piece straight away, rather
+    /// than after the delayed auto shift.
+    pub fn set_das_charge(&mut self, das_charge: bool) {
+        self.das_charge = das_charge;
+    }
+
+    pub fn drop_handling(&self) -> DropHandling {
+        self.drop_handling
+    }
+
+    /// Sets how far a soft drop key press moves the piece and whether hard drops are allowed.
+    pub fn set_drop_handling(&mut self, drop_handling: DropHandling) {
+        self.drop_handling = drop_handling;
+    }
+
+    pub fn lock_out_enabled(&self) -> bool {
+        self.lock_out_enabled
+    }
+
+    /// Sets whether a piece locking entirely above the visible board ends the game with a lock out. When
+    /// it's off play goes on, unless the stack tops out.
+    pub fn set_lock_out_enabled(&mut self, lock_out_enabled: bool) {
+        self.lock_out_enabled = lock_out_enabled;
+    }
+
+    pub fn auto_shift(&self) -> &AutoShift {
+        &self.auto_shift
+    }
+
+    /// Sets the delayed auto shift and repeat rate used for held movement keys.
+    pub fn set_auto_shift(&mut self, auto_shift: AutoShift) {
+        self.auto_shift = auto_shift;
+    }
+
+    /// Returns how many milliseconds the piece takes to fall one row.
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
+    /// Sets how long a piece takes to fall one row, in milliseconds.
+    pub fn set_duration(&mut self, duration: u64) {
+        self.duration = duration.max(1);
+        self.gravity = (TICK_MS as f64 / self.duration as f64).min(self.board.height() as f64);
+    }
+
+    /// Replaces the game with a fresh one, keeping the player's settings.
+    pub fn restart(&mut self) {
+        self.restart_seeded(rand::random());
+    }
+
+    /// Replaces the game with a fresh one whose pieces come from the given seed, keeping the player's
+    /// settings.
+    pub fn restart_seeded(&mut self, seed: u64) {
+        let bindings = self.bindings.clone();
+        let show_ghost = self.show_ghost;
+        let show_grid = self.show_grid;
+        let mirror_controls = self.mirror_controls;
+        let quit_to_menu = self.quit_to_menu;
+        let finesse = self.finesse;
+        let effects = self.effects;
+        let lock_flash = self.lock_flash;
+        let show_hidden = self.show_hidden;
+        let previews = self.previews;
+        let invisible = self.invisible;
+        let minimal_sidebar = self.minimal_sidebar;
+        let start_board = self.start_board.take();
+        let debug = self.debug;
+        let theme = self.theme;
+        let piece_colors = self.piece_colors;
+        let line_clear_delay = self.line_clear_delay;
+        let entry_delay = self.entry_delay;
+        let lock_reset_limit = self.lock_reset_limit;
+        let das_charge = self.das_charge;
+        let drop_handling = self.drop_handling;
+        let lock_out_enabled = self.lock_out_enabled;
+        let clear_gravity = self.clear_gravity;
+        let mode = self.mode;
+        let hold_policy = self.hold_policy;
+        let start_level = self.start_level;
+        let max_level = self.max_level;
+        let randomizer = self.randomizer();
+        let rotation_system = self.rotation_system;
+        let sound = std::mem::replace(&mut self.sound, Box::new(NoSound));
+        let auto_shift = AutoShift::new(self.auto_shift.das_ms(), self.auto_shift.arr_ms());
+        *self = Game::new_seeded(self.board.config(), seed);
+        // Set first, as the pieces placed from here on spawn by it.
+        self.rotation_system = rotation_system;
+        self.bindings = bindings;
+        self.show_ghost = show_ghost;
+        self.show_grid = show_grid;
+        self.mirror_controls = mirror_controls;
+        self.quit_to_menu = quit_to_menu;
+        self.finesse = finesse;
+        self.effects = effects;
+        self.lock_flash = lock_flash;
+        self.show_hidden = show_hidden;
+        self.previews = previews;
+        self.invisible = invisible;
+        self.minimal_sidebar = minimal_sidebar;
+        self.debug = debug;
+        self.auto_shift = auto_shift;
+        self.theme = theme;
+        self.piece_colors = piece_colors;
+        self.line_clear_delay = line_clear_delay;
+        self.entry_delay = entry_delay;
+        self.lock_reset_limit = lock_reset_limit;
+        self.das_charge = das_charge;
+        self.drop_handling = drop_handling;
+        self.lock_out_enabled = lock_out_enabled;
+        self.clear_gravity = clear_gravity;
+        if let Some(board) = start_board {
+            self.set_board(board);
+        }
+        self.set_mode(mode);
+        self.hold_policy = hold_policy;
+        self.set_start_level(start_level);
+        self.max_level = max_level;
+        self.set_randomizer(randomizer);
+        self.sound = sound;
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new(BoardConfig::default())
+    }
+}
+
+/// Returns where a new piece of the given type enters a board of the given width, as the top-left corner of
+/// its grid. Every piece spawns in its flat orientation in the hidden rows at the top. Following the
+/// guideline, the I and O pieces are centred and the three-wide pieces sit in the middle, rounding left: on
+/// a 10-wide board the I fills columns 3 to 6, the O columns 4 and 5, and the rest columns 3 to 5.
+pub fn spawn_position(piece_type: PieceType, board_width: u32) -> Point {
+    let grid_width = match piece_type {
+        PieceType::I => 4,
+        PieceType::O => 2,
+        _ => 3,
+    };
+    Point {
+        x: (board_width.saturating_sub(grid_width) / 2) as i32,
+        y: 0,
+    }
+}
+
+/// Formats a play time as minutes, seconds and milliseconds, e.g. "1:05.250".
+pub fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+/// Returns where the cell at `row` and `col` of a piece drawn from `origin` goes on the display, two
+/// columns to a cell at a `scale` of 1, or None if that's off the top or left edge.
+fn display_position(origin: Point, row: i32, col: i32, scale: u32) -> Option<(u32, u32)> {
+    let scale = scale as i32;
+    let x = origin.x.checked_add(col.checked_mul(2 * scale)?)?;
+    let y = origin.y.checked_add(row.checked_mul(scale)?)?;
+    Some((u32::try_from(x).ok()?, u32::try_from(y).ok()?))
+}
+
+/// Formats how fast pieces fall when each row takes `duration` milliseconds, in rows per second, e.g.
+/// "2.5 rows/s".
+pub fn format_speed(duration: u64) -> String {
+    format!("{:.1} rows/s", 1000.0 / duration.max(1) as f64)
+}
+
+/// Returns the points awarded for clearing the given number of lines at once on the given level.
+pub fn score_for_clear(lines: u32, level: u32) -> u32 {
+    let base = match lines {
+        0 => 0,
+        1 => 100,
+        2 => 300,
+        3 => 500,
+        _ => 800,
+    };
+    base * level
+}
+
+/// Returns the bonus points awarded for a line clear on the given level that leaves the board empty, on top
+/// of the points for the clear itself.
+pub fn score_for_perfect_clear(lines: u32, level: u32) -> u32 {
+    let base = match lines {
+        0 => 0,
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        _ => 2000,
+    };
+    base * level
+}
+
+/// Returns the points awarded for a T-spin clearing the given number of lines on the given level.
+pub fn score_for_t_spin(t_spin: TSpin, lines: u32, level: u32) -> u32 {
+    let base = match (t_spin, lines) {
+        (TSpin::None, _) => return score_for_clear(lines, level),
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, _) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    };
+    base * level
+}
+
+/// Returns how many rows of garbage a clear sends the opponent in a versus game, following the guideline
+/// table: a double sends 1, a triple 2 and a tetris 4, a T-spin sends 2 for each line, and back-to-back
+/// clears send 1 more.
+pub fn garbage_for_clear(t_spin: TSpin, lines: u32, back_to_back: bool) -> u32 {
+    let rows = match (t_spin, lines) {
+        (_, 0) => return 0,
+        (TSpin::Full, lines) => 2 * lines,
+        (TSpin::Mini, _) | (TSpin::None, 1) => 0,
+        (TSpin::None, 4..) => 4,
+        (TSpin::None, lines) => lines - 1,
+    };
+    rows + back_to_back as u32
+}
+
+/// Returns the name of a placement that scored for its clear or spin, such as "Tetris" or "T-Spin Double", or
+/// None if it scored nothing.
+fn clear_name(t_spin: TSpin, lines: u32) -> Option<String> {
+    let lines_name = match lines {
+        0 => None,
+        1 => Some("Single"),
+        2 => Some("Double"),
+        3 => Some("Triple"),
+        _ => Some("Tetris"),
+    };
+    let spin_name = match t_spin {
+        TSpin::None => return lines_name.map(String::from),
+        TSpin::Mini => "Mini T-Spin",
+        TSpin::Full => "T-Spin",
+    };
+    Some(match lines_name {
+        Some(lines_name) => format!("{} {}", spin_name, lines_name),
+        None => String::from(spin_name),
+    })
+}
+
+/// Returns how long a piece takes to fall one row on the given level, in milliseconds, following the
+/// guideline speed curve of `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds per row.
+pub fn gravity_for_level(level: u32) -> u64 {
+    let n = level.saturating_sub(1) as f64;
+    let seconds = (0.8 - n * 0.007).max(0.0).powf(n);
+    ((seconds * 1000.0) as u64).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BOARD_HEIGHT, HIDDEN_ROWS};
+
+    fn game() -> Game {
+        Game::new_seeded(BoardConfig::default(), 1)
+    }
+
+    /// Returns the board cells the falling piece covers, as (column, row) pairs in order.
+    fn piece_cells(game: &Game) -> Vec<(i32, i32)> {
+        let mut cells = Vec::new();
+        let origin = game.piece_position;
+        game.piece.each_point(&mut |row, col| cells.push((origin.x + col, origin.y + row)));
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Returns a game whose falling piece is of the given type, placed at `position`.
+    fn game_with_piece(kind: PieceType, position: Point) -> Game {
+        let mut game = game();
+        game.piece = Piece::of_type(kind);
+        game.piece_position = position;
+        game
+    }
+
+    /// Returns a game turning pieces by `rotation_system`, whose falling piece is of the given type, placed at
+    /// `position`.
+    fn game_with_piece_turned_by(rotation_system: RotationSystem, kind: PieceType, position: Point) -> Game {
+        let mut game = game();
+        game.set_rotation_system(rotation_system);
+        game.piece = Piece::of_type(kind);
+        game.piece_position = position;
+        game
+    }
+
+    #[test]
+    fn first_hold_takes_the_next_piece() {
+        let mut game = game();
+        let (current, next) = (game.piece.kind(), game.piece_bag.peek_n(1)[0].kind());
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.held_piece.as_ref().map(Piece::kind), Some(current));
+        assert_eq!(game.piece.kind(), next);
+        assert_eq!(game.piece_position, spawn_position(next, game.board.width()));
+    }
+
+    #[test]
+    fn hold_swaps_with_the_held_piece() {
+        let mut game = game();
+        let first = game.piece.kind();
+        game.perform(Action::Hold).unwrap();
+        game.perform(Action::HardDrop).unwrap();
+        let current = game.piece.kind();
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.kind(), first);
+        assert_eq!(game.held_piece.as_ref().map(Piece::kind), Some(current));
+    }
+
+    #[test]
+    fn hold_is_allowed_once_per_piece() {
+        let mut game = game();
+        game.perform(Action::Hold).unwrap();
+        let (current, held) = (game.piece.kind(), game.held_piece.as_ref().map(Piece::kind));
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.kind(), current);
+        assert_eq!(game.held_piece.as_ref().map(Piece::kind), held);
+    }
+
+    #[test]
+    fn held_piece_comes_back_in_its_spawn_orientation() {
+        let mut game = game();
+        game.perform(Action::RotateCW).unwrap();
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.held_piece.as_ref().map(|piece| piece.rotation), Some(0));
+        game.perform(Action::HardDrop).unwrap();
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.rotation, 0);
+        assert_eq!(game.piece.shape, Piece::of_type(game.piece.kind()).shape);
+    }
+
+    #[test]
+    fn i_piece_on_the_floor_kicks_up_off_either_wall() {
+        // A flat I in the bottom row has no room to turn in place, so each turn takes the last kick of its
+        // SRS table, two rows up and a column across.
+        let cases = [
+            (0, Direction::Right, 3),
+            (0, Direction::Left, 0),
+            (6, Direction::Right, 9),
+            (6, Direction::Left, 6),
+        ];
+        for (x, direction, column) in cases {
+            let mut game = game_with_piece(PieceType::I, Point { x, y: 18 });
+            assert!(game.rotate_piece(direction));
+            let expected: Vec<_> = (16..20).map(|row| (column, row)).collect();
+            assert_eq!(piece_cells(&game), expected, "I at column {} turned {:?}", x, direction);
+        }
+    }
+
+    #[test]
+    fn upright_i_piece_kicks_off_either_wall_as_it_lies_flat() {
+        // Standing in the third column of its grid, the I lies in its second row turned left and its third
+        // turned right.
+        let cases = [
+            (-2, Direction::Right, 0, 12),
+            (-2, Direction::Left, 0, 11),
+            (7, Direction::Right, 6, 12),
+            (7, Direction::Left, 6, 11),
+        ];
+        for (x, direction, column, row) in cases {
+            let mut game = game_with_piece(PieceType::I, Point { x, y: 10 });
+            game.piece.rotate(Direction::Right);
+            assert!(game.rotate_piece(direction));
+            let expected: Vec<_> = (column..column + 4).map(|col| (col, row)).collect();
+            assert_eq!(piece_cells(&game), expected, "I at column {} turned {:?}", x + 2, direction);
+        }
+    }
+
+    #[test]
+    fn each_rotation_system_handles_a_t_turned_flat_against_the_wall() {
+        // Standing upright against the left wall, the T has no room to lie flat where it is.
+        let cases = [(RotationSystem::Srs, true), (RotationSystem::Ars, true), (RotationSystem::Classic, false)];
+        for (rotation_system, kicks) in cases {
+            let mut game = game_with_piece_turned_by(rotation_system, PieceType::T, Point { x: -1, y: 10 });
+            game.piece.rotate(Direction::Right);
+            let before = piece_cells(&game);
+            assert_eq!(game.rotate_piece(Direction::Right), kicks, "{:?}", rotation_system);
+            if kicks {
+                assert_eq!(piece_cells(&game), vec![(0, 11), (1, 11), (1, 12), (2, 11)], "{:?}", rotation_system);
+            } else {
+                assert_eq!(piece_cells(&game), before);
+            }
+        }
+    }
+
+    #[test]
+    fn ars_never_kicks_the_i_piece() {
+        let mut game = game_with_piece_turned_by(RotationSystem::Ars, PieceType::I, Point { x: -2, y: 10 });
+        game.piece.rotate(Direction::Right);
+        let before = piece_cells(&game);
+        assert!(!game.rotate_piece(Direction::Right));
+        assert_eq!(piece_cells(&game), before);
+    }
+
+    #[test]
+    fn ars_spawns_the_t_point_down_on_the_bottom_of_its_grid() {
+        for (rotation_system, rotation, point_row) in [(RotationSystem::Srs, 0, 0), (RotationSystem::Ars, 2, 2)] {
+            let mut game = game();
+            game.set_rotation_system(rotation_system);
+            game.piece = Piece::of_type(PieceType::T);
+            game.place_new_piece().unwrap();
+            assert_eq!(game.piece.rotation, rotation, "{:?}", rotation_system);
+            let origin = game.piece_position;
+            let column = origin.x + 1;
+            let mut expected = vec![(column - 1, origin.y + 1), (column, origin.y + 1), (column + 1, origin.y + 1)];
+            expected.push((column, origin.y + point_row));
+            expected.sort_unstable();
+            assert_eq!(piece_cells(&game), expected, "{:?}", rotation_system);
+        }
+    }
+
+    #[test]
+    fn turning_a_piece_up_off_the_floor_runs_out_its_lock_resets() {
+        // Turning on the floor kicks the T up a row at times, and gravity brings it straight back down.
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::T, Point { x: 3, y: floor });
+        game.set_clock(Instant::now());
+        game.lowest_row = floor;
+        let locked = |game: &Game| game.board.cells[BOARD_HEIGHT as usize - 1].iter().any(|cell| cell.is_some());
+        for _ in 0..LOCK_RESET_LIMIT * 4 {
+            if locked(&game) {
+                break;
+            }
+            game.rotate_piece(Direction::Right);
+            game.advance_game(true);
+            game.lock_if_expired().unwrap();
+        }
+        assert!(locked(&game));
+    }
+
+    #[test]
+    fn undo_puts_the_speed_back() {
+        let mut game = game();
+        game.set_debug(true);
+        game.set_duration(500);
+        game.perform(Action::HardDrop).unwrap();
+        game.set_duration(100);
+        game.perform(Action::Undo).unwrap();
+        assert_eq!(game.duration(), 500);
+        assert_eq!(game.gravity, TICK_MS as f64 / 500.0);
+    }
+
+    #[test]
+    fn undo_takes_back_the_piece_counts() {
+        let mut game = game();
+        game.set_debug(true);
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.stats().total_pieces(), 1);
+        game.perform(Action::Undo).unwrap();
+        assert_eq!(game.stats().total_pieces(), 0);
+    }
+
+    #[test]
+    fn undo_does_nothing_outside_debug_mode() {
+        let mut game = game();
+        game.perform(Action::HardDrop).unwrap();
+        let board = game.board_snapshot();
+        game.perform(Action::Undo).unwrap();
+        assert_eq!(game.board_snapshot(), board);
+        assert_eq!(game.stats().total_pieces(), 1);
+    }
+
+    /// Returns a board whose four right-hand columns are filled below the hidden rows, for a piece to lock on
+    /// top of.
+    fn board_with_right_tower() -> Board {
+        let mut rows = vec![".........."; HIDDEN_ROWS as usize];
+        rows.extend(vec!["......####"; (BOARD_HEIGHT - HIDDEN_ROWS) as usize]);
+        rows.join("\n").parse().unwrap()
+    }
+
+    #[test]
+    fn piece_locking_in_the_hidden_rows_locks_out() {
+        let mut game = game_with_piece(PieceType::I, Point { x: 6, y: 0 });
+        game.set_board(board_with_right_tower());
+        assert_eq!(game.perform(Action::HardDrop), Err(GameOver::LockOut));
+    }
+
+    #[test]
+    fn piece_locking_in_the_hidden_rows_plays_on_without_lock_out() {
+        let mut game = game_with_piece(PieceType::I, Point { x: 6, y: 0 });
+        game.set_board(board_with_right_tower());
+        game.set_lock_out_enabled(false);
+        assert_eq!(game.perform(Action::HardDrop), Ok(()));
+        assert!(game.board.cells[1][6..].iter().all(|cell| cell.is_some()));
+        assert_eq!(game.piece_position, spawn_position(game.piece.kind(), game.board.width()));
+    }
+
+    #[test]
+    fn line_clears_score_by_the_guideline_values_times_the_level() {
+        assert_eq!(score_for_clear(0, 1), 0);
+        assert_eq!(score_for_clear(1, 1), 100);
+        assert_eq!(score_for_clear(2, 1), 300);
+        assert_eq!(score_for_clear(3, 1), 500);
+        assert_eq!(score_for_clear(4, 1), 800);
+        assert_eq!(score_for_clear(4, 3), 2400);
+        assert_eq!(score_for_clear(1, 7), 700);
+    }
+
+    #[test]
+    fn clearing_25_lines_reaches_level_3() {
+        let mut game = game();
+        for lines in [4, 4, 4, 4, 4, 4, 1] {
+            game.add_cleared_lines(lines);
+        }
+        assert_eq!(game.lines_cleared(), 25);
+        assert_eq!(game.level(), 3);
+        assert_eq!(game.duration(), gravity_for_level(3));
+    }
+
+    #[test]
+    fn gravity_gets_faster_every_level() {
+        assert_eq!(gravity_for_level(1), 1000);
+        for level in 1..15 {
+            assert!(gravity_for_level(level + 1) < gravity_for_level(level), "level {}", level);
+        }
+    }
+
+    /// Returns a board whose rows from `top` down have their leftmost cell filled, with nothing above.
+    fn board_with_left_column_from(top: u32) -> Board {
+        let mut rows = vec![".........."; top as usize];
+        rows.extend(vec!["#........."; (BOARD_HEIGHT - top) as usize]);
+        rows.join("\n").parse().unwrap()
+    }
+
+    #[test]
+    fn piece_locking_into_the_top_row_tops_out() {
+        // Upright against the left wall, the I reaches from the top row down into the visible rows.
+        let mut game = game_with_piece(PieceType::I, Point { x: -2, y: 0 });
+        game.piece.rotate(Direction::Right);
+        game.set_board(board_with_left_column_from(4));
+        assert_eq!(game.perform(Action::HardDrop), Err(GameOver::TopOut));
+    }
+
+    #[test]
+    fn piece_with_no_room_to_spawn_blocks_out() {
+        let mut game = game();
+        let mut rows = vec!["...####..."; 2];
+        rows.extend(vec![".........."; (BOARD_HEIGHT - 2) as usize]);
+        game.set_board(rows.join("\n").parse().unwrap());
+        assert_eq!(game.place_new_piece(), Err(GameOver::BlockOut));
+    }
+
+    /// Returns a game whose T piece has just been turned into place at `position` in the given rotation state
+    /// with the given kick, on a board built from `rows`, the bottom rows of the board.
+    fn game_with_spun_t(rows: &[&str], rotation: usize, position: Point, kick: (i32, i32)) -> Game {
+        let mut board = vec![".........."; BOARD_HEIGHT as usize - rows.len()];
+        board.extend(rows);
+        let mut game = game_with_piece(PieceType::T, position);
+        game.set_board(board.join("\n").parse().unwrap());
+        while game.piece.rotation != rotation {
+            game.piece.rotate(Direction::Right);
+        }
+        game.last_kick = Some(kick);
+        game
+    }
+
+    #[test]
+    fn t_pointing_into_a_covered_slot_is_a_t_spin_double() {
+        let rows = [".....#....", "###...####", "####.#####"];
+        let mut game = game_with_spun_t(&rows, 2, Point { x: 3, y: BOARD_HEIGHT as i32 - 3 }, (0, 0));
+        assert_eq!(game.detect_t_spin(), TSpin::Full);
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.lines_cleared(), 2);
+        assert_eq!(game.score(), 1200);
+    }
+
+    #[test]
+    fn t_with_one_front_corner_open_is_a_mini_t_spin() {
+        let rows = ["...#......", ".........."];
+        let game = game_with_spun_t(&rows, 0, Point { x: 3, y: BOARD_HEIGHT as i32 - 2 }, (0, 0));
+        assert_eq!(game.detect_t_spin(), TSpin::Mini);
+    }
+
+    #[test]
+    fn long_kick_into_the_slot_makes_a_full_t_spin() {
+        let rows = ["...#......", ".........."];
+        let game = game_with_spun_t(&rows, 0, Point { x: 3, y: BOARD_HEIGHT as i32 - 2 }, (1, 2));
+        assert_eq!(game.detect_t_spin(), TSpin::Full);
+    }
+
+    #[test]
+    fn t_that_was_not_turned_last_is_not_a_t_spin() {
+        let rows = [".....#....", "###...####", "####.#####"];
+        let mut game = game_with_spun_t(&rows, 2, Point { x: 3, y: BOARD_HEIGHT as i32 - 3 }, (0, 0));
+        game.last_kick = None;
+        assert_eq!(game.detect_t_spin(), TSpin::None);
+    }
+
+    #[test]
+    fn t_with_two_corners_blocked_is_not_a_t_spin() {
+        let rows = ["..........", ".........."];
+        let game = game_with_spun_t(&rows, 0, Point { x: 3, y: BOARD_HEIGHT as i32 - 2 }, (0, 0));
+        assert_eq!(game.detect_t_spin(), TSpin::None);
+    }
+
+    #[test]
+    fn t_spins_score_by_lines_and_level() {
+        assert_eq!(score_for_t_spin(TSpin::Full, 1, 1), 800);
+        assert_eq!(score_for_t_spin(TSpin::Full, 2, 1), 1200);
+        assert_eq!(score_for_t_spin(TSpin::Full, 3, 2), 3200);
+        assert_eq!(score_for_t_spin(TSpin::None, 1, 1), score_for_clear(1, 1));
+    }
+
+    #[test]
+    fn soft_drop_onto_the_floor_starts_the_lock_delay_instead_of_locking() {
+        // The O lies in the top two rows of its grid, so this puts it a row above the floor.
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: floor - 1 });
+        let start = Instant::now();
+        game.set_clock(start);
+        game.perform(Action::SoftDrop).unwrap();
+        game.tick().unwrap();
+        assert_eq!(game.piece_position, Point { x: 4, y: floor });
+        assert!(game.board.is_empty());
+
+        // Pressing down again on the floor starts the lock delay rather than locking.
+        game.advance_game(false);
+        game.lock_if_expired().unwrap();
+        assert!(game.board.is_empty());
+        assert_eq!(game.lock_deadline(), Some(start + game.lock_delay));
+
+        game.set_clock(start + game.lock_delay);
+        game.lock_if_expired().unwrap();
+        assert!(!game.board.is_empty());
+    }
+
+    #[test]
+    fn locked_pieces_are_counted_until_the_game_restarts() {
+        let mut game = game();
+        let kind = game.piece.kind();
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.stats().total_pieces(), 1);
+        assert!(game.stats().counts().any(|counted| counted == (kind, 1)));
+        game.restart();
+        assert_eq!(game.stats().total_pieces(), 0);
+    }
+
+    #[test]
+    fn hard_drop_locks_at_once_while_the_lock_delay_runs() {
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: floor });
+        game.set_clock(Instant::now());
+        game.advance_game(true);
+        assert!(game.lock_deadline().is_some());
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.board.cell(4, BOARD_HEIGHT - 1), Some(Color::Yellow));
+        assert_eq!(game.lock_deadline(), None);
+        assert_eq!(game.piece_position, spawn_position(game.piece.kind(), game.board.width()));
+    }
+
+    /// Returns a game whose holds follow `hold_policy`.
+    fn game_holding(hold_policy: HoldPolicy) -> Game {
+        let mut game = game();
+        game.set_hold_policy(hold_policy);
+        game
+    }
+
+    #[test]
+    fn unlimited_holds_can_swap_the_same_piece_back() {
+        let mut game = game_holding(HoldPolicy::Unlimited);
+        let first = game.piece.kind();
+        game.perform(Action::Hold).unwrap();
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.kind(), first);
+    }
+
+    #[test]
+    fn disabled_hold_does_nothing() {
+        let mut game = game_holding(HoldPolicy::Disabled);
+        let first = game.piece.kind();
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.kind(), first);
+        assert!(game.held_piece.is_none());
+    }
+
+    #[test]
+    fn limited_holds_run_out_after_the_last_one() {
+        let mut game = game_holding(HoldPolicy::Limited(2));
+        for _ in 0..2 {
+            let current = game.piece.kind();
+            game.perform(Action::Hold).unwrap();
+            assert_eq!(game.held_piece.as_ref().map(Piece::kind), Some(current));
+            game.perform(Action::HardDrop).unwrap();
+        }
+        let (current, held) = (game.piece.kind(), game.held_piece.as_ref().map(Piece::kind));
+        game.perform(Action::Hold).unwrap();
+        assert_eq!(game.piece.kind(), current);
+        assert_eq!(game.held_piece.as_ref().map(Piece::kind), held);
+    }
+
+    #[test]
+    fn no_holds_at_all_when_limited_to_none() {
+        let mut game = game_holding(HoldPolicy::Limited(0));
+        game.perform(Action::Hold).unwrap();
+        assert!(game.held_piece.is_none());
+    }
+
+    #[test]
+    fn hold_policies_read_back_their_names() {
+        for policy in [HoldPolicy::Unlimited, HoldPolicy::OncePerPiece, HoldPolicy::Limited(3), HoldPolicy::Disabled] {
+            assert_eq!(HoldPolicy::from_name(&policy.to_string()), Some(policy));
+        }
+        assert_eq!(HoldPolicy::from_name("sometimes"), None);
+    }
+
+    /// Returns a game with an I lying on the floor at the right, on a board whose bottom row is taken by
+    /// `bottom`, ready to be hard dropped into place.
+    fn game_with_i_over(bottom: &str) -> Game {
+        let mut rows = vec![".........."; BOARD_HEIGHT as usize - 1];
+        rows.push(bottom);
+        let mut game = game_with_piece(PieceType::I, Point { x: 6, y: BOARD_HEIGHT as i32 - 2 });
+        game.set_board(rows.join("\n").parse().unwrap());
+        game.set_line_clear_delay(0);
+        game
+    }
+
+    #[test]
+    fn line_clear_that_empties_the_board_is_a_perfect_clear() {
+        let mut game = game_with_i_over("######....");
+        game.perform(Action::HardDrop).unwrap();
+        assert!(game.board.is_empty());
+        assert_eq!(game.score(), (score_for_clear(1, 1) + score_for_perfect_clear(1, 1)) as u64);
+        assert!(game.all_clear_until.is_some());
+    }
+
+    #[test]
+    fn line_clear_leaving_cells_behind_is_not_a_perfect_clear() {
+        let mut game = game_with_i_over("######....");
+        game.board.cells[BOARD_HEIGHT as usize - 2][0] = Some(Color::Gray);
+        game.perform(Action::HardDrop).unwrap();
+        assert!(!game.board.is_empty());
+        assert_eq!(game.score(), score_for_clear(1, 1) as u64);
+        assert!(game.all_clear_until.is_none());
+    }
+
+    #[test]
+    fn pieces_spawn_in_the_guideline_columns() {
+        let cases = [
+            (PieceType::I, 3..7),
+            (PieceType::O, 4..6),
+            (PieceType::T, 3..6),
+            (PieceType::S, 3..6),
+            (PieceType::Z, 3..6),
+            (PieceType::J, 3..6),
+            (PieceType::L, 3..6),
+        ];
+        for (kind, columns) in cases {
+            let mut game = game();
+            game.piece = Piece::of_type(kind);
+            game.place_new_piece().unwrap();
+            let mut spawned: Vec<_> = piece_cells(&game).into_iter().map(|(column, _)| column).collect();
+            spawned.dedup();
+            assert_eq!(spawned, columns.collect::<Vec<_>>(), "{:?} piece", kind);
+            // Every piece spawns lying flat, across no more than the two rows at the top.
+            assert!(piece_cells(&game).iter().all(|&(_, row)| row < 2), "{:?} piece", kind);
+        }
+    }
+
+    /// Returns a game that has just locked its first piece and is waiting out an entry delay before the
+    /// next, along with the time the delay ends.
+    fn game_in_entry_delay() -> (Game, Instant) {
+        let mut game = game();
+        let start = Instant::now();
+        game.set_clock(start);
+        game.set_entry_delay(100);
+        game.perform(Action::HardDrop).unwrap();
+        (game, start + Duration::from_millis(100))
+    }
+
+    #[test]
+    fn rotation_pressed_before_the_spawn_turns_the_next_piece() {
+        let (mut game, spawn) = game_in_entry_delay();
+        let next = game.piece_bag.peek_n(1)[0].kind();
+        game.perform(Action::RotateCW).unwrap();
+        game.set_clock(spawn);
+        game.tick().unwrap();
+        assert_eq!(game.piece.kind(), next);
+        assert_eq!(game.piece.rotation, 1);
+    }
+
+    #[test]
+    fn hold_pressed_before_the_spawn_holds_the_next_piece() {
+        let (mut game, spawn) = game_in_entry_delay();
+        let next = game.piece_bag.peek_n(1)[0].kind();
+        game.perform(Action::Hold).unwrap();
+        game.set_clock(spawn);
+        game.tick().unwrap();
+        assert_eq!(game.held_piece.as_ref().map(Piece::kind), Some(next));
+    }
+
+    #[test]
+    fn hold_pressed_before_the_spawn_respects_the_hold_policy() {
+        let (mut game, spawn) = game_in_entry_delay();
+        game.set_hold_policy(HoldPolicy::Disabled);
+        let next = game.piece_bag.peek_n(1)[0].kind();
+        game.perform(Action::Hold).unwrap();
+        game.set_clock(spawn);
+        game.tick().unwrap();
+        assert_eq!(game.piece.kind(), next);
+        assert!(game.held_piece.is_none());
+    }
+
+    #[test]
+    fn garbage_pushing_the_stack_off_the_top_tops_out() {
+        let mut game = game();
+        game.board.cells[0][0] = Some(Color::Gray);
+        assert_eq!(game.push_garbage(3), Err(GameOver::TopOut));
+    }
+
+    #[test]
+    fn garbage_lifts_a_resting_piece_with_the_stack() {
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: floor });
+        game.push_garbage(13).unwrap();
+        assert_eq!(game.piece_position, Point { x: 4, y: floor - 1 });
+        assert_eq!(game.board.cell(3, BOARD_HEIGHT - 1), None);
+    }
+
+    /// Returns a board filled from row `top` down, except for the leftmost column.
+    fn nearly_full_board(top: u32) -> Board {
+        let mut rows = vec![".........."; top as usize];
+        rows.extend(vec![".#########"; (BOARD_HEIGHT - top) as usize]);
+        rows.join("\n").parse().unwrap()
+    }
+
+    #[test]
+    fn ghost_of_a_piece_with_nowhere_to_fall_is_where_the_piece_is() {
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 0 });
+        game.set_board(nearly_full_board(2));
+        assert_eq!(game.find_dropped_position(), Some(Point { x: 4, y: 0 }));
+    }
+
+    #[test]
+    fn piece_overlapping_the_stack_has_no_ghost() {
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 0 });
+        game.set_board(nearly_full_board(1));
+        assert_eq!(game.find_dropped_position(), None);
+        // Drawing the board without a ghost mustn't reach above it.
+        let (width, height) = game.board.config().display_size();
+        game.render(&mut Display::new(width + 100, height + 20));
+    }
+
+    #[test]
+    fn pieces_spawn_at_the_left_edge_of_a_board_narrower_than_them() {
+        assert_eq!(spawn_position(PieceType::I, 2), Point { x: 0, y: 0 });
+        assert_eq!(spawn_position(PieceType::T, 0), Point { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn play_time_leaves_out_the_time_paused() {
+        let mut game = game();
+        let start = Instant::now();
+        game.set_clock(start);
+        game.perform(Action::MoveLeft).unwrap();
+        game.set_clock(start + Duration::from_secs(2));
+        game.toggle_pause();
+        game.set_clock(start + Duration::from_secs(7));
+        game.toggle_pause();
+        game.set_clock(start + Duration::from_secs(8));
+        assert_eq!(game.play_time(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn mirrored_controls_move_the_piece_the_other_way() {
+        let mut game = game();
+        game.set_clock(Instant::now());
+        game.set_mirror_controls(true);
+        let x = game.piece_position.x;
+        game.keypress(Key::Left).unwrap();
+        assert_eq!(game.piece_position.x, x + 1);
+        assert_eq!(game.action_for(Key::Right), Some(Action::MoveLeft));
+        assert_eq!(game.action_for(Key::Space), Some(Action::HardDrop));
+    }
+
+    #[test]
+    fn mirrored_controls_turn_the_piece_the_other_way() {
+        let mut game = game_with_piece(PieceType::T, Point { x: 3, y: 5 });
+        game.set_mirror_controls(true);
+        assert_eq!(game.bindings().action_for(Key::Char('e')), Some(Action::RotateCW));
+        game.keypress(Key::Char('e')).unwrap();
+        assert_eq!(game.piece.rotation, 3);
+    }
+
+    #[test]
+    fn mirroring_applies_on_top_of_rebound_keys() {
+        let config: toml::Table = "[keys]\nmove_left = \"h\"".parse().unwrap();
+        let mut game = game();
+        game.set_bindings(KeyBindings::from_config(&config).unwrap());
+        game.set_mirror_controls(true);
+        assert_eq!(game.action_for(Key::Char('h')), Some(Action::MoveRight));
+    }
+
+    /// Returns how far the piece after the first one has moved right one tick after it spawned, with the
+    /// right key held down since before the first piece was hard dropped.
+    fn shift_after_spawn(das_charge: bool) -> i32 {
+        let mut game = game();
+        game.set_das_charge(das_charge);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        // A press, then the terminal's repeats every 20ms.
+        game.set_clock(start);
+        game.perform(Action::MoveRight).unwrap();
+        game.set_clock(at(20));
+        game.perform(Action::MoveRight).unwrap();
+        game.set_clock(at(40));
+        game.perform(Action::HardDrop).unwrap();
+        let x = game.piece_position.x;
+        game.set_clock(at(60));
+        game.perform(Action::MoveRight).unwrap();
+        game.tick().unwrap();
+        game.piece_position.x - x
+    }
+
+    #[test]
+    fn charged_das_shifts_the_next_piece_as_it_spawns() {
+        assert!(shift_after_spawn(true) > 0);
+        assert_eq!(shift_after_spawn(false), 0);
+    }
+
+    /// Returns how many lines hard dropping an I into a board whose bottom rows are `rows` clears under the
+    /// given clear gravity.
+    fn lines_cleared_with(clear_gravity: ClearGravity, rows: &[&str]) -> u32 {
+        let mut game = game_about_to_clear(clear_gravity, rows);
+        game.perform(Action::HardDrop).unwrap();
+        game.lines_cleared()
+    }
+
+    /// Returns a game with the given rows at the bottom of the board and an I piece lying flat in the right
+    /// four columns of the second of them, ready to be hard dropped.
+    fn game_about_to_clear(clear_gravity: ClearGravity, rows: &[&str]) -> Game {
+        let mut board = vec![".........."; BOARD_HEIGHT as usize - rows.len()];
+        board.extend(rows);
+        let mut game = game();
+        game.set_clear_gravity(clear_gravity);
+        game.set_line_clear_delay(0);
+        game.set_board(board.join("\n").parse().unwrap());
+        game.piece = Piece::of_type(PieceType::I);
+        game.piece_position = Point { x: 6, y: BOARD_HEIGHT as i32 - rows.len() as i32 };
+        game
+    }
+
+    #[test]
+    fn falling_cells_chain_clears_under_cascade_and_sticky_gravity_but_not_naive() {
+        // Clearing the middle row leaves the lone cell above the gap in the bottom row.
+        let rows = [".#........", "######....", "#.########"];
+        assert_eq!(lines_cleared_with(ClearGravity::Naive, &rows), 1);
+        assert_eq!(lines_cleared_with(ClearGravity::Cascade, &rows), 2);
+        assert_eq!(lines_cleared_with(ClearGravity::Sticky, &rows), 2);
+    }
+
+    #[test]
+    fn sticky_gravity_keeps_a_group_together_where_cascade_breaks_it_up() {
+        // The L-shaped group over the gap catches on its foot under sticky gravity, while under cascade
+        // its upright cell drops into the gap on its own.
+        let rows = [".##.......", "..#.......", "######....", "#.########"];
+        assert_eq!(lines_cleared_with(ClearGravity::Cascade, &rows), 2);
+        assert_eq!(lines_cleared_with(ClearGravity::Sticky, &rows), 1);
+    }
+
+    #[test]
+    fn theme_key_cycles_back_round_keeping_the_piece_colors() {
+        let mut game = game();
+        let piece_colors = PieceColors { i: Some(Color::Pink), ..PieceColors::default() };
+        game.set_piece_colors(piece_colors);
+        game.perform(Action::CycleTheme).unwrap();
+        assert_eq!(game.theme().name, Theme::ALL[1].name);
+        assert_eq!(game.theme().i, Color::Pink);
+        for _ in 1..Theme::ALL.len() {
+            game.perform(Action::CycleTheme).unwrap();
+        }
+        assert_eq!(*game.theme(), piece_colors.apply(Theme::ALL[0]));
+    }
+
+    #[test]
+    fn score_near_the_top_saturates_rather_than_wrapping() {
+        let mut game = game();
+        game.score = u64::MAX - 10;
+        game.award(score_for_clear(4, 30));
+        assert_eq!(game.score(), u64::MAX);
+    }
+
+    #[test]
+    fn level_never_passes_the_cap() {
+        let mut game = game();
+        game.set_max_level(12);
+        for _ in 0..100 {
+            game.add_cleared_lines(4);
+        }
+        assert_eq!(game.level(), 12);
+        assert_eq!(game.duration(), gravity_for_level(12));
+    }
+
+    #[test]
+    fn gravity_never_reaches_zero() {
+        assert!((1..1000).all(|level| gravity_for_level(level) >= 1));
+        assert_eq!(gravity_for_level(u32::MAX), 1);
+    }
+
+    #[test]
+    fn gravity_waits_for_the_entry_delay() {
+        let (mut game, spawn) = game_in_entry_delay();
+        game.set_duration(TICK_MS);
+        let (board, kind) = (game.board.clone(), game.piece_bag.peek_n(1)[0].kind());
+        for ms in [0, 50, 99] {
+            game.set_clock(spawn - Duration::from_millis(100 - ms));
+            game.tick().unwrap();
+            assert_eq!(game.board, board);
+            assert!(game.line_clear.is_some());
+        }
+        game.set_clock(spawn);
+        game.tick().unwrap();
+        assert_eq!(game.piece.kind(), kind);
+        assert_eq!(game.piece_position, spawn_position(kind, game.board.width()));
+        game.tick().unwrap();
+        assert_eq!(game.piece_position.y, 1);
+    }
+
+    /// Returns the text of a frame of the game, drawn on a display with room for the whole sidebar.
+    fn rendered_text(game: &Game) -> String {
+        let (width, height) = game.board.config().display_size();
+        let mut display = Display::new(width + 100, height + 20);
+        game.render(&mut display);
+        display.text()
+    }
+
+    #[test]
+    fn blind_play_shows_neither_the_hold_nor_the_next_pieces() {
+        let mut game = game();
+        let text = rendered_text(&game);
+        assert!(text.contains("Hold piece:") && text.contains("Next pieces:") && text.contains("PPS"));
+
+        game.set_hold_policy(HoldPolicy::Disabled);
+        game.set_previews(0);
+        game.set_minimal_sidebar(true);
+        let text = rendered_text(&game);
+        assert!(!text.contains("Hold piece:"));
+        assert!(!text.contains("Next pieces:"));
+        assert!(!text.contains("PPS"));
+        assert!(text.contains("Score"));
+    }
+
+    #[test]
+    fn zen_mode_never_ends() {
+        let mut game = game();
+        game.set_mode(GameMode::Zen);
+        game.set_clock(Instant::now());
+        // Dropping every piece where it spawns soon stacks up past the top, as garbage does from below.
+        for i in 0..500 {
+            assert_eq!(game.perform(Action::HardDrop), Ok(()));
+            if i % 3 == 0 {
+                assert_eq!(game.push_garbage(i), Ok(()));
+            }
+        }
+        assert!(!game.board.collision_test(&game.piece, game.piece_position));
+        assert!(game.board.cells[..HIDDEN_ROWS as usize].iter().flatten().all(|cell| cell.is_none()));
+    }
+
+    #[test]
+    fn same_stack_ends_the_game_outside_zen_mode() {
+        let mut game = game();
+        game.set_clock(Instant::now());
+        let ended = (0..500).any(|_| game.perform(Action::HardDrop).is_err());
+        assert!(ended);
+    }
+
+    #[test]
+    fn soft_drop_step_moves_the_piece_that_many_rows_a_press() {
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 5 });
+        game.set_clock(Instant::now());
+        game.set_drop_handling(DropHandling { soft_drop_step: 2, hard_drop_enabled: true });
+        game.keypress(Key::Down).unwrap();
+        assert_eq!(game.piece_position.y, 7);
+    }
+
+    #[test]
+    fn soft_drop_step_stops_at_the_floor() {
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: floor - 1 });
+        game.set_clock(Instant::now());
+        game.set_drop_handling(DropHandling { soft_drop_step: 2, hard_drop_enabled: true });
+        game.keypress(Key::Down).unwrap();
+        assert_eq!(game.piece_position.y, floor);
+        assert!(game.board.is_empty());
+    }
+
+    #[test]
+    fn space_does_nothing_with_hard_drop_turned_off() {
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 5 });
+        game.set_clock(Instant::now());
+        game.set_drop_handling(DropHandling { soft_drop_step: 1, hard_drop_enabled: false });
+        game.keypress(Key::Space).unwrap();
+        assert_eq!(game.piece_position, Point { x: 4, y: 5 });
+        assert!(game.board.is_empty());
+    }
+
+    #[test]
+    fn drop_handling_reads_the_config() {
+        let config: toml::Table = "[handling]\nsoft_drop_step = 3\nhard_drop = false".parse().unwrap();
+        let drop_handling = DropHandling::from_config(&config).unwrap();
+        assert_eq!(drop_handling, DropHandling { soft_drop_step: 3, hard_drop_enabled: false });
+        assert_eq!(DropHandling::from_config(&toml::Table::new()).unwrap(), DropHandling::default());
+    }
+
+    #[test]
+    fn sonic_drop_reaches_the_floor_without_locking() {
+        let floor = BOARD_HEIGHT as i32 - 2;
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 3 });
+        game.set_clock(Instant::now());
+        game.perform(Action::SonicDrop).unwrap();
+        assert_eq!(game.piece_position, Point { x: 4, y: floor });
+        assert!(game.board.is_empty());
+        assert_eq!(game.piece.kind(), PieceType::O);
+
+        // The piece can still slide along the floor before the lock delay runs out.
+        game.perform(Action::MoveLeft).unwrap();
+        assert_eq!(game.piece_position, Point { x: 3, y: floor });
+        assert!(game.board.is_empty());
+    }
+
+
+    #[test]
+    fn noop_steps_alone_lock_a_piece() {
+        let mut game = game();
+        let first = game.piece.kind;
+        for _ in 0..200 {
+            game.step(Action::Noop);
+            if game.board.cells.iter().flatten().any(Option::is_some) {
+                break;
+            }
+        }
+        let locked = game.board.cells.iter().flatten().filter(|cell| cell.is_some()).count();
+        assert_eq!(locked, 4, "the first {:?} should have locked", first);
+    }
+
+    #[test]
+    fn steps_on_the_same_seed_give_the_same_results() {
+        let actions = [Action::MoveLeft, Action::RotateCW, Action::Noop, Action::HardDrop, Action::MoveRight];
+        let run = || {
+            let mut game = game();
+            (0..500).map(|i| game.step(actions[i % actions.len()])).collect::<Vec<StepResult>>()
+        };
+        assert_eq!(run(), run());
+    }
+
+
+    #[test]
+    fn clearing_a_line_keeps_the_speed_until_the_level_goes_up() {
+        let mut game = game_about_to_clear(ClearGravity::Naive, &["..........", "######...."]);
+        game.set_duration(100);
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.lines_cleared(), 1);
+        assert_eq!(game.duration(), 100);
+
+        let mut game = game_about_to_clear(ClearGravity::Naive, &["..........", "######...."]);
+        game.lines_cleared = LINES_PER_LEVEL - 1;
+        game.set_duration(100);
+        game.perform(Action::HardDrop).unwrap();
+        assert_eq!(game.level(), 2);
+        assert_eq!(game.duration(), gravity_for_level(2));
+    }
+
+
+    #[test]
+    fn sidebar_shows_a_failed_price_read_until_the_next_price() {
+        let mut game = game();
+        game.set_price(100.0, 1.5);
+        assert!(rendered_text(&game).contains("Price: 100.00 (+1.50%)"));
+        game.set_price_failed();
+        let text = rendered_text(&game);
+        assert!(text.contains("Price: unavailable, retrying"));
+        assert!(!text.contains("Price: 100.00"));
+        game.set_price(101.0, 1.0);
+        assert!(rendered_text(&game).contains("Price: 101.00 (+1.00%)"));
+    }
+
+
+    #[test]
+    fn once_releases_are_reported_soft_drop_holds_until_its_key_goes_up() {
+        let start = Instant::now();
+        let mut game = game_with_piece(PieceType::O, Point { x: 4, y: 2 });
+        game.set_duration(1000);
+        game.set_clock(start);
+        game.release(Action::MoveLeft);
+        game.keypress(Key::Down).unwrap();
+        assert_eq!(game.piece_position.y, 3);
+
+        // No repeats arrive, yet the key is still down, so the piece keeps soft dropping.
+        game.set_clock(start + Duration::from_millis(500));
+        for _ in 0..4 {
+            game.tick().unwrap();
+        }
+        assert_eq!(game.piece_position.y, 4);
+
+        game.release(Action::SoftDrop);
+        for _ in 0..10 {
+            game.tick().unwrap();
+        }
+        assert_eq!(game.piece_position.y, 4);
+    }
+}
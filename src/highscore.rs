@@ -0,0 +1,156 @@
+use crate::config;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How many entries the high score table keeps.
+pub const MAX_HIGHSCORES: usize = 10;
+
+/// A finished game recorded in the high score table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: u64,
+    pub level: u32,
+    pub lines: u32,
+    /// When the game finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl ScoreEntry {
+    /// Creates an entry for a game finishing now.
+    pub fn new(name: String, score: u64, level: u32, lines: u32) -> ScoreEntry {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        ScoreEntry {
+            name,
+            score,
+            level,
+            lines,
+            timestamp,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}", self.name, self.score, self.level, self.lines, self.timestamp)
+    }
+
+    fn from_line(line: &str) -> Option<ScoreEntry> {
+        let mut fields = line.split('\t');
+        let entry = ScoreEntry {
+            name: String::from(fields.next()?),
+            score: fields.next()?.parse().ok()?,
+            level: fields.next()?.parse().ok()?,
+            lines: fields.next()?.parse().ok()?,
+            timestamp: fields.next()?.parse().ok()?,
+        };
+
+        if fields.next().is_some() {
+            None
+        } else {
+            Some(entry)
+        }
+    }
+}
+
+/// Returns the default location of the high score file, inside the game's config directory.
+pub fn default_path() -> Option<PathBuf> {
+    Some(config::config_dir()?.join("highscores"))
+}
+
+/// Reads the high score table, best score first. A missing or corrupt file gives an empty table.
+pub fn load_highscores(path: &Path) -> Vec<ScoreEntry> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return Vec::new(),
+    };
+
+    let entries: Option<Vec<ScoreEntry>> = contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ScoreEntry::from_line)
+        .collect();
+
+    let mut entries = entries.unwrap_or_default();
+    sort_highscores(&mut entries);
+    entries
+}
+
+/// Adds an entry to the high score table stored at `path`, keeping only the best `MAX_HIGHSCORES`.
+pub fn save_highscore(path: &Path, entry: ScoreEntry) -> io::Result<()> {
+    let mut entries = load_highscores(path);
+    entries.push(entry);
+    sort_highscores(&mut entries);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let contents: String = entries.iter().map(|e| e.to_line() + "\n").collect();
+    fs::write(path, contents)
+}
+
+/// Returns true if a game with the given score would make it into the high score table.
+pub fn qualifies(entries: &[ScoreEntry], score: u64) -> bool {
+    score > 0 && (entries.len() < MAX_HIGHSCORES || entries.iter().any(|e| score > e.score))
+}
+
+/// Returns the default location of the best sprint times file, next to the high score file.
+pub fn default_sprint_path() -> Option<PathBuf> {
+    Some(config::config_dir()?.join("sprint_times"))
+}
+
+/// Reads the best sprint time for each line goal. A missing or corrupt file gives no times.
+fn load_sprint_times(path: &Path) -> Vec<(u32, u64)> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return Vec::new(),
+    };
+
+    let times: Option<Vec<(u32, u64)>> = contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (goal, millis) = line.split_once('\t')?;
+            Some((goal.parse().ok()?, millis.parse().ok()?))
+        })
+        .collect();
+    times.unwrap_or_default()
+}
+
+/// Returns the best time recorded for a sprint to `goal` lines, if there is one.
+pub fn best_sprint_time(path: &Path, goal: u32) -> Option<Duration> {
+    load_sprint_times(path)
+        .into_iter()
+        .find(|&(g, _)| g == goal)
+        .map(|(_, millis)| Duration::from_millis(millis))
+}
+
+/// Records a sprint to `goal` lines finished in `time`, if it beats the best time for that goal. Returns
+/// true if it was a new best.
+pub fn save_sprint_time(path: &Path, goal: u32, time: Duration) -> io::Result<bool> {
+    let millis = time.as_millis() as u64;
+    let mut times = load_sprint_times(path);
+    match times.iter_mut().find(|(g, _)| *g == goal) {
+        Some((_, best)) if *best <= millis => return Ok(false),
+        Some((_, best)) => *best = millis,
+        None => times.push((goal, millis)),
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let contents: String = times.iter().map(|(goal, millis)| format!("{}\t{}\n", goal, millis)).collect();
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+fn sort_highscores(entries: &mut Vec<ScoreEntry>) {
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then(a.timestamp.cmp(&b.timestamp)));
+    entries.truncate(MAX_HIGHSCORES);
+}
@@ -0,0 +1,204 @@
+use crate::bindings::Key;
+
+/// A key the terminal reports going down, or coming back up. Only some terminals report releases; the rest
+/// send nothing when a key goes up, only repeats for as long as it is held.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum KeyInput {
+    Press(Key),
+    Release(Key),
+}
+
+/// Turns the bytes read from a raw terminal into keys. A key can arrive split across several reads, so
+/// the decoder keeps any incomplete UTF-8 character or escape sequence until the rest of it comes in.
+#[derive(Debug, Default)]
+pub struct KeyDecoder {
+    state: State,
+}
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Ground,
+    /// An escape byte, which may start a sequence.
+    Escape,
+    /// A control sequence (`ESC [`) with the parameter and intermediate bytes read so far.
+    Csi(Vec<u8>),
+    /// A single shift sequence (`ESC O`), sent for F1, and for the arrow keys in application cursor mode.
+    Ss3,
+    /// A multi-byte UTF-8 character, waiting for `needed` more bytes.
+    Utf8 { bytes: Vec<u8>, needed: usize },
+}
+
+impl KeyDecoder {
+    pub fn new() -> KeyDecoder {
+        KeyDecoder::default()
+    }
+
+    /// Feeds the decoder the next bytes read, returning the keys they complete.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Key> {
+        bytes.iter().filter_map(|&byte| self.push(byte)).collect()
+    }
+
+    /// Feeds the decoder a single byte, returning the key it completes, if any.
+    pub fn push(&mut self, byte: u8) -> Option<Key> {
+        match std::mem::take(&mut self.state) {
+            State::Ground => self.start(byte),
+            State::Escape => match byte {
+                b'[' => {
+                    self.state = State::Csi(Vec::new());
+                    None
+                }
+                b'O' => {
+                    self.state = State::Ss3;
+                    None
+                }
+                // Anything else means the escape key was pressed on its own; it isn't used, so it's dropped.
+                _ => self.start(byte),
+            },
+            State::Csi(mut params) => match byte {
+                0x20..=0x3f => {
+                    params.push(byte);
+                    self.state = State::Csi(params);
+                    None
+                }
+                // Only the arrow keys and F1 are understood; other sequences, such as the other function
+                // keys, are read in full and ignored.
+                0x40..=0x7e if params.is_empty() => arrow(byte),
+                b'~' if params == b"11" => Some(Key::F1),
+                0x40..=0x7e => None,
+                // Not a valid sequence; start over from this byte.
+                _ => self.start(byte),
+            },
+            State::Ss3 if byte == b'P' => Some(Key::F1),
+            State::Ss3 => arrow(byte),
+            State::Utf8 { mut bytes, needed } => {
+                if byte & 0xc0 != 0x80 {
+                    return self.start(byte);
+                }
+                bytes.push(byte);
+                if needed > 1 {
+                    self.state = State::Utf8 { bytes, needed: needed - 1 };
+                    return None;
+                }
+                std::str::from_utf8(&bytes).ok()?.chars().next().map(Key::Char)
+            }
+        }
+    }
+
+    /// Handles a byte that starts a new key.
+    fn start(&mut self, byte: u8) -> Option<Key> {
+        let needed = match byte {
+            0x1b => {
+                self.state = State::Escape;
+                return None;
+            }
+            0x03 => return Some(Key::CtrlC),
+            b' ' => return Some(Key::Space),
+            0x00..=0x7f => return Some(Key::Char(byte as char)),
+            0xc0..=0xdf => 1,
+            0xe0..=0xef => 2,
+            0xf0..=0xf7 => 3,
+            // A stray continuation byte or an invalid lead byte.
+            _ => return None,
+        };
+        self.state = State::Utf8 {
+            bytes: vec![byte],
+            needed,
+        };
+        None
+    }
+}
+
+fn arrow(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        _ => None,
+    }
+}
+
+#[cfg(any(windows, feature = "crossterm-input"))]
+pub use self::crossterm_keys::key_from_event;
+
+#[cfg(any(windows, feature = "crossterm-input"))]
+mod crossterm_keys {
+    use super::KeyInput;
+    use crate::bindings::Key;
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+    /// Returns the key a crossterm key event presses or releases, if it's one the game knows. A repeat of a
+    /// held key counts as another press, as it does from a terminal that doesn't report releases.
+    pub fn key_from_event(event: &KeyEvent) -> Option<KeyInput> {
+        let key = match event.code {
+            KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Some(Key::CtrlC),
+            KeyCode::Char(' ') => Some(Key::Space),
+            KeyCode::Char(c) => Some(Key::Char(c)),
+            KeyCode::Up => Some(Key::Up),
+            KeyCode::Down => Some(Key::Down),
+            KeyCode::Left => Some(Key::Left),
+            KeyCode::Right => Some(Key::Right),
+            KeyCode::F(1) => Some(Key::F1),
+            // The keys a raw terminal sends as control characters come through as those characters.
+            KeyCode::Enter => Some(Key::Char('\r')),
+            KeyCode::Tab => Some(Key::Char('\t')),
+            KeyCode::Backspace => Some(Key::Char('\x7f')),
+            _ => None,
+        }?;
+        Some(match event.kind {
+            KeyEventKind::Release => KeyInput::Release(key),
+            KeyEventKind::Press | KeyEventKind::Repeat => KeyInput::Press(key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_sequence_split_across_reads_is_one_key() {
+        let mut decoder = KeyDecoder::new();
+        assert_eq!(decoder.feed(b"\x1b["), vec![]);
+        assert_eq!(decoder.feed(b"A"), vec![Key::Up]);
+        assert_eq!(decoder.feed(b"\x1b"), vec![]);
+        assert_eq!(decoder.feed(b"[Dx"), vec![Key::Left, Key::Char('x')]);
+    }
+
+    #[test]
+    fn multi_byte_characters_decode_whole() {
+        let mut decoder = KeyDecoder::new();
+        let bytes = "é€".as_bytes();
+        assert_eq!(decoder.feed(&bytes[..1]), vec![]);
+        assert_eq!(decoder.feed(&bytes[1..3]), vec![Key::Char('é')]);
+        assert_eq!(decoder.feed(&bytes[3..]), vec![Key::Char('€')]);
+    }
+
+    #[test]
+    fn long_and_application_mode_sequences() {
+        let mut decoder = KeyDecoder::new();
+        assert_eq!(decoder.feed(b"\x1b[11~\x1bOP\x1bOB"), vec![Key::F1, Key::F1, Key::Down]);
+        // F5 is read in full but not used, leaving nothing behind to garble the next key.
+        assert_eq!(decoder.feed(b"\x1b[15~ "), vec![Key::Space]);
+    }
+
+    #[test]
+    fn control_c_and_plain_characters() {
+        let mut decoder = KeyDecoder::new();
+        assert_eq!(decoder.feed(b"\x03q"), vec![Key::CtrlC, Key::Char('q')]);
+    }
+
+    #[cfg(any(windows, feature = "crossterm-input"))]
+    #[test]
+    fn crossterm_releases_come_through_and_repeats_are_presses() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+        let event = |kind| KeyEvent::new_with_kind(KeyCode::Left, KeyModifiers::NONE, kind);
+        assert_eq!(key_from_event(&event(KeyEventKind::Press)), Some(KeyInput::Press(Key::Left)));
+        assert_eq!(key_from_event(&event(KeyEventKind::Repeat)), Some(KeyInput::Press(Key::Left)));
+        assert_eq!(key_from_event(&event(KeyEventKind::Release)), Some(KeyInput::Release(Key::Left)));
+        let home = KeyEvent::new_with_kind(KeyCode::Home, KeyModifiers::NONE, KeyEventKind::Release);
+        assert_eq!(key_from_event(&home), None);
+    }
+}
@@ -0,0 +1,38 @@
+//! The game logic behind tetrust: the board, the pieces and the rules of play, independent of the terminal
+//! the binary runs them in.
+
+mod board;
+mod clock;
+mod finesse;
+mod game;
+mod piece;
+mod rotation;
+mod stats;
+
+pub mod autoshift;
+pub mod bindings;
+pub mod config;
+pub mod display;
+pub mod error;
+pub mod highscore;
+pub mod input;
+pub mod price;
+pub mod replay;
+pub mod sound;
+pub mod theme;
+pub mod tick;
+pub mod util;
+pub mod workers;
+
+pub use board::{Board, BoardConfig, Point, BOARD_HEIGHT, BOARD_WIDTH, HIDDEN_ROWS};
+pub use clock::GameClock;
+pub use error::TetrustError;
+pub use game::{
+    format_speed, format_time, garbage_for_clear, gravity_for_level, score_for_clear, score_for_perfect_clear,
+    score_for_t_spin, spawn_position, ClearGravity, DropHandling, Game, GameMode, GameOver, HoldPolicy, StepResult,
+    TSpin, LINE_CLEAR_DELAY_MS, LOCK_RESET_LIMIT, MAX_LEVEL, MAX_NEXT_PIECES, MAX_START_LEVEL, NEXT_PIECE_COUNT,
+    TICK_MS,
+};
+pub use piece::{Piece, PieceBag, PieceType, Randomizer};
+pub use rotation::RotationSystem;
+pub use stats::{InputLatency, Stats};
@@ -1,647 +1,1038 @@
-mod display;
+mod menu;
+mod options;
 mod terminal;
-mod util;
 
-use display::Display;
-use std::fmt::format;
-use std::sync::mpsc;
+use menu::{Menu, MenuChoice};
+use options::Options;
+use terminal::KeyReader;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use util::*;
-use std::process::{Command, Stdio};
-
-
-const BOARD_WIDTH: u32 = 10;
-const BOARD_HEIGHT: u32 = 20;
-const HIDDEN_ROWS: u32 = 2;
-
-enum Key {
-    Up,
-    Down,
-    Left,
-    Right,
-    Space,
-    CtrlC,
-    Char(char),
+use std::time::{Duration, Instant};
+use tetrust::autoshift::AutoShift;
+use tetrust::bindings::{Action, Key, KeyBindings};
+use tetrust::display::Display;
+use tetrust::highscore::{self, ScoreEntry};
+use tetrust::input::KeyInput;
+use tetrust::price::PriceFeed;
+use tetrust::replay::{Recorder, ReplayEvent};
+use tetrust::theme::{PieceColors, Theme};
+use tetrust::tick::{RealTicks, TickSource};
+use tetrust::workers::{
+    spawn_garbage_thread, spawn_price_thread, spawn_tick_thread, GameUpdate, Workers, SHUTDOWN_POLL_MS,
+};
+use tetrust::{
+    config, gravity_for_level, Board, BoardConfig, DropHandling, Game, GameMode, GameOver, TetrustError,
+    MAX_NEXT_PIECES, TICK_MS,
+};
+
+/// How often a game is drawn, in milliseconds: about 60 frames a second, however often its updates come.
+const FRAME_MS: u64 = 16;
+
+/// Runs the game until the player quits or loses, returning the reason the game was lost, or an error if the
+/// game couldn't go on, such as when the keyboard can no longer be read. Prices from
+/// `price_feed` speed up or slow down the game; with no feed the game runs at a fixed speed. If
+/// `record_path` is set, each game is recorded there for `--replay` when it ends. The game advances on
+/// each tick from `ticks`. The threads feeding the game are stopped and joined before this returns. With a
+/// `garbage_interval`, a row of garbage comes up that many milliseconds apart. Each game opens with a
+/// `countdown`, during which the board is shown but nothing moves; any key but quit skips it.
+fn play(
+    game: &mut Game,
+    display: &mut Display,
+    ticks: impl TickSource + 'static,
+    price_feed: Option<PriceFeed>,
+    record_path: Option<&Path>,
+    garbage_interval: Option<u64>,
+    countdown: Duration,
+) -> Result<Option<GameOver>, TetrustError> {
+    let (tx_event, rx_event) = mpsc::channel();
+    game.set_duration(gravity_for_level(game.level()));
+    // The game's current speed, shared with the price thread so each price change adjusts whatever speed
+    // the game has reached rather than one the thread last saw.
+    let duration = Arc::new(AtomicU64::new(game.duration()));
+
+    let mut workers = Workers::new();
+    spawn_input_thread(&mut workers, tx_event.clone());
+    spawn_game_workers(&mut workers, ticks, garbage_interval, price_feed, &duration, tx_event.clone());
+    // Only the threads hold senders now, so the loop hears once every one of them has stopped.
+    drop(tx_event);
+
+    run_game_loop(game, display, &rx_event, &duration, record_path, countdown)
 }
 
-enum GameUpdate {
-    KeyPress(Key),
-    Tick,
-    DurationUpdate(u64),
-}
-
-// #[derive{PartialEq, Eq}]
-enum GameOver {
-    TopOut,
-    LockOut,
-    BlockOut,
-}
-
-#[derive(Debug, Copy, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
+/// Spawns the threads feeding a game other than the keyboard's: its ticks, the garbage coming up every
+/// `garbage_interval` milliseconds if set, and the prices from `price_feed` if there is one. `duration` is
+/// shared with the price thread, as `play` describes.
+fn spawn_game_workers(
+    workers: &mut Workers,
+    ticks: impl TickSource + 'static,
+    garbage_interval: Option<u64>,
+    price_feed: Option<PriceFeed>,
+    duration: &Arc<AtomicU64>,
+    tx_event: mpsc::Sender<GameUpdate>,
+) {
+    spawn_tick_thread(workers, ticks, terminal::take_resize, tx_event.clone());
+    if let Some(interval) = garbage_interval {
+        spawn_garbage_thread(workers, interval, tx_event.clone());
+    }
+    if let Some(price_feed) = price_feed {
+        spawn_price_thread(workers, price_feed, Arc::clone(duration), tx_event);
+    }
 }
 
-struct Board {
-    cells: [[Option<Color>; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize],
-}
+/// Runs `play`'s game loop on the updates arriving on `rx_event`, until the player quits or loses the game,
+/// or fails with `TetrustError::Disconnected` once every thread sending updates has stopped. The game's speed
+/// is kept in `duration` for the price thread. Each game is recorded to `record_path`, if set, as it ends.
+fn run_game_loop(
+    game: &mut Game,
+    display: &mut Display,
+    rx_event: &mpsc::Receiver<GameUpdate>,
+    duration: &AtomicU64,
+    record_path: Option<&Path>,
+    countdown: Duration,
+) -> Result<Option<GameOver>, TetrustError> {
+    let mut recorder = Recorder::new(game);
+    let mut started = Instant::now();
+    let mut countdown_end = started + countdown;
+
+    terminal::watch_resize();
+    let mut too_small = !fit_to_terminal(display, min_terminal_size(game.board()));
+    let mut paused_for_resize = false;
+    if too_small && !game.is_paused() {
+        let _ = apply_event(game, &mut recorder, started, ReplayEvent::Action(Action::Pause));
+        paused_for_resize = true;
+    }
 
-impl Board {
-    pub fn render(&self, display: &mut Display) {
-        for y in HIDDEN_ROWS..BOARD_HEIGHT {
-            display.set_text("|", 0, y, Color::Red, Color::Black);
-            display.set_text("|", BOARD_WIDTH * 2 + 1, y, Color::Red, Color::Black);
-        }
-        for x in 0..(BOARD_WIDTH * 2 + 1) {
-            display.set_text("-", x, BOARD_HEIGHT, Color::Red, Color::Black);
-        }
-        for row in 0..BOARD_HEIGHT {
-            for col in 0..BOARD_WIDTH {
-                if let Some(color) = self.cells[row as usize][col as usize] {
-                    let c = 1 + (col * 2);
-                    display.set_text(" ", c, row, color, color);
-                    display.set_text(" ", c + 1, row, color, color);
-                }
+    // Main game loop. The loop listens and responds to timer and keyboard updates received on a channel
+    // as sent by the threads `play` spawns, and draws a frame every `FRAME_MS` whether or not any arrive.
+    // Everything that arrives between two frames is handled before the next is drawn, so a burst of key
+    // presses is shown at once rather than playing out over the frames that follow.
+    let mut next_frame = Instant::now();
+    loop {
+        if Instant::now() >= next_frame {
+            display.clear_buffer();
+            if too_small {
+                render_too_small(display, game.theme(), min_terminal_size(game.board()));
+            } else {
+                game.render(display);
+                render_countdown(display, game, countdown_end);
             }
+            display.render();
+            next_frame = Instant::now() + Duration::from_millis(FRAME_MS);
         }
-    }
-
-    pub fn lock_piece(&mut self, piece: &Piece, origin: Point) {
-        piece.each_point(&mut |row, col| {
-            let x = origin.x + (col as i32);
-            let y = origin.y + (row as i32);
-            self.cells[y as usize][x as usize] = Some(piece.color);
-        });
-    }
 
-    pub fn collision_test(&self, piece: &Piece, origin: Point) -> bool {
-        let mut found = false;
-        piece.each_point(&mut |row, col| {
-            if !found {
-                let x = origin.x + col;
-                let y = origin.y + row;
-                if x < 0
-                    || x >= (BOARD_WIDTH as i32)
-                    || y < 0
-                    || y >= (BOARD_HEIGHT as i32)
-                    || self.cells[y as usize][x as usize] != None
-                {
-                    found = true;
+        // Wake up for the next frame, or sooner when a pending lock delay runs out.
+        let lock_deadline = game.lock_deadline().filter(|_| !game.is_paused());
+        let wake = lock_deadline.map_or(next_frame, |deadline| deadline.min(next_frame));
+        let update = rx_event.recv_timeout(wake.saturating_duration_since(Instant::now()));
+
+        let event = match update {
+            Ok(update) => {
+                match update {
+                    GameUpdate::KeyPress(key, read_at) => {
+                        game.record_input_latency(read_at.elapsed());
+                        match key {
+                            Key::Char('z') | Key::CtrlC => {
+                                save_recording(&recorder, record_path);
+                                return Ok(None);
+                            }
+                            Key::F1 => {
+                                dump_board(game);
+                                None
+                            }
+                            _ if Instant::now() < countdown_end => {
+                                countdown_end = Instant::now();
+                                None
+                            }
+                            // Keep the game paused until the board can be seen again.
+                            _ if too_small => None,
+                            k => game.action_for(k).map(ReplayEvent::Action),
+                        }
+                    }
+                    // A key going up stops whatever holding it was doing, even during the countdown.
+                    GameUpdate::KeyRelease(key) => game.action_for(key).map(ReplayEvent::Release),
+                    // Nothing moves until the countdown is over.
+                    GameUpdate::Tick | GameUpdate::Garbage if Instant::now() < countdown_end => None,
+                    GameUpdate::Tick => Some(ReplayEvent::Tick),
+                    GameUpdate::DurationUpdate(new_duration) => Some(ReplayEvent::Speed(new_duration)),
+                    // The price is only shown, not recorded; the speed change it brings is.
+                    GameUpdate::Price(price, percentage_change) => {
+                        game.set_price(price, percentage_change);
+                        None
+                    }
+                    GameUpdate::PriceError(msg) => {
+                        log_error(&format!("{}, retrying", msg));
+                        game.set_price_failed();
+                        None
+                    }
+                    // The gap is picked here rather than by the game so the recording can say where it was.
+                    GameUpdate::Garbage => Some(ReplayEvent::Garbage(rand::random::<u32>() % game.board().width())),
+                    // Pause while the board doesn't fit, and resume once it does unless the player had
+                    // paused the game already.
+                    GameUpdate::Resize => {
+                        let was_too_small = too_small;
+                        too_small = !fit_to_terminal(display, min_terminal_size(game.board()));
+                        if too_small && !was_too_small && !game.is_paused() {
+                            paused_for_resize = true;
+                            Some(ReplayEvent::Action(Action::Pause))
+                        } else if !too_small && was_too_small && paused_for_resize {
+                            paused_for_resize = false;
+                            Some(ReplayEvent::Action(Action::Pause))
+                        } else {
+                            None
+                        }
+                    }
+                    GameUpdate::Failed(err) => {
+                        save_recording(&recorder, record_path);
+                        return Err(err);
+                    }
                 }
             }
-        });
+            Err(RecvTimeoutError::Timeout) => {
+                lock_deadline.filter(|&deadline| Instant::now() >= deadline).map(|_| ReplayEvent::LockCheck)
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                save_recording(&recorder, record_path);
+                return Err(TetrustError::Disconnected);
+            }
+        };
 
-        found
+        let result = match event {
+            Some(event) => apply_event(game, &mut recorder, started, event),
+            None => Ok(()),
+        };
+        // Levelling up changes the speed too; pass it on so the next price change starts from there.
+        duration.store(game.duration(), Ordering::Relaxed);
+
+        // Restarting starts a fresh game in this same loop, so the threads spawned above keep serving it.
+        if let Err(reason) = result {
+            save_recording(&recorder, record_path);
+            dump_board(game);
+            if !run_game_over(game, display, rx_event, reason)? {
+                return Ok(Some(reason));
+            }
+
+            game.restart();
+            recorder = Recorder::new(game);
+            started = Instant::now();
+            countdown_end = started + countdown;
+            duration.store(game.duration(), Ordering::Relaxed);
+            next_frame = Instant::now();
+        }
     }
+}
 
-    /// Clears the board of any complete lines, shifting down rows to take their place.
-    /// Returns the total number of lines that were cleared.
-    fn clear_lines(&mut self) -> u32 {
-        let mut cleared_lines: usize = 0;
-        for row in (0..self.cells.len()).rev() {
-            if (row as i32) - (cleared_lines as i32) < 0 {
-                break;
-            }
+/// Shows the menu before each game and plays the game picked from it, coming back to the menu when the game
+/// ends, until the player quits from the menu. Each game is set up from `options` and the player's settings
+/// from the config file, with the mode, start level and theme chosen from the menu written into the options;
+/// the first is dealt the pieces of `game`'s seed.
+/// Returns the reason the last game played was lost, or an error if the game couldn't go on.
+fn play_from_menu(
+    options: &mut Options,
+    game: &mut Game,
+    display: &mut Display,
+    bindings: &KeyBindings,
+    auto_shift: &AutoShift,
+    piece_colors: PieceColors,
+    start_board: Option<&Board>,
+) -> Result<Option<GameOver>, TetrustError> {
+    let countdown = Duration::from_secs(options.countdown);
+    let mut menu = Menu::new(options.mode, options.start_level, options.theme.unwrap_or_default());
+    let mut seed = game.seed();
+    let mut game_over = None;
+    loop {
+        if run_menu(&mut menu, display)? == MenuChoice::Quit {
+            return Ok(game_over);
+        }
 
-            if cleared_lines > 0 {
-                self.cells[row] = self.cells[row - cleared_lines];
-                self.cells[row - cleared_lines] = [None; BOARD_WIDTH as usize];
-            }
+        options.mode = menu.mode();
+        options.start_level = menu.start_level();
+        options.theme = Some(menu.theme());
+        *game = new_game(options, seed, bindings.clone(), auto_shift, piece_colors, start_board);
+        game.set_quit_to_menu(true);
+        let ticks = RealTicks::new(Duration::from_millis(TICK_MS));
+        game_over = play(
+            game,
+            display,
+            ticks,
+            options.price_feed.clone(),
+            options.record_path.as_deref(),
+            options.garbage_interval,
+            countdown,
+        )?;
+        seed = options.seed.unwrap_or_else(rand::random);
+    }
+}
 
-            while !self.cells[row].iter().any(|x| *x == None) {
-                cleared_lines += 1;
-                self.cells[row] = self.cells[row - cleared_lines];
-                self.cells[row - cleared_lines] = [None; BOARD_WIDTH as usize];
+/// Shows the menu until the player picks what to do, or an error if the keyboard can no longer be read. The
+/// menu reads the keyboard on a thread of its own, stopped before this returns so the game can start its own.
+fn run_menu(menu: &mut Menu, display: &mut Display) -> Result<MenuChoice, TetrustError> {
+    let (tx_event, rx_event) = mpsc::channel();
+    let mut workers = Workers::new();
+    spawn_input_thread(&mut workers, tx_event);
+    // The menu is small enough for any terminal; this only stops long lines wrapping.
+    fit_to_terminal(display, (0, 0));
+
+    loop {
+        display.clear_buffer();
+        menu.render(display);
+        display.render();
+
+        match rx_event.recv() {
+            Ok(GameUpdate::KeyPress(key, _)) => {
+                if let Some(choice) = menu.keypress(key) {
+                    return Ok(choice);
+                }
             }
+            Ok(GameUpdate::Failed(err)) => return Err(err),
+            Ok(_) => (),
+            Err(_) => return Err(TetrustError::Disconnected),
         }
+    }
+}
 
-        cleared_lines as u32
+/// How long "GO!" stays up once a countdown ends, in milliseconds. The game is already under way by then.
+const GO_MESSAGE_MS: u64 = 500;
+
+/// Draws the countdown to the start of the game over its board, ending in "GO!" as it starts.
+fn render_countdown(display: &mut Display, game: &Game, countdown_end: Instant) {
+    let now = Instant::now();
+    let lines = if now < countdown_end {
+        let seconds_left = countdown_end.duration_since(now).as_secs_f64().ceil();
+        vec![String::from("Ready?"), String::new(), format!("{}", seconds_left)]
+    } else if now < countdown_end + Duration::from_millis(GO_MESSAGE_MS) {
+        vec![String::from("GO!")]
+    } else {
+        return;
+    };
+
+    let ((width, height), theme) = (game.board().config().display_size(), game.theme());
+    for (i, line) in lines.iter().enumerate() {
+        let x = 1 + width.saturating_sub(line.len() as u32) / 2;
+        display.set_text(line, x, height / 2 - 1 + i as u32, theme.text, theme.background);
     }
 }
 
-struct Piece {
-    color: Color,
-    shape: Vec<Vec<u8>>,
+/// Applies an event to the game, noting it in the recording along with how long after `started` it
+/// happened.
+fn apply_event(
+    game: &mut Game,
+    recorder: &mut Recorder,
+    started: Instant,
+    event: ReplayEvent,
+) -> Result<(), GameOver> {
+    // The game's clock is kept to the millisecond the recording stores, so a replay sees exactly the same
+    // times as the original game did.
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    game.set_clock(started + Duration::from_millis(elapsed_ms));
+    recorder.record(elapsed_ms, event);
+    event.apply(game)
 }
 
-impl Clone for Piece {
-    fn clone(&self) -> Piece {
-        let mut p = Piece {
-            color: self.color,
-            shape: Vec::with_capacity(self.shape.len()),
-        };
-        for row in &self.shape {
-            p.shape.push(row.clone());
+fn save_recording(recorder: &Recorder, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(err) = recorder.save(path) {
+            log_error(&format!("could not save replay: {}", err));
         }
-        p
     }
 }
 
-impl Piece {
-    pub fn new_o() -> Piece {
-        Piece {
-            color: Color::Cyan,
-            shape: vec![vec![1, 1], vec![1, 1]],
-        }
-    }
+/// The file in the config directory that errors hit during a game are written to.
+const ERROR_LOG: &str = "error.log";
+
+/// Notes an error that doesn't stop the game in `ERROR_LOG`. While a game is on screen the terminal is in
+/// raw mode and being drawn over, so anything written to it would land on the board and stay there.
+fn log_error(msg: &str) {
+    let _ = config::append_line(ERROR_LOG, msg);
+}
+
+/// Plays back a recorded game at its original pace, returning the reason the game was lost, or `None` if
+/// the recording ended first or the viewer quit. Fails if the keyboard can no longer be read.
+fn replay(game: &mut Game, display: &mut Display, recorder: &Recorder) -> Result<Option<GameOver>, TetrustError> {
+    let (tx_event, rx_event) = mpsc::channel();
+    let mut workers = Workers::new();
+    spawn_input_thread(&mut workers, tx_event);
+
+    let started = Instant::now();
+    for &(elapsed_ms, event) in &recorder.events {
+        let at = started + Duration::from_millis(elapsed_ms);
 
-    pub fn new_l() -> Piece {
-        Piece {
-            color: Color::Orange,
-            shape: vec![vec![0, 0, 1], vec![1, 1, 1], vec![0, 0, 0]],
+        // Wait for the event to come round, stopping early if a quit key is pressed.
+        loop {
+            match rx_event.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                Ok(GameUpdate::KeyPress(Key::Char('q') | Key::Char('z') | Key::CtrlC, _)) => return Ok(None),
+                Ok(GameUpdate::Failed(err)) => return Err(err),
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => break,
+                // Without the input thread nothing can stop the replay early, but it still plays at its own pace.
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(at.saturating_duration_since(Instant::now()));
+                    break;
+                }
+            }
         }
-    }
 
-    pub fn new_j() -> Piece {
-        Piece {
-            color: Color::Blue,
-            shape: vec![vec![1, 0, 0], vec![1, 1, 1], vec![0, 0, 0]],
+        game.set_clock(at);
+        let result = event.apply(game);
+
+        display.clear_buffer();
+        game.render(display);
+        display.render();
+
+        if let Err(reason) = result {
+            return Ok(Some(reason));
         }
     }
+    Ok(None)
+}
 
-    pub fn new_t() -> Piece {
-        Piece {
-            color: Color::Purple,
-            shape: vec![vec![0, 1, 0], vec![1, 1, 1], vec![0, 0, 0]],
+/// Spawns a thread which listens for keyboard input
+fn spawn_input_thread(workers: &mut Workers, tx_event: mpsc::Sender<GameUpdate>) {
+    workers.spawn(move |running| {
+        let reader = &mut KeyReader::new();
+        while running.load(Ordering::Relaxed) {
+            // Wait for input a little at a time, so the thread can stop without a key being pressed
+            let keys = match reader.read_keys(Duration::from_millis(SHUTDOWN_POLL_MS)) {
+                Ok(Some(keys)) => keys,
+                Ok(None) => break,
+                Err(err) => {
+                    let _ = tx_event.send(GameUpdate::Failed(TetrustError::Input(err)));
+                    return;
+                }
+            };
+            for key in keys {
+                let update = match key {
+                    KeyInput::Press(key) => GameUpdate::KeyPress(key, Instant::now()),
+                    KeyInput::Release(key) => GameUpdate::KeyRelease(key),
+                };
+                if tx_event.send(update).is_err() {
+                    return;
+                }
+            }
         }
+    });
+}
+
+/// Appends the board, drawn as plain text, to `board.log` in the config directory for bug reports.
+fn dump_board(game: &Game) {
+    if let Err(err) = config::append_line("board.log", &game.to_ascii()) {
+        log_error(&format!("could not write board.log: {}", err));
     }
+}
 
-    pub fn new_s() -> Piece {
-        Piece {
-            color: Color::Green,
-            shape: vec![vec![0, 1, 1], vec![1, 1, 0], vec![0, 0, 0]],
+/// Shows the game over screen until the player restarts or quits, first asking for a name to record in
+/// the high score table if the game earned a place in it. Returns true if the player chose to restart, or
+/// an error if the keyboard can no longer be read.
+fn run_game_over(
+    game: &Game,
+    display: &mut Display,
+    rx_event: &mpsc::Receiver<GameUpdate>,
+    reason: GameOver,
+) -> Result<bool, TetrustError> {
+    let path = highscore::default_path();
+    let mut highscores = match &path {
+        Some(path) => highscore::load_highscores(path),
+        None => Vec::new(),
+    };
+    let mut name_entry = if path.is_some() && highscore::qualifies(&highscores, game.score()) {
+        Some(String::new())
+    } else {
+        None
+    };
+
+    // A won sprint is compared with the best time for its goal, and replaces it if faster.
+    let mut previous_best = None;
+    if let (GameOver::Victory, GameMode::Sprint { goal }) = (reason, game.mode()) {
+        if let Some(path) = highscore::default_sprint_path() {
+            previous_best = highscore::best_sprint_time(&path, goal);
+            if let Err(err) = highscore::save_sprint_time(&path, goal, game.play_time()) {
+                log_error(&format!("could not save sprint time: {}", err));
+            }
         }
     }
 
-    pub fn new_z() -> Piece {
-        Piece {
-            color: Color::Red,
-            shape: vec![vec![1, 1, 0], vec![0, 1, 1], vec![0, 0, 0]],
+    loop {
+        display.clear_buffer();
+        game.render_game_over(display, reason, name_entry.as_deref(), &highscores, previous_best);
+        display.render();
+
+        let key = match rx_event.recv() {
+            Ok(GameUpdate::KeyPress(key, _)) => key,
+            Ok(
+                GameUpdate::KeyRelease(_)
+                | GameUpdate::DurationUpdate(_)
+                | GameUpdate::Price(..)
+                | GameUpdate::PriceError(_)
+                | GameUpdate::Tick
+                | GameUpdate::Garbage,
+            ) => continue,
+            Ok(GameUpdate::Resize) => {
+                fit_to_terminal(display, min_terminal_size(game.board()));
+                continue;
+            }
+            Ok(GameUpdate::Failed(err)) => return Err(err),
+            Err(_) => return Err(TetrustError::Disconnected),
+        };
+
+        match (&mut name_entry, key) {
+            (_, Key::CtrlC) => return Ok(false),
+            (Some(name), Key::Char(c)) if c.is_ascii_alphabetic() && name.len() < 3 => {
+                name.push(c.to_ascii_uppercase());
+            }
+            (Some(name), Key::Char('\x7f')) => {
+                name.pop();
+            }
+            (Some(name), Key::Char('\r')) if name.len() == 3 => {
+                let entry = ScoreEntry::new(name.clone(), game.score(), game.level(), game.lines_cleared());
+                if let Some(path) = &path {
+                    if let Err(err) = highscore::save_highscore(path, entry) {
+                        log_error(&format!("could not save high score: {}", err));
+                    }
+                    highscores = highscore::load_highscores(path);
+                }
+                name_entry = None;
+            }
+            (Some(_), _) => (),
+            (None, Key::Char('r')) => return Ok(true),
+            (None, Key::Char('q') | Key::Char('z')) => return Ok(false),
+            (None, _) => (),
         }
     }
+}
 
-    pub fn new_i() -> Piece {
-        Piece {
-            color: Color::Cyan,
-            shape: vec![
-                vec![0, 0, 0, 0],
-                vec![1, 1, 1, 1],
-                vec![0, 0, 0, 0],
-                vec![0, 0, 0, 0],
-            ],
+/// Columns taken up by the panels beside a board: the level and score, the next and held pieces, and the
+/// statistics.
+const PANEL_WIDTH: u32 = 50;
+/// Rows taken up by the panels beside a board, with the most next pieces shown.
+const PANEL_HEIGHT: u32 = 9 + 3 * MAX_NEXT_PIECES as u32;
+
+/// Runs a versus game between two players sharing the keyboard until they quit. Each player's clears send
+/// rows of garbage to the other, with the gap in a random column. As soon as either player tops out the
+/// other wins, and the two can play again on a fresh pair of boards. Both games are dealt the same pieces,
+/// and prices from `price_feed` change their speeds together. Like a single game, each round opens with a
+/// `countdown`.
+fn play_versus(
+    games: &mut [Game; 2],
+    display: &mut Display,
+    ticks: impl TickSource + 'static,
+    price_feed: Option<PriceFeed>,
+    garbage_interval: Option<u64>,
+    countdown: Duration,
+) -> Result<(), TetrustError> {
+    let (tx_event, rx_event) = mpsc::channel();
+    for game in games.iter_mut() {
+        game.set_duration(gravity_for_level(game.level()));
+    }
+    let duration = Arc::new(AtomicU64::new(games[0].duration()));
+    let config = games[0].board().config();
+    let min_size = versus_min_terminal_size(config);
+    let mut countdown_end = Instant::now() + countdown;
+
+    terminal::watch_resize();
+    let mut too_small = !fit_to_terminal(display, min_size);
+    let mut paused_for_resize = false;
+    if too_small && !games[0].is_paused() {
+        for game in games.iter_mut() {
+            let _ = game.perform(Action::Pause);
         }
+        paused_for_resize = true;
     }
 
-    fn rotate(&mut self, direction: Direction) {
-        let size = self.shape.len();
-
-        for row in 0..size / 2 {
-            for col in row..(size - row - 1) {
-                let t = self.shape[row][col];
-
-                match direction {
-                    Direction::Left => {
-                        self.shape[row][col] = self.shape[col][size - row - 1];
-                        self.shape[col][size - row - 1] =
-                            self.shape[size - row - 1][size - col - 1];
-                        self.shape[size - row - 1][size - col - 1] =
-                            self.shape[size - col - 1][row];
-                        self.shape[size - col - 1][row] = t;
-                    }
-                    Direction::Right => {
-                        self.shape[row][col] = self.shape[size - col - 1][row];
-                        self.shape[size - col - 1][row] =
-                            self.shape[size - row - 1][size - col - 1];
-                        self.shape[size - row - 1][size - col - 1] =
-                            self.shape[col][size - row - 1];
-                        self.shape[col][size - row - 1] = t;
-                    }
+    let mut workers = Workers::new();
+    spawn_input_thread(&mut workers, tx_event.clone());
+    spawn_game_workers(&mut workers, ticks, garbage_interval, price_feed, &duration, tx_event.clone());
+    drop(tx_event);
+
+    // Like a single game, frames are drawn every `FRAME_MS` with the updates in between handled first.
+    let mut next_frame = Instant::now();
+    loop {
+        if Instant::now() >= next_frame {
+            display.clear_buffer();
+            if too_small {
+                render_too_small(display, games[0].theme(), min_size);
+            } else {
+                render_versus(display, games);
+                let offset = versus_offset(config);
+                for (i, game) in games.iter().enumerate() {
+                    display.set_origin(i as u32 * offset);
+                    render_countdown(display, game, countdown_end);
                 }
+                display.set_origin(0);
             }
+            display.render();
+            next_frame = Instant::now() + Duration::from_millis(FRAME_MS);
         }
-    }
 
-    fn each_point(&self, callback: &mut dyn FnMut(i32, i32)) {
-        let piece_width = self.shape.len() as i32;
-        for row in 0..piece_width {
-            for col in 0..piece_width {
-                if self.shape[row as usize][col as usize] != 0 {
-                    callback(row, col);
+        // Wake up for the next frame, or sooner when either player's lock delay runs out.
+        let lock_deadlines = games.each_ref().map(|game| game.lock_deadline().filter(|_| !game.is_paused()));
+        let wake = lock_deadlines.iter().flatten().fold(next_frame, |wake, &deadline| wake.min(deadline));
+        let update = rx_event.recv_timeout(wake.saturating_duration_since(Instant::now()));
+
+        // The event each player's game sees; a key press only reaches the player it's bound for.
+        let events = match update {
+            Ok(GameUpdate::KeyPress(Key::Char('z') | Key::CtrlC, _)) => return Ok(()),
+            Ok(GameUpdate::KeyPress(_, _)) if Instant::now() < countdown_end => {
+                countdown_end = Instant::now();
+                [None, None]
+            }
+            Ok(GameUpdate::KeyPress(_, _)) if too_small => [None, None],
+            Ok(GameUpdate::KeyPress(key, read_at)) => {
+                for game in games.iter_mut() {
+                    game.record_input_latency(read_at.elapsed());
+                }
+                [0, 1].map(|i| games[i].action_for(key).map(ReplayEvent::Action))
+            }
+            Ok(GameUpdate::KeyRelease(key)) => [0, 1].map(|i| games[i].action_for(key).map(ReplayEvent::Release)),
+            Ok(GameUpdate::Tick | GameUpdate::Garbage) if Instant::now() < countdown_end => [None, None],
+            Ok(GameUpdate::Tick) => [Some(ReplayEvent::Tick); 2],
+            Ok(GameUpdate::DurationUpdate(new_duration)) => [Some(ReplayEvent::Speed(new_duration)); 2],
+            Ok(GameUpdate::Price(price, percentage_change)) => {
+                for game in games.iter_mut() {
+                    game.set_price(price, percentage_change);
+                }
+                [None, None]
+            }
+            Ok(GameUpdate::PriceError(msg)) => {
+                log_error(&format!("{}, retrying", msg));
+                for game in games.iter_mut() {
+                    game.set_price_failed();
+                }
+                [None, None]
+            }
+            Ok(GameUpdate::Garbage) => [Some(ReplayEvent::Garbage(rand::random::<u32>() % config.width)); 2],
+            Ok(GameUpdate::Resize) => {
+                let was_too_small = too_small;
+                too_small = !fit_to_terminal(display, min_size);
+                if too_small && !was_too_small && !games[0].is_paused() {
+                    paused_for_resize = true;
+                    [Some(ReplayEvent::Action(Action::Pause)); 2]
+                } else if !too_small && was_too_small && paused_for_resize {
+                    paused_for_resize = false;
+                    [Some(ReplayEvent::Action(Action::Pause)); 2]
+                } else {
+                    [None, None]
+                }
+            }
+            Ok(GameUpdate::Failed(err)) => return Err(err),
+            Err(RecvTimeoutError::Timeout) => lock_deadlines.map(|deadline| {
+                deadline.filter(|&deadline| Instant::now() >= deadline).map(|_| ReplayEvent::LockCheck)
+            }),
+            Err(RecvTimeoutError::Disconnected) => return Err(TetrustError::Disconnected),
+        };
+
+        let mut results = [Ok(()), Ok(())];
+        for (i, event) in events.into_iter().enumerate() {
+            if let Some(event) = event {
+                results[i] = event.apply(&mut games[i]);
+            }
+        }
+        // Each attack comes up as one block of garbage, every row with its gap in the same column.
+        for sender in 0..2 {
+            let receiver = 1 - sender;
+            let rows = games[sender].take_garbage_sent();
+            let gap_column = rand::random::<u32>() % config.width;
+            for _ in 0..rows {
+                if results[receiver].is_ok() {
+                    results[receiver] = games[receiver].push_garbage(gap_column);
                 }
             }
         }
-    }
-}
+        duration.store(games[0].duration(), Ordering::Relaxed);
 
-/// Implements a queue of randomized tetrominoes.
-///
-/// Instead of a purely random stream of tetromino types, this queue generates a random ordering of all
-/// possible types and ensures all of those pieces are used before re-generating a new random set. This helps
-/// avoid pathological cases where purely random generation provides the same piece type repeately in a row,
-/// or fails to provide a required piece for a very long time.
-struct PieceBag {
-    pieces: Vec<Piece>,
-}
+        // A player who reaches their mode's goal wins; any other end to a game loses it.
+        let knockout = results.iter().enumerate().find_map(|(i, result)| match result {
+            Err(GameOver::Victory) => Some(1 - i),
+            Err(_) => Some(i),
+            Ok(()) => None,
+        });
+        if let Some(loser) = knockout {
+            if !run_knockout(games, display, &rx_event, loser)? {
+                return Ok(());
+            }
 
-impl PieceBag {
-    fn new() -> PieceBag {
-        let mut p = PieceBag { pieces: Vec::new() };
-        p.fill_bag();
-        p
+            let seed = rand::random();
+            for game in games.iter_mut() {
+                game.restart_seeded(seed);
+            }
+            countdown_end = Instant::now() + countdown;
+            duration.store(games[0].duration(), Ordering::Relaxed);
+            next_frame = Instant::now();
+        }
     }
+}
 
-    /// Removes and returns the next piece in the queue.
-    fn pop(&mut self) -> Piece {
-        let piece = self.pieces.remove(0);
-        if self.pieces.is_empty() {
-            self.fill_bag();
-        }
-        piece
+/// Draws the two players' games side by side, each labelled below its board.
+fn render_versus(display: &mut Display, games: &[Game; 2]) {
+    let offset = versus_offset(games[0].board().config());
+    for (i, game) in games.iter().enumerate() {
+        display.set_origin(i as u32 * offset);
+        game.render(display);
+        let theme = game.theme();
+        let label = format!("Player {}", i + 1);
+        let (_, height) = game.board().config().display_size();
+        display.set_text(&label, 1, height + 1, theme.text, theme.background);
     }
+    display.set_origin(0);
+}
 
-    /// Returns a copy of the next piece in the queue.
-    fn peek(&self) -> Piece {
-        match self.pieces.first() {
-            Some(p) => p.clone(),
-            None => panic!("No next piece in piece bag"),
+/// Shows who won a versus game until the players choose to play again or quit. Returns true for a rematch,
+/// or an error if the keyboard can no longer be read.
+fn run_knockout(
+    games: &[Game; 2],
+    display: &mut Display,
+    rx_event: &mpsc::Receiver<GameUpdate>,
+    loser: usize,
+) -> Result<bool, TetrustError> {
+    let config = games[0].board().config();
+    let offset = versus_offset(config);
+    let (width, height) = config.display_size();
+    loop {
+        display.clear_buffer();
+        render_versus(display, games);
+        for (i, game) in games.iter().enumerate() {
+            let theme = game.theme();
+            let lines = [if i == loser { "K.O." } else { "WINNER!" }, "", "r: rematch", "z: quit"];
+            for (row, line) in lines.iter().enumerate() {
+                let x = i as u32 * offset + 1 + width.saturating_sub(line.len() as u32) / 2;
+                display.set_text(line, x, height / 2 + row as u32, theme.text, theme.background);
+            }
         }
-    }
+        display.render();
 
-    /// Generates a random ordering of all possible pieces and adds them to the piece queue.
-    fn fill_bag(&mut self) {
-        use rand::Rng;
-
-        let mut pieces: Vec<Piece> = vec![
-            Piece::new_o(),
-            Piece::new_l(),
-            Piece::new_j(),
-            Piece::new_t(),
-            Piece::new_s(),
-            Piece::new_z(),
-            Piece::new_i(),
-        ];
-
-        let mut rng = rand::thread_rng();
-        while !pieces.is_empty() {
-            let i = rng.gen::<usize>() % pieces.len();
-            self.pieces.push(pieces.swap_remove(i));
+        match rx_event.recv() {
+            Ok(GameUpdate::KeyPress(Key::Char('r'), _)) => return Ok(true),
+            Ok(GameUpdate::KeyPress(Key::Char('z') | Key::CtrlC, _)) => return Ok(false),
+            Ok(GameUpdate::Resize) => {
+                fit_to_terminal(display, versus_min_terminal_size(config));
+            }
+            Ok(GameUpdate::Failed(err)) => return Err(err),
+            Ok(_) => (),
+            Err(_) => return Err(TetrustError::Disconnected),
         }
     }
 }
 
-struct Game {
-    board: Board,
-    piece_bag: PieceBag,
-    piece: Piece,
-    piece_position: Point,
-    score: u32,
-    level: u32,
-    duration: u64,
+/// Returns how far right the second player's game is drawn: past the first player's board and panels.
+fn versus_offset(config: BoardConfig) -> u32 {
+    config.display_size().0 + PANEL_WIDTH
 }
 
-impl Game {
-    fn new() -> Game {
-        let mut piece_bag = PieceBag::new();
-        let piece = piece_bag.pop();
-
-        let mut game = Game {
-            board: Board {
-                cells: [[None; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize],
-            },
-            piece_bag: piece_bag,
-            piece: piece,
-            piece_position: Point { x: 0, y: 0 },
-            score: 0,
-            level: 1,
-            duration: 0,
-        };
-
-        game.place_new_piece();
-        game
-    }
+/// Returns the smallest terminal size both boards of a versus game fit in, borders and labels included.
+fn versus_min_terminal_size(config: BoardConfig) -> (u32, u32) {
+    let (width, height) = config.display_size();
+    (versus_offset(config) + width + 2, height + 2)
+}
 
-    /// Returns the new position of the current piece if it were to be dropped.
-    fn find_dropped_position(&self) -> Point {
-        let mut origin = self.piece_position;
-        while !self.board.collision_test(&self.piece, origin) {
-            origin.y += 1;
-        }
-        origin.y -= 1;
-        origin
-    }
+/// Returns the smallest terminal size the board fits in, borders included.
+fn min_terminal_size(board: &Board) -> (u32, u32) {
+    let (width, height) = board.config().display_size();
+    (width + 2, height + 1)
+}
 
-    /// Draws the game to the display.
-    fn render(&self, display: &mut Display) {
-        // Render the board
-        self.board.render(display);
-
-        // Render the level
-        let left_margin = BOARD_WIDTH * 2 + 5;
-        let level_line = format!("Level: {}", self.level);
-        display.set_text(&level_line, left_margin, 3, Color::Red, Color::Black);
-        let score_line = format!("Score: {}", self.score);
-        display.set_text(&score_line, left_margin, 4, Color::Red, Color::Black);
-        let score_line = format!("Speed: {}", self.duration);
-        display.set_text(&score_line, left_margin, 5, Color::Red, Color::Black);
-
-        // Render the currently falling piece
-        let x = 1 + (2 * self.piece_position.x);
-        self.render_piece(
-            display,
-            &self.piece,
-            Point {
-                x: x,
-                y: self.piece_position.y,
-            },
-        );
-
-        // Render a ghost piece
-        let ghost_position = self.find_dropped_position();
-        self.render_piece(
-            display,
-            &self.piece,
-            Point {
-                x: x,
-                y: ghost_position.y,
-            },
-        );
-
-        // Render the next piece
-        display.set_text("Next piece:", left_margin, 7, Color::Red, Color::Black);
-        let next_piece = self.piece_bag.peek();
-        self.render_piece(
-            display,
-            &next_piece,
-            Point {
-                x: (left_margin as i32) + 2,
-                y: 9,
-            },
-        );
+/// Limits the display to the current terminal size. Returns false if the terminal is smaller than
+/// `min_size`, the (columns, rows) needed to show the boards.
+fn fit_to_terminal(display: &mut Display, min_size: (u32, u32)) -> bool {
+    if let Some((width, height)) = terminal::terminal_size() {
+        display.set_visible_size(width, height);
     }
+    let (min_width, min_height) = min_size;
+    display.visible_width() >= min_width && display.visible_height() >= min_height
+}
 
-    fn render_piece(&self, display: &mut Display, piece: &Piece, origin: Point) {
-        let color = piece.color;
+fn render_too_small(display: &mut Display, theme: &Theme, min_size: (u32, u32)) {
+    let (min_width, min_height) = min_size;
+    display.set_text("Terminal too small", 0, 0, theme.text, theme.background);
+    let needed = format!("Resize to {}x{}", min_width, min_height);
+    display.set_text(&needed, 0, 1, theme.text, theme.background);
+}
 
-        piece.each_point(&mut |row, col| {
-            let x = (origin.x + 2 * col) as u32;
-            let y = (origin.y + row) as u32;
-            display.set_text(" ", x, y, color, color);
-            display.set_text(" ", x + 1, y, color, color);
-        });
+/// Creates a game with the settings chosen on the command line, played with the given keys and drawn with
+/// the given piece colors.
+fn new_game(
+    options: &Options,
+    seed: u64,
+    bindings: KeyBindings,
+    auto_shift: &AutoShift,
+    piece_colors: PieceColors,
+    start_board: Option<&Board>,
+) -> Game {
+    let mut game = Game::new_seeded(options.board, seed);
+    if let Some(board) = start_board {
+        game.set_board(board.clone());
+    }
+    game.set_bindings(bindings);
+    game.set_auto_shift(AutoShift::new(auto_shift.das_ms(), auto_shift.arr_ms()));
+    game.set_theme(options.theme.unwrap_or_default());
+    game.set_piece_colors(piece_colors);
+    game.set_debug(options.debug);
+    game.set_mirror_controls(options.mirror_controls);
+    game.set_finesse(options.finesse);
+    game.set_invisible(options.invisible.map(Duration::from_millis));
+    game.set_effects(options.effects);
+    game.set_lock_flash(options.lock_flash);
+    game.set_show_hidden(options.show_hidden);
+    game.set_show_grid(options.grid);
+    game.set_previews(options.previews);
+    game.set_minimal_sidebar(options.blind);
+    game.set_line_clear_delay(options.line_clear_delay);
+    game.set_entry_delay(options.entry_delay);
+    game.set_lock_reset_limit(options.lock_reset_limit);
+    game.set_das_charge(options.das_charge);
+    game.set_drop_handling(DropHandling {
+        soft_drop_step: options.soft_drop_step.unwrap_or(DropHandling::default().soft_drop_step),
+        hard_drop_enabled: !options.no_hard_drop,
+    });
+    game.set_lock_out_enabled(options.lock_out);
+    game.set_clear_gravity(options.clear_gravity);
+    game.set_mode(options.mode);
+    game.set_hold_policy(options.hold_policy);
+    game.set_start_level(options.start_level);
+    game.set_max_level(options.max_level);
+    game.set_randomizer(options.randomizer);
+    game.set_rotation_system(options.rotation_system);
+    #[cfg(feature = "sound")]
+    match tetrust::sound::RodioSound::new() {
+        Ok(sound) => game.set_sound(Box::new(sound)),
+        Err(msg) => log_error(&format!("no sound: {}", msg)),
     }
+    game
+}
 
-    /// Moves the current piece in the specified direction. Returns true if the piece could be moved and
-    /// didn't collide.
-    fn move_piece(&mut self, x: i32, y: i32) -> bool {
-        let new_position = Point {
-            x: self.piece_position.x + x,
-            y: self.piece_position.y + y,
-        };
-        if self.board.collision_test(&self.piece, new_position) {
-            false
-        } else {
-            self.piece_position = new_position;
-            true
+/// Saves the theme the last game ended in, so the next launch starts in it, unless it's the one saved already.
+fn remember_theme(theme: &Theme, saved_theme: Option<Theme>) {
+    if saved_theme.map(|saved| saved.name) != Some(theme.name) {
+        if let Err(err) = config::save_theme(theme) {
+            eprintln!("could not save theme: {}", err);
         }
     }
+}
 
-    /// Rotates the current piece in the specified direction. Returns true if the piece could be rotated
-    /// without any collisions.
-    fn rotate_piece(&mut self, direction: Direction) -> bool {
-        let mut new_piece = self.piece.clone();
-        new_piece.rotate(direction);
-
-        if self.board.collision_test(&new_piece, self.piece_position) {
-            false
-        } else {
-            self.piece = new_piece;
-            true
-        }
+/// Reads a starting layout from a file, as `Board::from_str` parses one. It must be the size of the board
+/// being played on.
+fn load_board(path: &Path, config: BoardConfig) -> Result<Board, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let board: Board = contents.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+    if (board.width(), board.height()) != (config.width, config.height) {
+        return Err(format!(
+            "{}: the board is {}x{}, but the game is played on a {}x{} board",
+            path.display(),
+            board.width(),
+            board.height(),
+            config.width,
+            config.height
+        ));
     }
+    Ok(board)
+}
 
-    /// Positions the current piece at the top of the board. Returns true if the piece can be placed without
-    /// any collisions.
-    fn place_new_piece(&mut self) -> bool {
-        let origin = Point {
-            x: ((BOARD_WIDTH - (self.piece.shape.len() as u32)) / 2) as i32,
-            y: 0,
-        };
-        if self.board.collision_test(&self.piece, origin) {
-            //GAME OVER
-            false
-        } else {
-            self.piece_position = origin;
-            true
+/// Creates a display with room for a board of the given size and the panels beside it.
+fn new_display(config: BoardConfig) -> Display {
+    let (width, height) = config.display_size();
+    Display::new(width + 100, (height + 2).max(PANEL_HEIGHT))
+}
+
+fn main() {
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("tetrust: {}\n\n{}", msg, options::USAGE);
+            std::process::exit(1);
         }
+    };
+    if options.help {
+        println!("{}", options::USAGE);
+        return;
     }
 
-    /// Advances the game by moving the current piece down one step. If the piece cannot move down, the piece
-    /// is locked and the game is set up to drop the next piece.  Returns true if the game could be advanced,
-    /// false if the player has lost.
-    fn advance_game(&mut self) -> bool {
-        if !self.move_piece(0, 1) {
-            self.board.lock_piece(&self.piece, self.piece_position);
-            let increm = self.board.clear_lines();
-            self.score = self.score + increm;
-            if self.score % 10 == 0 && self.score != 0 {
-                self.level += 1;
+    let (bindings, auto_shift, drop_handling, piece_colors) = match config::load_config().and_then(|config| {
+        Ok((
+            KeyBindings::from_config(&config)?,
+            AutoShift::from_config(&config)?,
+            DropHandling::from_config(&config)?,
+            PieceColors::from_config(&config)?,
+        ))
+    }) {
+        Ok(settings) => settings,
+        Err(msg) => {
+            eprintln!("tetrust: {}", msg);
+            std::process::exit(1);
+        }
+    };
+    let auto_shift = AutoShift::new(
+        options.das.unwrap_or(auto_shift.das_ms()),
+        options.arr.unwrap_or(auto_shift.arr_ms()),
+    );
+    options.soft_drop_step = options.soft_drop_step.or(Some(drop_handling.soft_drop_step));
+    options.no_hard_drop |= !drop_handling.hard_drop_enabled;
+    // Games are drawn in the theme last played with, unless another is picked on the command line.
+    let saved_theme = config::load_theme();
+    options.theme = options.theme.or(saved_theme);
+
+    if let Some(path) = &options.replay_path {
+        let recorder = match Recorder::load(path) {
+            Ok(recorder) => recorder,
+            Err(msg) => {
+                eprintln!("tetrust: {}", msg);
+                std::process::exit(1);
             }
-            self.piece = self.piece_bag.pop();
+        };
 
-            if !self.place_new_piece() {
-                return false;
+        let game = &mut recorder.new_game(options.board.scale);
+        let display = &mut new_display(game.board().config());
+        game.set_theme(options.theme.unwrap_or_default());
+        game.set_piece_colors(piece_colors);
+        game.set_finesse(options.finesse);
+        game.set_invisible(options.invisible.map(Duration::from_millis));
+        game.set_effects(options.effects);
+        game.set_lock_flash(options.lock_flash);
+        game.set_show_hidden(options.show_hidden);
+        game.set_show_grid(options.grid);
+        game.set_previews(options.previews);
+        game.set_minimal_sidebar(options.blind);
+        // A recording made in debug mode may undo actions, which only replay in debug mode too.
+        game.set_debug(recorder.debug || options.debug);
+
+        // The terminal is restored as soon as the replay ends, before any error is reported.
+        match terminal::set_terminal_raw_mode().and_then(|_restorer| replay(game, display, &recorder)) {
+            Ok(Some(reason)) => println!("\nGame over: {}", reason),
+            Ok(None) => (),
+            Err(err) => {
+                eprintln!("\ntetrust: {}", err);
+                std::process::exit(1);
             }
         }
-
-        true
-    }
-
-    /// Drops the current piece to the lowest spot on the board where it fits without collisions and
-    /// advances the game.
-    fn drop_piece(&mut self) -> bool {
-        while self.move_piece(0, 1) {}
-        self.advance_game()
-    }
-
-    fn keypress(&mut self, key: Key) {
-        match key {
-            Key::Left => self.move_piece(-1, 0),
-            Key::Right => self.move_piece(1, 0),
-            Key::Down => self.advance_game(),
-            Key::Up => self.rotate_piece(Direction::Left),
-            Key::Space => self.drop_piece(),
-            Key::Char('q') => self.rotate_piece(Direction::Left),
-            Key::Char('e') => self.rotate_piece(Direction::Right),
-            _ => false,
-        };
+        println!("\nReplay finished with a score of {}", game.score());
+        return;
     }
 
-    fn play(&mut self, display: &mut Display) {
-        let (tx_event, rx_event) = mpsc::channel();
-        let (tx_duration, rx_duration) = mpsc::channel();
-        let mut duration = 200;
-        //let (tx_duration)
-
-        // Spawn a thread which sends periodic game ticks to advance the piece
-        {
-            let tx_event = tx_event.clone();
-            thread::spawn(move || {
-                loop {
-                    // print!("Hello : {}",rx_duration.recv().unwrap());
-                    // thread::sleep(Duration::from_millis(rx_duration.recv().unwrap()));
-                    thread::sleep(Duration::from_millis(duration));
-                    //if let Ok(new_duration )
-                    tx_event.send(GameUpdate::Tick).unwrap();
-                }
-            });
+    let start_board = match options.board_path.as_deref().map(|path| load_board(path, options.board)).transpose() {
+        Ok(board) => board,
+        Err(msg) => {
+            eprintln!("tetrust: {}", msg);
+            std::process::exit(1);
         }
-
-        // Spawn a thread which listens for keyboard input
-        {
-            let tx_event = tx_event.clone();
-            thread::spawn(move || {
-                let stdin = &mut std::io::stdin();
-                loop {
-                    match get_input(stdin) {
-                        Some(k) => tx_event.send(GameUpdate::KeyPress(k)).unwrap(),
-                        None => (),
-                    }
-                }
-            });
+    };
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let countdown = Duration::from_secs(options.countdown);
+    if options.versus {
+        // The config file's keys are for one player; the two players get a fixed set each instead.
+        let games = &mut KeyBindings::versus().map(|bindings| {
+            new_game(&options, seed, bindings, &auto_shift, piece_colors, start_board.as_ref())
+        });
+        let config = options.board;
+        let display = &mut Display::new(versus_offset(config) * 2, (config.display_size().1 + 2).max(PANEL_HEIGHT));
+        let result = terminal::set_terminal_raw_mode().and_then(|_restorer| {
+            let ticks = RealTicks::new(Duration::from_millis(TICK_MS));
+            play_versus(games, display, ticks, options.price_feed, options.garbage_interval, countdown)
+        });
+        remember_theme(games[0].theme(), saved_theme);
+        if let Err(err) = result {
+            eprintln!("\ntetrust: {}", err);
+            std::process::exit(1);
         }
+        return;
+    }
 
-        //thread api
-        {
-            let tx_event = tx_event.clone();
-            thread::spawn(move || {
-                let mut previous_price: f64 = 0.0;
-                loop{
-                    let curl_output = Command::new("curl")
-                        .args(&[
-                            "-s",
-                            "https://api.binance.com/api/v3/ticker/price?symbol=BTCUSDT",
-                        ])
-                        .stdout(Stdio::piped())
-                        .spawn()
-                        .expect("Failed to execute curl command");
-
-                    let curl_stdout = curl_output.stdout.expect("Failed to read stdout of curl");
-
-                    let jq_output = Command::new("jq")
-                        .args(&["-r", ".price"])
-                        .stdin(Stdio::from(curl_stdout))
-                        .output()
-                        .expect("Failed to execute jq command");
-
-                    let current_price = String::from_utf8_lossy(&jq_output.stdout);
-                    let current_price: f64 = current_price
-                        .trim()
-                        .parse()
-                        .expect("Failed to parse price as f64");
-
-                    if previous_price != 0.0 {
-                        let price_change = current_price - previous_price;
-                        let percentage_change = (price_change / previous_price) * 100.0;
-
-                        // println!("Price Change: {:.6} USD", price_change);
-                        // println!("{:.6}", percentage_change);
-                        if percentage_change > 0.0 && duration >=500 {
-                            
-                            duration = duration - 500;
-                        } else {
-                            duration = duration + 500;
-                        }
-                    }
-
-                    previous_price = current_price;
-                    // println!("Duration: {}", duration);
-
-                    tx_duration.send(duration).unwrap();
-                    tx_event.send(GameUpdate::DurationUpdate(duration)).unwrap();
-                    thread::sleep(Duration::from_millis(5000));
+    let display = &mut new_display(options.board);
+    let game = &mut new_game(&options, seed, bindings.clone(), &auto_shift, piece_colors, start_board.as_ref());
 
-                    // duration=0;
-                }
-            });
+    let game_over = terminal::set_terminal_raw_mode().and_then(|_restorer| {
+        if options.menu {
+            let start_board = start_board.as_ref();
+            return play_from_menu(&mut options, game, display, &bindings, &auto_shift, piece_colors, start_board);
+        }
+        let ticks = RealTicks::new(Duration::from_millis(TICK_MS));
+        play(
+            game,
+            display,
+            ticks,
+            options.price_feed.take(),
+            options.record_path.as_deref(),
+            options.garbage_interval,
+            countdown,
+        )
+    });
+
+    remember_theme(game.theme(), saved_theme);
+    // The seed is printed once the terminal is restored, so it can be copied to play the same pieces again.
+    match game_over {
+        Ok(Some(reason)) => println!("\nGame over: {}\nSeed: {}", reason, game.seed()),
+        Ok(None) => println!("\nSeed: {}", game.seed()),
+        Err(err) => {
+            eprintln!("\ntetrust: {}", err);
+            std::process::exit(1);
         }
-        
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use tetrust::tick::ManualTicks;
 
-        // Main game loop. The loop listens and responds to timer and keyboard updates received on a channel
-        // as sent by the threads spawned above.
-        loop {
-            display.clear_buffer();
-            self.render(display);
-            display.render();
+    /// Stands in for the terminal restorer `main` holds around a game, noting when it is dropped.
+    struct Restorer(Arc<AtomicBool>);
 
-            match rx_event.recv() {
-                Ok(update) => {
-                    match update {
-                        GameUpdate::KeyPress(key) => {
-                            match key {
-                                Key::Char('z') | Key::CtrlC => break,
-                                k => {
-                                    self.keypress(k);
-                                }
-                            };
-                        }
-                        GameUpdate::Tick => {
-                            self.advance_game();
-                        }
-                        GameUpdate::DurationUpdate(new_duration) => {
-                            duration = new_duration;
-                            self.duration = duration;
-                        }
-                    };
-                }
-                Err(err) => panic!("{}", err),
-            }
+    impl Drop for Restorer {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
         }
     }
-}
 
-fn get_input(stdin: &mut std::io::Stdin) -> Option<Key> {
-    use std::io::Read;
-
-    let c = &mut [0u8];
-    match stdin.read(c) {
-        Ok(_) => {
-            match std::str::from_utf8(c) {
-                Ok("w") => Some(Key::Up),
-                Ok("a") => Some(Key::Left),
-                Ok("s") => Some(Key::Down),
-                Ok("d") => Some(Key::Right),
-                Ok(" ") => Some(Key::Space),
-                Ok("\x03") => Some(Key::CtrlC),
-                // Escape sequence started - must read two more bytes.
-                Ok("\x1b") => {
-                    let code = &mut [0u8; 2];
-                    match stdin.read(code) {
-                        Ok(_) => match std::str::from_utf8(code) {
-                            Ok("[A") => Some(Key::Up),
-                            Ok("[B") => Some(Key::Down),
-                            Ok("[C") => Some(Key::Right),
-                            Ok("[D") => Some(Key::Left),
-                            _ => None,
-                        },
-                        Err(msg) => panic!("could not read from standard in: {}", msg),
-                    }
-                }
-                Ok(n) => Some(Key::Char(n.chars().next().unwrap())),
-                _ => None,
-            }
+    #[test]
+    fn quitting_the_game_loop_stops_every_thread_feeding_it() {
+        let (tx_event, rx_event) = mpsc::channel();
+        let mut game = Game::new_seeded(BoardConfig::default(), 1);
+        let duration = Arc::new(AtomicU64::new(game.duration()));
+        let (ticks, ticker) = ManualTicks::new();
+        let mut workers = Workers::new();
+        spawn_game_workers(&mut workers, ticks, Some(10), None, &duration, tx_event.clone());
+        assert!(ticker.fire(3));
+        tx_event.send(GameUpdate::KeyPress(Key::Char('z'), Instant::now())).unwrap();
+        drop(tx_event);
+
+        let display = &mut Display::headless(120, 40);
+        let result = run_game_loop(&mut game, display, &rx_event, &duration, None, Duration::ZERO);
+        assert!(matches!(result, Ok(None)));
+
+        // As in `play`, the workers go once the loop is done, and each thread takes its sender with it.
+        let started = Instant::now();
+        drop(workers);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!ticker.fire(1));
+        while let Ok(update) = rx_event.try_recv() {
+            assert!(matches!(update, GameUpdate::Tick | GameUpdate::Garbage));
         }
-        Err(msg) => panic!("could not read from standard in: {}", msg),
+        assert!(matches!(rx_event.try_recv(), Err(mpsc::TryRecvError::Disconnected)));
     }
-}
 
-fn main() {
-    let display = &mut Display::new(BOARD_WIDTH * 2 + 100, BOARD_HEIGHT + 2);
-    let game = &mut Game::new();
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tetrust-{}-{}", name, std::process::id()))
+    }
 
-    let _restorer = terminal::set_terminal_raw_mode();
+    #[test]
+    fn losing_every_sender_ends_the_game_with_the_recording_saved_and_the_terminal_restored() {
+        let (tx_event, rx_event) = mpsc::channel();
+        tx_event.send(GameUpdate::Tick).unwrap();
+        drop(tx_event);
+
+        let mut game = Game::new_seeded(BoardConfig::default(), 1);
+        let display = &mut Display::headless(120, 40);
+        let duration = AtomicU64::new(game.duration());
+        let path = temp_path("disconnect");
+        let restored = Arc::new(AtomicBool::new(false));
+        let result = Ok(Restorer(Arc::clone(&restored))).and_then(|_restorer| {
+            run_game_loop(&mut game, display, &rx_event, &duration, Some(&path), Duration::ZERO)
+        });
 
-    game.play(display);
+        assert!(matches!(result, Err(TetrustError::Disconnected)));
+        assert!(restored.load(Ordering::Relaxed));
+        let recorder = Recorder::load(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(recorder.unwrap().events.iter().filter(|(_, event)| *event == ReplayEvent::Tick).count(), 1);
+    }
 }
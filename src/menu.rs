@@ -0,0 +1,130 @@
+use tetrust::bindings::Key;
+use tetrust::display::Display;
+use tetrust::theme::Theme;
+use tetrust::{GameMode, MAX_START_LEVEL};
+
+/// The lines of the menu, in the order they are drawn.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum MenuItem {
+    Mode,
+    StartLevel,
+    Theme,
+    Play,
+    Quit,
+}
+
+impl MenuItem {
+    const ALL: [MenuItem; 5] = [MenuItem::Mode, MenuItem::StartLevel, MenuItem::Theme, MenuItem::Play, MenuItem::Quit];
+}
+
+/// What the player picked from the menu.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum MenuChoice {
+    Play,
+    Quit,
+}
+
+/// The menu shown before each game, choosing the mode, the level to start on and the theme. The up and down
+/// arrows move between its lines, left and right change the setting on a line, and Enter plays or quits.
+pub struct Menu {
+    modes: Vec<GameMode>,
+    mode: usize,
+    start_level: u32,
+    theme: Theme,
+    selected: usize,
+}
+
+impl Menu {
+    /// Creates a menu starting from the given settings. A mode with a goal keeps it, and the modes not
+    /// chosen are offered with their usual goals.
+    pub fn new(mode: GameMode, start_level: u32, theme: Theme) -> Menu {
+        let modes: Vec<GameMode> = ["endless", "marathon", "sprint", "cheese", "zen"]
+            .iter()
+            .filter_map(|name| if mode.name() == *name { Some(mode) } else { GameMode::from_name(name, None) })
+            .collect();
+        Menu {
+            mode: modes.iter().position(|&m| m == mode).unwrap_or(0),
+            modes,
+            start_level,
+            theme,
+            selected: MenuItem::ALL.iter().position(|&item| item == MenuItem::Play).unwrap_or(0),
+        }
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.modes[self.mode]
+    }
+
+    pub fn start_level(&self) -> u32 {
+        self.start_level
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Responds to a key press, returning the player's choice once they make one.
+    pub fn keypress(&mut self, key: Key) -> Option<MenuChoice> {
+        let items = MenuItem::ALL.len();
+        match key {
+            Key::Up => self.selected = (self.selected + items - 1) % items,
+            Key::Down => self.selected = (self.selected + 1) % items,
+            Key::Left => self.change(false),
+            Key::Right => self.change(true),
+            Key::Char('\r') | Key::Space => match MenuItem::ALL[self.selected] {
+                MenuItem::Play => return Some(MenuChoice::Play),
+                MenuItem::Quit => return Some(MenuChoice::Quit),
+                _ => self.change(true),
+            },
+            Key::Char('q') | Key::Char('z') | Key::CtrlC => return Some(MenuChoice::Quit),
+            _ => (),
+        }
+        None
+    }
+
+    /// Steps the setting on the selected line to its next or previous value, wrapping around at either end.
+    fn change(&mut self, forward: bool) {
+        let step = |value: usize, count: usize| if forward { (value + 1) % count } else { (value + count - 1) % count };
+        match MenuItem::ALL[self.selected] {
+            MenuItem::Mode => self.mode = step(self.mode, self.modes.len()),
+            MenuItem::StartLevel => {
+                self.start_level = step(self.start_level as usize - 1, MAX_START_LEVEL as usize) as u32 + 1;
+            }
+            MenuItem::Theme => {
+                let i = Theme::ALL.iter().position(|theme| theme.name == self.theme.name).unwrap_or(0);
+                self.theme = Theme::ALL[step(i, Theme::ALL.len())];
+            }
+            MenuItem::Play | MenuItem::Quit => (),
+        }
+    }
+
+    /// Draws the menu in the theme being chosen, with the selected line marked.
+    pub fn render(&self, display: &mut Display) {
+        let theme = &self.theme;
+        display.set_text("TETRUST", 4, 1, theme.border, theme.background);
+        for (i, item) in MenuItem::ALL.iter().enumerate() {
+            let line = match item {
+                MenuItem::Mode => format!("Mode:        < {} >", mode_label(self.mode())),
+                MenuItem::StartLevel => format!("Start level: < {} >", self.start_level),
+                MenuItem::Theme => format!("Theme:       < {} >", self.theme.name),
+                MenuItem::Play => String::from("Play"),
+                MenuItem::Quit => String::from("Quit"),
+            };
+            let marker = if i == self.selected { "> " } else { "  " };
+            display.set_text(&format!("{}{}", marker, line), 2, 3 + i as u32, theme.text, theme.background);
+        }
+        let help = "Up/Down: choose  Left/Right: change  Enter: select";
+        display.set_text(help, 2, 4 + MenuItem::ALL.len() as u32, theme.text, theme.background);
+    }
+}
+
+/// Returns the name a mode is listed under, along with its goal.
+fn mode_label(mode: GameMode) -> String {
+    match mode {
+        GameMode::Endless => String::from("Endless"),
+        GameMode::Marathon { goal } => format!("Marathon, {} lines", goal),
+        GameMode::Sprint { goal } => format!("Sprint, {} lines", goal),
+        GameMode::Cheese { rows } => format!("Cheese, {} rows", rows),
+        GameMode::Zen => String::from("Zen"),
+    }
+}
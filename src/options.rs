@@ -0,0 +1,385 @@
+use tetrust::price::{PriceFeed, DEFAULT_MAX_DURATION_MS, DEFAULT_MIN_DURATION_MS, DEFAULT_PRICE_URL, DEFAULT_SYMBOL};
+use tetrust::theme::Theme;
+use tetrust::{
+    BoardConfig, ClearGravity, GameMode, HoldPolicy, Randomizer, RotationSystem, LINE_CLEAR_DELAY_MS, LOCK_RESET_LIMIT,
+    MAX_NEXT_PIECES, MAX_LEVEL, MAX_START_LEVEL, NEXT_PIECE_COUNT,
+};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How many seconds the countdown before each game lasts, unless changed with `--countdown`.
+pub const DEFAULT_COUNTDOWN_SECONDS: u64 = 3;
+
+pub const USAGE: &str = "\
+usage: tetrust [options]
+
+options:
+    --symbol <SYMBOL>    ticker symbol whose price drives the game speed (default: BTCUSDT)
+    --price-url <URL>    ticker price endpoint to query (default: the Binance ticker API)
+    --no-price           don't fetch prices; play at a fixed speed
+    --min-duration <MS>  fastest the price can make a row fall, in milliseconds (default: 50)
+    --max-duration <MS>  slowest the price can make a row fall, in milliseconds (default: 1000)
+    --seed <SEED>        seed the piece sequence so a game can be replayed
+    --theme <NAME>       color theme: guideline, monochrome, high-contrast or deuteranopia (default: the
+                         theme last played with)
+    --record <FILE>      record each game to a file when it ends
+    --replay <FILE>      play back a recorded game
+    --clear-delay <MS>   how long cleared lines flash before they're removed; 0 to skip (default: 150)
+    --entry-delay <MS>   how long the next piece takes to come in after one locks, as in classic games; a
+                         hold or rotation pressed meanwhile applies to it as it spawns (default: 0)
+    --width <COLUMNS>    board width (default: 10)
+    --height <ROWS>      board height, counting the 2 hidden rows at the top (default: 20)
+    --big                play with every cell doubled in size, on a board half as wide and high
+    --load-board <FILE>  start from the board drawn in a file: a line per row from the top, including the
+                         hidden rows, with '.' for an empty cell, a piece letter or '#' for garbage
+    --lock-resets <N>    how many times moving or rotating a landed piece may delay its lock (default: 15)
+    --das <MS>           delay before a held movement key repeats (default: 170)
+    --arr <MS>           delay between repeats of a held movement key (default: 30)
+    --das-charge         keep a movement key held as a piece locks shifting the next piece straight away,
+                         instead of waiting out the delay again
+    --soft-drop-step <N> rows each press of the soft drop key moves the piece (default: 1)
+    --no-hard-drop       make the hard drop key do nothing
+    --no-lock-out        keep playing when a piece locks entirely in the hidden rows above the visible board,
+                         as long as nothing is left in the top row
+    --mode <MODE>        endless, marathon (clear 150 lines), sprint (clear 40 lines fast), cheese (dig
+                         through 10 rows of garbage fast) or zen (no speed-up and no game over; the bottom
+                         rows go to make room) (default: endless)
+    --goal <N>           lines to clear to win a marathon or sprint, or rows of garbage in a cheese race
+    --start-level <N>    level to start on, from 1 to 15 (default: 1)
+    --max-level <N>      level to stop speeding up at, no lower than the start level (default: 30)
+    --randomizer <NAME>  how pieces are dealt: bag (shuffled sets of seven), double-bag (sets of fourteen),
+                         nes (the NES's random picks) or random (default: bag)
+    --rotation <SYSTEM>  how pieces turn: srs (the guideline kicks), ars (the TGM games' kicks, with pieces
+                         spawning point down) or classic (no kicks) (default: srs)
+    --clear-gravity <G>  what the cells above a line clear do: naive (move down with their rows), sticky
+                         (fall in connected groups) or cascade (fall cell by cell) (default: naive)
+    --garbage <MS>       push a row of garbage up from the bottom this often
+    --countdown <SECS>   count down this long before each game starts; any key skips it (default: 3)
+    --no-menu            start playing straight away instead of choosing the mode, start level and theme
+                         from a menu, and quit when the game ends
+    --versus             two players on one keyboard, sending each other garbage: the left player uses
+                         WASD, Q/E to rotate and C to hold; the right uses the arrows or IJKL, U/O and M
+    --hold <POLICY>      how often hold may be used: unlimited, once (per piece), off, or a number of holds
+                         per game (default: once)
+    --previews <N>       how many upcoming pieces to show, from 0 to 5 (default: 3)
+    --blind              see only the falling piece: no hold, no upcoming pieces, and only the score, level
+                         and lines beside the board
+    --mirror-controls    swap left and right movement and the two rotation directions, for left-handed play
+    --show-hidden        draw the hidden rows above the board that pieces spawn in dimmed and bordered
+    --grid               dot a faint grid over the board's empty cells; 'b' shows or hides it in a game
+    --effects            draw cosmetic effects, such as a trail behind each hard-dropped piece
+    --no-lock-flash      don't flash each piece white as it locks
+    --finesse            count the inputs used to place each piece against the fewest it needed, and flag
+                         the pieces that took more
+    --invisible <MS>     hide each piece this long after it locks, though it's still there to land on; 'r'
+                         shows the board for a second, once every 10 seconds
+    --debug              show internals such as the raw speed in milliseconds per row, and let 'u' undo
+                         the last action on the piece
+    -h, --help           print this message";
+
+/// Settings chosen on the command line.
+pub struct Options {
+    /// The price feed driving the game speed, or `None` if prices are disabled.
+    pub price_feed: Option<PriceFeed>,
+    /// Seed for the piece sequence, or `None` for a random one.
+    pub seed: Option<u64>,
+    /// The theme chosen on the command line, if any; otherwise the last one played with is used.
+    pub theme: Option<Theme>,
+    /// The size of the board to play on.
+    pub board: BoardConfig,
+    /// Where to record games for replaying them later.
+    pub record_path: Option<PathBuf>,
+    /// A recorded game to play back instead of playing.
+    pub replay_path: Option<PathBuf>,
+    /// A file holding the layout to start each game from.
+    pub board_path: Option<PathBuf>,
+    /// How long cleared lines flash for, in milliseconds.
+    pub line_clear_delay: u64,
+    /// How long the next piece takes to come in after one locks, in milliseconds.
+    pub entry_delay: u64,
+    /// How many times a landed piece's lock can be put off by moving or rotating it.
+    pub lock_reset_limit: u32,
+    /// Delayed auto shift in milliseconds, overriding the config file.
+    pub das: Option<u64>,
+    /// Auto repeat rate in milliseconds, overriding the config file.
+    pub arr: Option<u64>,
+    /// Whether a held movement key shifts each new piece without waiting out the delayed auto shift again.
+    pub das_charge: bool,
+    /// Rows a soft drop key press moves the piece, overriding the config file.
+    pub soft_drop_step: Option<u32>,
+    /// Whether the hard drop key is turned off, whatever the config file says.
+    pub no_hard_drop: bool,
+    /// Whether a piece locking entirely in the hidden rows ends the game.
+    pub lock_out: bool,
+    /// How the game is won, if it can be.
+    pub mode: GameMode,
+    pub hold_policy: HoldPolicy,
+    pub start_level: u32,
+    /// The level the game stops rising at.
+    pub max_level: u32,
+    /// How often a row of garbage comes up, in milliseconds, if it does at all.
+    pub garbage_interval: Option<u64>,
+    pub randomizer: Randomizer,
+    pub rotation_system: RotationSystem,
+    pub clear_gravity: ClearGravity,
+    /// Whether to choose the mode, start level and theme from a menu before each game.
+    pub menu: bool,
+    /// Whether two players play each other side by side.
+    pub versus: bool,
+    /// How many seconds to count down before each game starts.
+    pub countdown: u64,
+    /// Whether left and right movement and the two rotations are swapped.
+    pub mirror_controls: bool,
+    /// Whether to set the hidden rows apart from the playfield.
+    pub show_hidden: bool,
+    /// Whether to draw a grid in the board's empty cells.
+    pub grid: bool,
+    /// How many upcoming pieces to show.
+    pub previews: usize,
+    /// Whether to leave everything but the score, level and lines out of the sidebar.
+    pub blind: bool,
+    /// Whether to draw cosmetic effects.
+    pub effects: bool,
+    /// Whether to flash each piece as it locks.
+    pub lock_flash: bool,
+    /// Whether to count each piece's inputs against the fewest it needed.
+    pub finesse: bool,
+    /// How long locked pieces stay in sight, in milliseconds, when they're hidden.
+    pub invisible: Option<u64>,
+    /// Whether to show internals useful when debugging, and allow undoing.
+    pub debug: bool,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parses the command line arguments, not including the program name.
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut symbol = String::from(DEFAULT_SYMBOL);
+        let mut price_url = String::from(DEFAULT_PRICE_URL);
+        let mut price_enabled = true;
+        let mut min_duration = DEFAULT_MIN_DURATION_MS;
+        let mut max_duration = DEFAULT_MAX_DURATION_MS;
+        let mut seed = None;
+        let mut theme = None;
+        let mut board = BoardConfig::default();
+        let mut record_path = None;
+        let mut replay_path = None;
+        let mut board_path = None;
+        let mut line_clear_delay = LINE_CLEAR_DELAY_MS;
+        let mut entry_delay = 0;
+        let mut lock_reset_limit = LOCK_RESET_LIMIT;
+        let mut das = None;
+        let mut arr = None;
+        let mut das_charge = false;
+        let mut soft_drop_step = None;
+        let mut no_hard_drop = false;
+        let mut lock_out = true;
+        let mut mode_name = String::from(GameMode::default().name());
+        let mut goal = None;
+        let mut hold_policy = HoldPolicy::default();
+        let mut start_level = 1;
+        let mut max_level = MAX_LEVEL;
+        let mut garbage_interval = None;
+        let mut randomizer = Randomizer::default();
+        let mut rotation_system = RotationSystem::default();
+        let mut clear_gravity = ClearGravity::default();
+        let mut menu = true;
+        let mut versus = false;
+        let mut countdown = DEFAULT_COUNTDOWN_SECONDS;
+        let mut mirror_controls = false;
+        let mut show_hidden = false;
+        let mut grid = false;
+        let mut previews = NEXT_PIECE_COUNT;
+        let mut blind = false;
+        let mut effects = false;
+        let mut lock_flash = true;
+        let mut finesse = false;
+        let mut invisible = None;
+        let mut big = false;
+        let mut debug = false;
+        let mut help = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--symbol" => symbol = value_for(&arg, args.next())?,
+                "--price-url" => price_url = value_for(&arg, args.next())?,
+                "--no-price" => price_enabled = false,
+                "--min-duration" => min_duration = parse_value(&arg, args.next())?,
+                "--max-duration" => max_duration = parse_value(&arg, args.next())?,
+                "--seed" => seed = Some(parse_value(&arg, args.next())?),
+                "--theme" => {
+                    let name = value_for(&arg, args.next())?;
+                    theme = Some(Theme::by_name(&name).ok_or_else(|| format!("unknown theme '{}'", name))?);
+                }
+                "--record" => record_path = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--replay" => replay_path = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--load-board" => board_path = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--clear-delay" => line_clear_delay = parse_value(&arg, args.next())?,
+                "--entry-delay" => entry_delay = parse_value(&arg, args.next())?,
+                "--lock-resets" => lock_reset_limit = parse_value(&arg, args.next())?,
+                "--width" => board.width = parse_value(&arg, args.next())?,
+                "--height" => board.height = parse_value(&arg, args.next())?,
+                "--big" => big = true,
+                "--das" => das = Some(parse_value(&arg, args.next())?),
+                "--arr" => arr = Some(parse_value(&arg, args.next())?),
+                "--das-charge" => das_charge = true,
+                "--soft-drop-step" => soft_drop_step = Some(parse_value(&arg, args.next())?),
+                "--no-hard-drop" => no_hard_drop = true,
+                "--no-lock-out" => lock_out = false,
+                "--mode" => mode_name = value_for(&arg, args.next())?,
+                "--goal" => goal = Some(parse_value(&arg, args.next())?),
+                "--randomizer" => {
+                    let name = value_for(&arg, args.next())?;
+                    randomizer = Randomizer::from_name(&name).ok_or_else(|| format!("unknown randomizer '{}'", name))?;
+                }
+                "--rotation" => {
+                    let name = value_for(&arg, args.next())?;
+                    rotation_system =
+                        RotationSystem::from_name(&name).ok_or_else(|| format!("unknown rotation system '{}'", name))?;
+                }
+                "--clear-gravity" => {
+                    let name = value_for(&arg, args.next())?;
+                    clear_gravity =
+                        ClearGravity::from_name(&name).ok_or_else(|| format!("unknown clear gravity '{}'", name))?;
+                }
+                "--garbage" => garbage_interval = Some(parse_value(&arg, args.next())?),
+                "--start-level" => start_level = parse_value(&arg, args.next())?,
+                "--max-level" => max_level = parse_value(&arg, args.next())?,
+                "--hold" => {
+                    let name = value_for(&arg, args.next())?;
+                    hold_policy =
+                        HoldPolicy::from_name(&name).ok_or_else(|| format!("unknown hold policy '{}'", name))?;
+                }
+                "--no-menu" => menu = false,
+                "--versus" => versus = true,
+                "--countdown" => countdown = parse_value(&arg, args.next())?,
+                "--previews" => previews = parse_value(&arg, args.next())?,
+                "--blind" => blind = true,
+                "--mirror-controls" => mirror_controls = true,
+                "--show-hidden" => show_hidden = true,
+                "--grid" => grid = true,
+                "--effects" => effects = true,
+                "--no-lock-flash" => lock_flash = false,
+                "--finesse" => finesse = true,
+                "--invisible" => invisible = Some(parse_value(&arg, args.next())?),
+                "--debug" => debug = true,
+                "-h" | "--help" => help = true,
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+
+        if big {
+            board.width /= 2;
+            board.height /= 2;
+            board.scale = 2;
+        }
+        board.validate()?;
+        if !(1..=MAX_START_LEVEL).contains(&start_level) {
+            return Err(format!("start level must be between 1 and {}", MAX_START_LEVEL));
+        }
+        if max_level < start_level {
+            return Err(String::from("max level can't be lower than the start level"));
+        }
+        if soft_drop_step == Some(0) {
+            return Err(String::from("'--soft-drop-step' must be at least 1"));
+        }
+        if blind {
+            hold_policy = HoldPolicy::Disabled;
+            previews = 0;
+        }
+        if previews > MAX_NEXT_PIECES {
+            return Err(format!("at most {} upcoming pieces can be shown", MAX_NEXT_PIECES));
+        }
+        let mode = GameMode::from_name(&mode_name, goal).ok_or_else(|| format!("unknown mode '{}'", mode_name))?;
+        if goal.is_some() && mode.goal().is_none() {
+            return Err(format!("'--goal' doesn't apply to {} mode", mode.name()));
+        }
+        if versus && (record_path.is_some() || replay_path.is_some()) {
+            return Err(String::from("versus games can't be recorded or replayed"));
+        }
+        if min_duration == 0 || min_duration > max_duration {
+            return Err(String::from("'--min-duration' must be above 0 and no more than '--max-duration'"));
+        }
+        let price_feed = price_enabled.then(|| {
+            let mut price_feed = PriceFeed::new(price_url, symbol);
+            price_feed.set_duration_range(min_duration, max_duration);
+            price_feed
+        });
+
+        Ok(Options {
+            price_feed,
+            seed,
+            theme,
+            board,
+            record_path,
+            replay_path,
+            board_path,
+            line_clear_delay,
+            entry_delay,
+            lock_reset_limit,
+            das,
+            arr,
+            das_charge,
+            soft_drop_step,
+            no_hard_drop,
+            lock_out,
+            mode,
+            hold_policy,
+            start_level,
+            max_level,
+            garbage_interval,
+            randomizer,
+            rotation_system,
+            clear_gravity,
+            menu,
+            versus,
+            countdown,
+            mirror_controls,
+            show_hidden,
+            grid,
+            previews,
+            blind,
+            effects,
+            lock_flash,
+            finesse,
+            invisible,
+            debug,
+            help,
+        })
+    }
+}
+
+fn value_for(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing value for '{}'", option))
+}
+
+fn parse_value<T: FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value_for(option, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn blind_turns_off_hold_and_the_previews() {
+        let options = parse(&["--blind"]).unwrap();
+        assert!(options.blind);
+        assert_eq!(options.hold_policy, HoldPolicy::Disabled);
+        assert_eq!(options.previews, 0);
+    }
+
+    #[test]
+    fn blind_overrides_the_hold_and_preview_options() {
+        let options = parse(&["--hold", "unlimited", "--previews", "3", "--blind"]).unwrap();
+        assert_eq!(options.hold_policy, HoldPolicy::Disabled);
+        assert_eq!(options.previews, 0);
+    }
+}
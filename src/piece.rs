@@ -0,0 +1,506 @@
+use crate::util::{Color, Direction};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Which of the seven tetrominoes a piece is.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PieceType {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl PieceType {
+    pub const ALL: [PieceType; 7] = [
+        PieceType::I,
+        PieceType::O,
+        PieceType::T,
+        PieceType::S,
+        PieceType::Z,
+        PieceType::J,
+        PieceType::L,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PieceType::I => "I",
+            PieceType::O => "O",
+            PieceType::T => "T",
+            PieceType::S => "S",
+            PieceType::Z => "Z",
+            PieceType::J => "J",
+            PieceType::L => "L",
+        }
+    }
+}
+
+pub struct Piece {
+    pub(crate) kind: PieceType,
+    pub(crate) color: Color,
+    pub(crate) shape: Vec<Vec<u8>>,
+    /// Current rotation state, counted in clockwise quarter turns from the spawn orientation (0, R, 2, L).
+    pub(crate) rotation: usize,
+}
+
+impl Clone for Piece {
+    fn clone(&self) -> Piece {
+        let mut p = Piece {
+            kind: self.kind,
+            color: self.color,
+            shape: Vec::with_capacity(self.shape.len()),
+            rotation: self.rotation,
+        };
+        for row in &self.shape {
+            p.shape.push(row.clone());
+        }
+        p
+    }
+}
+
+impl Piece {
+    pub fn kind(&self) -> PieceType {
+        self.kind
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Creates a piece of the given type in its spawn orientation.
+    pub fn of_type(kind: PieceType) -> Piece {
+        match kind {
+            PieceType::I => Piece::new_i(),
+            PieceType::O => Piece::new_o(),
+            PieceType::T => Piece::new_t(),
+            PieceType::S => Piece::new_s(),
+            PieceType::Z => Piece::new_z(),
+            PieceType::J => Piece::new_j(),
+            PieceType::L => Piece::new_l(),
+        }
+    }
+
+    pub fn new_o() -> Piece {
+        Piece {
+            kind: PieceType::O,
+            color: Color::Yellow,
+            shape: vec![vec![1, 1], vec![1, 1]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_l() -> Piece {
+        Piece {
+            kind: PieceType::L,
+            color: Color::Orange,
+            shape: vec![vec![0, 0, 1], vec![1, 1, 1], vec![0, 0, 0]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_j() -> Piece {
+        Piece {
+            kind: PieceType::J,
+            color: Color::Blue,
+            shape: vec![vec![1, 0, 0], vec![1, 1, 1], vec![0, 0, 0]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_t() -> Piece {
+        Piece {
+            kind: PieceType::T,
+            color: Color::Purple,
+            shape: vec![vec![0, 1, 0], vec![1, 1, 1], vec![0, 0, 0]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_s() -> Piece {
+        Piece {
+            kind: PieceType::S,
+            color: Color::Green,
+            shape: vec![vec![0, 1, 1], vec![1, 1, 0], vec![0, 0, 0]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_z() -> Piece {
+        Piece {
+            kind: PieceType::Z,
+            color: Color::Red,
+            shape: vec![vec![1, 1, 0], vec![0, 1, 1], vec![0, 0, 0]],
+            rotation: 0,
+        }
+    }
+
+    pub fn new_i() -> Piece {
+        Piece {
+            kind: PieceType::I,
+            color: Color::Cyan,
+            shape: vec![
+                vec![0, 0, 0, 0],
+                vec![1, 1, 1, 1],
+                vec![0, 0, 0, 0],
+                vec![0, 0, 0, 0],
+            ],
+            rotation: 0,
+        }
+    }
+
+    /// Turns the shape a quarter turn within its square grid, in place. Each pass of the loop moves four
+    /// cells one corner round a ring of the grid, so a clockwise turn moves the cell at `(row, col)` to
+    /// `(col, size - row - 1)`; four turns either way, or one each way, give back the original shape. This
+    /// holds for the I piece's 4x4 grid as well as the 3x3 ones, which have a fixed centre cell.
+    pub fn rotate(&mut self, direction: Direction) {
+        let size = self.shape.len();
+
+        for row in 0..size / 2 {
+            for col in row..(size - row - 1) {
+                let t = self.shape[row][col];
+
+                match direction {
+                    Direction::Left => {
+                        self.shape[row][col] = self.shape[col][size - row - 1];
+                        self.shape[col][size - row - 1] =
+                            self.shape[size - row - 1][size - col - 1];
+                        self.shape[size - row - 1][size - col - 1] =
+                            self.shape[size - col - 1][row];
+                        self.shape[size - col - 1][row] = t;
+                    }
+                    Direction::Right => {
+                        self.shape[row][col] = self.shape[size - col - 1][row];
+                        self.shape[size - col - 1][row] =
+                            self.shape[size - row - 1][size - col - 1];
+                        self.shape[size - row - 1][size - col - 1] =
+                            self.shape[col][size - row - 1];
+                        self.shape[col][size - row - 1] = t;
+                    }
+                }
+            }
+        }
+
+        self.rotation = match direction {
+            Direction::Left => (self.rotation + 3) % 4,
+            Direction::Right => (self.rotation + 1) % 4,
+        };
+    }
+
+    pub(crate) fn each_point(&self, callback: &mut dyn FnMut(i32, i32)) {
+        let piece_width = self.shape.len() as i32;
+        for row in 0..piece_width {
+            for col in 0..piece_width {
+                if self.shape[row as usize][col as usize] != 0 {
+                    callback(row, col);
+                }
+            }
+        }
+    }
+
+    /// Calls `predicate` with the row and column of each filled cell, stopping at the first one for which it
+    /// returns true. Returns true if any cell matched.
+    pub(crate) fn any_point(&self, predicate: &mut dyn FnMut(i32, i32) -> bool) -> bool {
+        let piece_width = self.shape.len() as i32;
+        for row in 0..piece_width {
+            for col in 0..piece_width {
+                if self.shape[row as usize][col as usize] != 0 && predicate(row, col) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// How the sequence of pieces is randomized.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum Randomizer {
+    /// Deals out a shuffled set of all seven pieces before shuffling the next set, so the same piece never
+    /// comes up more than twice in a row and no piece is ever more than 12 pieces away.
+    #[default]
+    SevenBag,
+    /// Like the seven-bag, but shuffles two of each piece together.
+    DoubleBag,
+    /// The original NES randomizer: picks a piece at random, picking again once if it rolls the previous
+    /// piece or a dummy eighth choice.
+    ClassicNes,
+    /// Picks each piece at random, with no history.
+    PureRandom,
+}
+
+impl Randomizer {
+    pub const ALL: [Randomizer; 4] = [
+        Randomizer::SevenBag,
+        Randomizer::DoubleBag,
+        Randomizer::ClassicNes,
+        Randomizer::PureRandom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Randomizer::SevenBag => "bag",
+            Randomizer::DoubleBag => "double-bag",
+            Randomizer::ClassicNes => "nes",
+            Randomizer::PureRandom => "random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Randomizer> {
+        Randomizer::ALL.iter().copied().find(|randomizer| randomizer.name() == name)
+    }
+}
+
+/// Implements a queue of randomized tetrominoes, generated by its randomizer.
+///
+/// By default, instead of a purely random stream of tetromino types, this queue generates a random ordering
+/// of all possible types and ensures all of those pieces are used before re-generating a new random set.
+/// This helps avoid pathological cases where purely random generation provides the same piece type
+/// repeately in a row, or fails to provide a required piece for a very long time.
+#[derive(Clone)]
+pub struct PieceBag {
+    pieces: Vec<Piece>,
+    rng: StdRng,
+    randomizer: Randomizer,
+    /// The last piece generated, which the NES randomizer tries not to repeat.
+    last: Option<PieceType>,
+}
+
+impl PieceBag {
+    /// Creates a seven-bag whose sequence of pieces is fully determined by the seed.
+    pub fn new_seeded(seed: u64) -> PieceBag {
+        PieceBag::with_randomizer(seed, Randomizer::default())
+    }
+
+    /// Creates a queue using the given randomizer, whose sequence of pieces is fully determined by the seed.
+    pub fn with_randomizer(seed: u64, randomizer: Randomizer) -> PieceBag {
+        let mut p = PieceBag {
+            pieces: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            randomizer,
+            last: None,
+        };
+        p.fill_bag();
+        p
+    }
+
+    pub fn randomizer(&self) -> Randomizer {
+        self.randomizer
+    }
+
+    /// Removes and returns the next piece in the queue. The queue is refilled before a piece is taken should
+    /// it ever be found empty, and again as soon as it runs out, so it always holds a next piece.
+    pub fn pop(&mut self) -> Piece {
+        if self.pieces.is_empty() {
+            self.fill_bag();
+        }
+        let piece = self.pieces.remove(0);
+        if self.pieces.is_empty() {
+            self.fill_bag();
+        }
+        piece
+    }
+
+    /// Returns the next piece in the queue without removing it. The queue is filled when it's created and
+    /// refilled by `pop` whenever it runs out, so there is always a next piece.
+    pub fn peek(&self) -> &Piece {
+        &self.pieces[0]
+    }
+
+    /// Returns copies of the next `n` pieces in the queue, in the order `pop` will return them. Pieces
+    /// beyond the current bag are previewed by filling bags from a copy of the random number generator, so
+    /// the queue itself is left untouched.
+    pub fn peek_n(&self, n: usize) -> Vec<Piece> {
+        let mut preview = PieceBag {
+            pieces: self.pieces.clone(),
+            rng: self.rng.clone(),
+            randomizer: self.randomizer,
+            last: self.last,
+        };
+        while preview.pieces.len() < n {
+            preview.fill_bag();
+        }
+        preview.pieces.truncate(n);
+        preview.pieces
+    }
+
+    /// Adds the randomizer's next pieces to the piece queue: a whole bag for the bag randomizers, or a
+    /// single piece for the others.
+    fn fill_bag(&mut self) {
+        let bags = match self.randomizer {
+            Randomizer::SevenBag => 1,
+            Randomizer::DoubleBag => 2,
+            Randomizer::ClassicNes => {
+                // Roll one of the seven pieces or a dummy eighth; rolling the dummy or a repeat earns a reroll
+                // among the seven, which is kept whatever it is.
+                let roll = self.rng.gen_range(0..8);
+                let kind = match PieceType::ALL.get(roll) {
+                    Some(&kind) if Some(kind) != self.last => kind,
+                    _ => PieceType::ALL[self.rng.gen_range(0..7)],
+                };
+                return self.push(kind);
+            }
+            Randomizer::PureRandom => {
+                let kind = PieceType::ALL[self.rng.gen_range(0..7)];
+                return self.push(kind);
+            }
+        };
+
+        // Shuffled from this order rather than `PieceType::ALL`'s, which keeps the pieces each seed gives the
+        // same as before there was a choice of randomizer.
+        use PieceType::*;
+        let mut pieces: Vec<PieceType> = (0..bags).flat_map(|_| [O, L, J, T, S, Z, I]).collect();
+        while !pieces.is_empty() {
+            let i = self.rng.gen::<usize>() % pieces.len();
+            self.push(pieces.swap_remove(i));
+        }
+    }
+
+    fn push(&mut self, kind: PieceType) {
+        self.last = Some(kind);
+        self.pieces.push(Piece::of_type(kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constructors_use_the_guideline_colors() {
+        let pieces = [
+            (Piece::new_i(), Color::Cyan),
+            (Piece::new_o(), Color::Yellow),
+            (Piece::new_t(), Color::Purple),
+            (Piece::new_s(), Color::Green),
+            (Piece::new_z(), Color::Red),
+            (Piece::new_j(), Color::Blue),
+            (Piece::new_l(), Color::Orange),
+        ];
+        for (piece, color) in pieces {
+            assert_eq!(piece.color(), color, "{:?} piece", piece.kind());
+        }
+    }
+
+    #[test]
+    fn peek_shows_each_next_piece_as_the_bag_runs_out_and_refills() {
+        let mut bag = PieceBag::new_seeded(1);
+        for _ in 0..3 * PieceType::ALL.len() {
+            let next = bag.peek().kind();
+            assert_eq!(bag.pop().kind(), next);
+        }
+    }
+
+    #[test]
+    fn four_turns_the_same_way_give_back_the_shape() {
+        for kind in PieceType::ALL {
+            for direction in [Direction::Left, Direction::Right] {
+                let mut piece = Piece::of_type(kind);
+                for _ in 0..4 {
+                    piece.rotate(direction);
+                }
+                assert_eq!(piece.shape, Piece::of_type(kind).shape, "{:?} turned {:?}", kind, direction);
+                assert_eq!(piece.rotation, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn a_turn_each_way_gives_back_the_shape() {
+        for kind in PieceType::ALL {
+            let mut piece = Piece::of_type(kind);
+            for _ in 0..4 {
+                let before = piece.clone();
+                piece.rotate(Direction::Left);
+                assert_ne!(piece.rotation, before.rotation);
+                piece.rotate(Direction::Right);
+                assert_eq!(piece.shape, before.shape, "{:?} from rotation {}", kind, before.rotation);
+                assert_eq!(piece.rotation, before.rotation);
+                piece.rotate(Direction::Right);
+            }
+        }
+    }
+
+    /// Returns the kinds of the first `count` pieces a bag deals.
+    fn deal(bag: &mut PieceBag, count: usize) -> Vec<PieceType> {
+        (0..count).map(|_| bag.pop().kind()).collect()
+    }
+
+    #[test]
+    fn same_seed_deals_the_same_pieces() {
+        let (mut first, mut second) = (PieceBag::new_seeded(42), PieceBag::new_seeded(42));
+        assert_eq!(deal(&mut first, 20), deal(&mut second, 20));
+    }
+
+    #[test]
+    fn different_seeds_deal_different_pieces() {
+        let (mut first, mut second) = (PieceBag::new_seeded(1), PieceBag::new_seeded(2));
+        assert_ne!(deal(&mut first, 20), deal(&mut second, 20));
+    }
+
+    #[test]
+    fn constructors_report_their_kind() {
+        let pieces = [
+            (Piece::new_i(), PieceType::I),
+            (Piece::new_o(), PieceType::O),
+            (Piece::new_t(), PieceType::T),
+            (Piece::new_s(), PieceType::S),
+            (Piece::new_z(), PieceType::Z),
+            (Piece::new_j(), PieceType::J),
+            (Piece::new_l(), PieceType::L),
+        ];
+        for (piece, kind) in pieces {
+            assert_eq!(piece.kind(), kind);
+            assert_eq!(Piece::of_type(kind).kind(), kind);
+        }
+    }
+
+    #[test]
+    fn kind_survives_clone_and_rotate() {
+        let piece = Piece::new_s();
+        let mut turned = piece.clone();
+        turned.rotate(Direction::Right);
+        assert_eq!(turned.kind(), piece.kind());
+    }
+
+    /// Returns the longest run of pieces a randomizer deals without one of the given kind, over `count`
+    /// pieces.
+    fn longest_drought(randomizer: Randomizer, kind: PieceType, count: usize) -> usize {
+        let mut bag = PieceBag::with_randomizer(7, randomizer);
+        let (mut longest, mut current) = (0, 0);
+        for _ in 0..count {
+            if bag.pop().kind() == kind {
+                current = 0;
+            } else {
+                current += 1;
+                longest = longest.max(current);
+            }
+        }
+        longest
+    }
+
+    #[test]
+    fn seven_bag_never_goes_more_than_12_pieces_without_a_piece() {
+        for kind in PieceType::ALL {
+            assert!(longest_drought(Randomizer::SevenBag, kind, 10_000) <= 12, "{:?} piece", kind);
+        }
+    }
+
+    #[test]
+    fn pure_random_can_go_longer_without_a_piece() {
+        assert!(longest_drought(Randomizer::PureRandom, PieceType::I, 10_000) > 12);
+    }
+
+    #[test]
+    fn nes_randomizer_repeats_pieces_less_than_pure_random() {
+        let repeats = |randomizer| {
+            let mut bag = PieceBag::with_randomizer(7, randomizer);
+            let kinds: Vec<_> = (0..10_000).map(|_| bag.pop().kind()).collect();
+            kinds.windows(2).filter(|pair| pair[0] == pair[1]).count()
+        };
+        // Pure random repeats about one piece in seven; rolling again makes a repeat a few times rarer.
+        assert!(repeats(Randomizer::ClassicNes) * 2 < repeats(Randomizer::PureRandom));
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::time::Duration;
+
+pub const DEFAULT_PRICE_URL: &str = "https://api.binance.com/api/v3/ticker/price";
+pub const DEFAULT_SYMBOL: &str = "BTCUSDT";
+/// How long a price request may take before it is given up on.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The fastest price changes can make a row fall, in milliseconds.
+pub const DEFAULT_MIN_DURATION_MS: u64 = 50;
+/// The slowest price changes can make a row fall, in milliseconds.
+pub const DEFAULT_MAX_DURATION_MS: u64 = 1000;
+/// How much each percent the price moves scales the time a row takes to fall: a rise of one percent
+/// divides it by e, and a fall of one percent multiplies it by e.
+const PRICE_SENSITIVITY: f64 = 1.0;
+
+#[derive(Debug)]
+pub enum PriceError {
+    Http(String),
+    Parse(String),
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceError::Http(msg) => write!(f, "price request failed: {}", msg),
+            PriceError::Parse(msg) => write!(f, "could not parse price: {}", msg),
+        }
+    }
+}
+
+/// A ticker endpoint and the symbol whose price is read from it, along with the range of speeds the price
+/// may move the game between.
+#[derive(Clone)]
+pub struct PriceFeed {
+    url: String,
+    symbol: String,
+    min_duration_ms: u64,
+    max_duration_ms: u64,
+}
+
+impl PriceFeed {
+    pub fn new(url: String, symbol: String) -> PriceFeed {
+        PriceFeed {
+            url,
+            symbol,
+            min_duration_ms: DEFAULT_MIN_DURATION_MS,
+            max_duration_ms: DEFAULT_MAX_DURATION_MS,
+        }
+    }
+
+    /// Sets the fastest and slowest, in milliseconds per row, that price changes can make the game.
+    pub fn set_duration_range(&mut self, min_ms: u64, max_ms: u64) {
+        self.min_duration_ms = min_ms;
+        self.max_duration_ms = max_ms;
+    }
+
+    /// Returns how long a row should take to fall once the price has moved by `percentage_change` percent
+    /// from a game where it takes `current` milliseconds. A rise speeds the game up and a fall slows it
+    /// down, in proportion to the size of the move, within the feed's range. A game already outside the
+    /// range, say from levelling up past its fastest speed, is only ever moved back towards it.
+    pub fn price_change_to_duration(&self, percentage_change: f64, current: u64) -> u64 {
+        let scaled = current as f64 * (-percentage_change * PRICE_SENSITIVITY).exp();
+        let (min, max) = (self.min_duration_ms.min(current), self.max_duration_ms.max(current));
+        (scaled.round() as u64).clamp(min, max).max(1)
+    }
+
+    /// Fetches the current price of the symbol from the ticker endpoint.
+    pub fn fetch_price(&self) -> Result<f64, PriceError> {
+        self.fetch_price_with(http_get)
+    }
+
+    /// Fetches the current price of the symbol with `get`, which is given the endpoint and the symbol and
+    /// returns the body of the response. This lets the parsing be tried out without a network.
+    pub fn fetch_price_with<F>(&self, get: F) -> Result<f64, PriceError>
+    where
+        F: FnOnce(&str, &str) -> Result<String, PriceError>,
+    {
+        parse_price(&get(&self.url, &self.symbol)?)
+    }
+}
+
+/// Fetches the price of Bitcoin in US dollars from Binance, the feed the game follows by default.
+pub fn fetch_btc_price() -> Result<f64, PriceError> {
+    PriceFeed::new(String::from(DEFAULT_PRICE_URL), String::from(DEFAULT_SYMBOL)).fetch_price()
+}
+
+/// Requests the price of `symbol` from the ticker endpoint at `url`, returning the body of the response.
+fn http_get(url: &str, symbol: &str) -> Result<String, PriceError> {
+    ureq::get(url)
+        .query("symbol", symbol)
+        .timeout(REQUEST_TIMEOUT)
+        .call()
+        .map_err(|e| PriceError::Http(e.to_string()))?
+        .into_string()
+        .map_err(|e| PriceError::Http(e.to_string()))
+}
+
+/// Reads the `price` field out of a Binance ticker response such as `{"symbol":"BTCUSDT","price":"1.00"}`.
+/// Binance encodes the price as a string, but a bare number is accepted too.
+pub fn parse_price(body: &str) -> Result<f64, PriceError> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| PriceError::Parse(e.to_string()))?;
+
+    match &json["price"] {
+        serde_json::Value::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| PriceError::Parse(format!("invalid price {:?}", s))),
+        serde_json::Value::Number(n) => n
+            .as_f64()
+            .ok_or_else(|| PriceError::Parse(format!("invalid price {}", n))),
+        _ => Err(PriceError::Parse(String::from("missing price field"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> PriceFeed {
+        PriceFeed::new(String::from(DEFAULT_PRICE_URL), String::from(DEFAULT_SYMBOL))
+    }
+
+    /// Fetches a price from a feed whose endpoint answers with `body`.
+    fn fetch(body: &str) -> Result<f64, PriceError> {
+        feed().fetch_price_with(|_, _| Ok(String::from(body)))
+    }
+
+    #[test]
+    fn fetch_asks_for_the_feed_symbol() {
+        let price = feed().fetch_price_with(|url, symbol| {
+            assert_eq!((url, symbol), (DEFAULT_PRICE_URL, DEFAULT_SYMBOL));
+            Ok(String::from(r#"{"symbol":"BTCUSDT","price":"1.00"}"#))
+        });
+        assert_eq!(price.unwrap(), 1.0);
+    }
+
+    #[test]
+    fn price_given_as_a_string_is_parsed() {
+        assert_eq!(fetch(r#"{"symbol":"BTCUSDT","price":"64123.45000000"}"#).unwrap(), 64123.45);
+    }
+
+    #[test]
+    fn price_given_as_a_number_is_parsed() {
+        assert_eq!(fetch(r#"{"price":64123.5}"#).unwrap(), 64123.5);
+    }
+
+    #[test]
+    fn missing_price_is_an_error() {
+        assert!(matches!(fetch(r#"{"symbol":"BTCUSDT"}"#), Err(PriceError::Parse(_))));
+    }
+
+    #[test]
+    fn garbage_price_is_an_error() {
+        assert!(matches!(fetch(r#"{"price":"lots"}"#), Err(PriceError::Parse(_))));
+        assert!(matches!(fetch(r#"{"price":[1]}"#), Err(PriceError::Parse(_))));
+        assert!(matches!(fetch("<html>"), Err(PriceError::Parse(_))));
+    }
+
+    #[test]
+    fn failed_request_is_passed_on() {
+        let result = feed().fetch_price_with(|_, _| Err(PriceError::Http(String::from("timed out"))));
+        assert!(matches!(result, Err(PriceError::Http(_))));
+    }
+
+    #[test]
+    fn unchanged_price_keeps_the_speed() {
+        assert_eq!(feed().price_change_to_duration(0.0, 500), 500);
+    }
+
+    #[test]
+    fn price_moves_scale_the_speed_smoothly() {
+        assert_eq!(feed().price_change_to_duration(1.0, 500), 184);
+        assert_eq!(feed().price_change_to_duration(-0.5, 500), 824);
+    }
+
+    #[test]
+    fn big_price_moves_stop_at_the_range() {
+        let feed = feed();
+        assert_eq!(feed.price_change_to_duration(100.0, 500), DEFAULT_MIN_DURATION_MS);
+        assert_eq!(feed.price_change_to_duration(-100.0, 500), DEFAULT_MAX_DURATION_MS);
+        assert_eq!(feed.price_change_to_duration(100.0, DEFAULT_MIN_DURATION_MS), DEFAULT_MIN_DURATION_MS);
+        assert_eq!(feed.price_change_to_duration(-100.0, DEFAULT_MAX_DURATION_MS), DEFAULT_MAX_DURATION_MS);
+    }
+
+    #[test]
+    fn speed_outside_the_range_only_moves_back_towards_it() {
+        let feed = feed();
+        assert_eq!(feed.price_change_to_duration(5.0, 20), 20);
+        assert_eq!(feed.price_change_to_duration(1.0, 20), 20);
+        assert_eq!(feed.price_change_to_duration(-1.0, 20), 54);
+        assert_eq!(feed.price_change_to_duration(-5.0, 3000), 3000);
+        assert_eq!(feed.price_change_to_duration(1.0, 3000), 1104);
+    }
+
+    #[test]
+    fn duration_range_can_be_changed() {
+        let mut feed = feed();
+        feed.set_duration_range(200, 300);
+        assert_eq!(feed.price_change_to_duration(100.0, 250), 200);
+        assert_eq!(feed.price_change_to_duration(-100.0, 250), 300);
+    }
+}
@@ -0,0 +1,386 @@
+use crate::autoshift::AutoShift;
+use crate::bindings::Action;
+use crate::{
+    Board, BoardConfig, ClearGravity, DropHandling, Game, GameMode, GameOver, HoldPolicy, Randomizer, RotationSystem,
+};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Something that happened to a recorded game, replayed by applying it again at the same moment.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ReplayEvent {
+    /// The player performed an action.
+    Action(Action),
+    /// The player let go of the key for an action, on a terminal that reports it.
+    Release(Action),
+    /// A game tick went by.
+    Tick,
+    /// The game speed changed to the given row duration in milliseconds.
+    Speed(u64),
+    /// The game woke up to check whether the current piece's lock delay had run out.
+    LockCheck,
+    /// A row of garbage came up with a gap in the given column.
+    Garbage(u32),
+}
+
+impl ReplayEvent {
+    /// Applies the event to the game. Fails if it ended the game.
+    pub fn apply(self, game: &mut Game) -> Result<(), GameOver> {
+        match self {
+            ReplayEvent::Action(action) => game.perform(action),
+            ReplayEvent::Release(action) => {
+                game.release(action);
+                Ok(())
+            }
+            // Ticks arriving while paused are dropped rather than queued, so nothing piles up to fire at
+            // once on resume.
+            ReplayEvent::Tick if game.is_paused() => Ok(()),
+            ReplayEvent::Tick => game.tick(),
+            ReplayEvent::Speed(duration) => {
+                game.set_duration(duration);
+                Ok(())
+            }
+            ReplayEvent::LockCheck => game.lock_if_expired(),
+            ReplayEvent::Garbage(gap_column) => game.push_garbage(gap_column),
+        }
+    }
+
+    fn to_field(self) -> String {
+        match self {
+            ReplayEvent::Action(action) => String::from(action.config_name()),
+            ReplayEvent::Release(action) => format!("release {}", action.config_name()),
+            ReplayEvent::Tick => String::from("tick"),
+            ReplayEvent::Speed(duration) => format!("speed {}", duration),
+            ReplayEvent::LockCheck => String::from("lock"),
+            ReplayEvent::Garbage(gap_column) => format!("garbage {}", gap_column),
+        }
+    }
+
+    fn from_field(field: &str) -> Option<ReplayEvent> {
+        match field {
+            "tick" => Some(ReplayEvent::Tick),
+            "lock" => Some(ReplayEvent::LockCheck),
+            "noop" => Some(ReplayEvent::Action(Action::Noop)),
+            _ => {
+                if let Some(action) = field.strip_prefix("release ") {
+                    Action::from_config_name(action).map(ReplayEvent::Release)
+                } else if let Some(duration) = field.strip_prefix("speed ") {
+                    duration.parse().ok().map(ReplayEvent::Speed)
+                } else if let Some(gap_column) = field.strip_prefix("garbage ") {
+                    gap_column.parse().ok().map(ReplayEvent::Garbage)
+                } else {
+                    Action::from_config_name(field).map(ReplayEvent::Action)
+                }
+            }
+        }
+    }
+}
+
+/// A recording of a game: the settings it was played with and every event applied to it, each with the
+/// number of milliseconds since the game started. Replaying the events at those times on a game created
+/// with the same seed and settings plays out exactly the same game.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recorder {
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    pub das_ms: u64,
+    pub arr_ms: u64,
+    pub line_clear_delay_ms: u64,
+    pub entry_delay_ms: u64,
+    pub lock_reset_limit: u32,
+    pub start_level: u32,
+    pub max_level: u32,
+    pub das_charge: bool,
+    pub soft_drop_step: u32,
+    pub hard_drop_enabled: bool,
+    pub lock_out_enabled: bool,
+    pub mode: GameMode,
+    pub hold_policy: HoldPolicy,
+    pub randomizer: Randomizer,
+    pub rotation_system: RotationSystem,
+    pub clear_gravity: ClearGravity,
+    /// Whether the game was played in debug mode, which allows undoing actions.
+    pub debug: bool,
+    /// The layout the game started from, or `None` for an empty board.
+    pub board: Option<Board>,
+    pub events: Vec<(u64, ReplayEvent)>,
+}
+
+impl Recorder {
+    /// Starts an empty recording of the given game.
+    pub fn new(game: &Game) -> Recorder {
+        Recorder {
+            seed: game.seed(),
+            width: game.board().width(),
+            height: game.board().height(),
+            das_ms: game.auto_shift().das_ms(),
+            arr_ms: game.auto_shift().arr_ms(),
+            line_clear_delay_ms: game.line_clear_delay_ms(),
+            entry_delay_ms: game.entry_delay_ms(),
+            lock_reset_limit: game.lock_reset_limit(),
+            start_level: game.start_level(),
+            max_level: game.max_level(),
+            das_charge: game.das_charge(),
+            soft_drop_step: game.drop_handling().soft_drop_step,
+            hard_drop_enabled: game.drop_handling().hard_drop_enabled,
+            lock_out_enabled: game.lock_out_enabled(),
+            mode: game.mode(),
+            hold_policy: game.hold_policy(),
+            randomizer: game.randomizer(),
+            rotation_system: game.rotation_system(),
+            clear_gravity: game.clear_gravity(),
+            debug: game.debug(),
+            board: game.start_board().cloned(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Creates a game with the seed and settings the recording was made with, ready for its events to be
+    /// applied. How big the board is drawn doesn't change the game, so `scale` is left to the viewer, as is
+    /// anything else about how the game looks.
+    pub fn new_game(&self, scale: u32) -> Game {
+        let config = BoardConfig { width: self.width, height: self.height, scale, ..BoardConfig::default() };
+        let mut game = Game::new_seeded(config, self.seed);
+        if let Some(board) = &self.board {
+            game.set_board(board.clone());
+        }
+        game.set_auto_shift(AutoShift::new(self.das_ms, self.arr_ms));
+        game.set_line_clear_delay(self.line_clear_delay_ms);
+        game.set_entry_delay(self.entry_delay_ms);
+        game.set_lock_reset_limit(self.lock_reset_limit);
+        game.set_das_charge(self.das_charge);
+        game.set_drop_handling(DropHandling {
+            soft_drop_step: self.soft_drop_step,
+            hard_drop_enabled: self.hard_drop_enabled,
+        });
+        game.set_lock_out_enabled(self.lock_out_enabled);
+        game.set_clear_gravity(self.clear_gravity);
+        game.set_mode(self.mode);
+        game.set_hold_policy(self.hold_policy);
+        game.set_start_level(self.start_level);
+        game.set_max_level(self.max_level);
+        game.set_randomizer(self.randomizer);
+        game.set_rotation_system(self.rotation_system);
+        game.set_debug(self.debug);
+        game
+    }
+
+    pub fn record(&mut self, elapsed_ms: u64, event: ReplayEvent) {
+        self.events.push((elapsed_ms, event));
+    }
+
+    /// Writes the recording to a file, as a header of settings followed by one tab-separated line per event.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut contents = format!(
+            "seed {}\nwidth {}\nheight {}\ndas {}\narr {}\nclear_delay {}\nentry_delay {}\nlock_resets {}\n\
+             start_level {}\nmax_level {}\ndas_charge {}\nsoft_drop_step {}\nhard_drop {}\nlock_out {}\nmode {} {}\n\
+             hold {}\nrandomizer {}\nrotation {}\nclear_gravity {}\ndebug {}\n\
+             board {}\n",
+            self.seed,
+            self.width,
+            self.height,
+            self.das_ms,
+            self.arr_ms,
+            self.line_clear_delay_ms,
+            self.entry_delay_ms,
+            self.lock_reset_limit,
+            self.start_level,
+            self.max_level,
+            self.das_charge as u8,
+            self.soft_drop_step,
+            self.hard_drop_enabled as u8,
+            self.lock_out_enabled as u8,
+            self.mode.name(),
+            self.mode.goal().unwrap_or(0),
+            self.hold_policy,
+            self.randomizer.name(),
+            self.rotation_system.name(),
+            self.clear_gravity.name(),
+            self.debug as u8,
+            // The rows of a starting layout go on the one line, separated by slashes.
+            self.board.as_ref().map_or(String::from("-"), |board| board.to_string().trim_end().replace('\n', "/"))
+        );
+        for (elapsed_ms, event) in &self.events {
+            contents += &format!("{}\t{}\n", elapsed_ms, event.to_field());
+        }
+        fs::write(path, contents)
+    }
+
+    /// Reads a recording written by `save`.
+    pub fn load(path: &Path) -> Result<Recorder, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let mut lines = contents.lines().enumerate();
+
+        let invalid = |name: &str| format!("{}: missing or invalid '{}' line", path.display(), name);
+        let mut header = |name: &str| -> Result<String, String> {
+            lines
+                .next()
+                .and_then(|(_, line)| Some(String::from(line.strip_prefix(name)?.strip_prefix(' ')?)))
+                .ok_or_else(|| invalid(name))
+        };
+        let mut number = |name: &str| -> Result<u64, String> { header(name)?.parse().map_err(|_| invalid(name)) };
+        let seed = number("seed")?;
+        let width = number("width")? as u32;
+        let height = number("height")? as u32;
+        let das_ms = number("das")?;
+        let arr_ms = number("arr")?;
+        let line_clear_delay_ms = number("clear_delay")?;
+        let entry_delay_ms = number("entry_delay")?;
+        let lock_reset_limit = number("lock_resets")? as u32;
+        let start_level = number("start_level")? as u32;
+        let max_level = number("max_level")? as u32;
+        let das_charge = match number("das_charge")? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("das_charge")),
+        };
+        let soft_drop_step = match number("soft_drop_step")? {
+            0 => return Err(invalid("soft_drop_step")),
+            rows => rows as u32,
+        };
+        let hard_drop_enabled = match number("hard_drop")? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("hard_drop")),
+        };
+        let lock_out_enabled = match number("lock_out")? {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("lock_out")),
+        };
+        let mode = header("mode")?
+            .split_once(' ')
+            .and_then(|(name, goal)| GameMode::from_name(name, Some(goal.parse().ok()?)))
+            .ok_or_else(|| invalid("mode"))?;
+        let hold_policy = HoldPolicy::from_name(&header("hold")?).ok_or_else(|| invalid("hold"))?;
+        let randomizer = Randomizer::from_name(&header("randomizer")?).ok_or_else(|| invalid("randomizer"))?;
+        let rotation_system =
+            RotationSystem::from_name(&header("rotation")?).ok_or_else(|| invalid("rotation"))?;
+        let clear_gravity =
+            ClearGravity::from_name(&header("clear_gravity")?).ok_or_else(|| invalid("clear_gravity"))?;
+        let debug = match header("debug")?.as_str() {
+            "0" => false,
+            "1" => true,
+            _ => return Err(invalid("debug")),
+        };
+        let board = match header("board")?.as_str() {
+            "-" => None,
+            rows => Some(
+                rows.replace('/', "\n")
+                    .parse::<Board>()
+                    .ok()
+                    .filter(|board| (board.width(), board.height()) == (width, height))
+                    .ok_or_else(|| invalid("board"))?,
+            ),
+        };
+
+        let events = lines
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                line.split_once('\t')
+                    .and_then(|(elapsed_ms, event)| Some((elapsed_ms.parse().ok()?, ReplayEvent::from_field(event)?)))
+                    .ok_or_else(|| format!("{}:{}: invalid event '{}'", path.display(), i + 1, line))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Recorder {
+            seed,
+            width,
+            height,
+            das_ms,
+            arr_ms,
+            line_clear_delay_ms,
+            entry_delay_ms,
+            lock_reset_limit,
+            start_level,
+            max_level,
+            das_charge,
+            soft_drop_step,
+            hard_drop_enabled,
+            lock_out_enabled,
+            mode,
+            hold_policy,
+            randomizer,
+            rotation_system,
+            clear_gravity,
+            debug,
+            board,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Applies an event to the game at `elapsed_ms` into it, noting it in the recording, as the game loop
+    /// does.
+    fn play(game: &mut Game, recorder: &mut Recorder, started: Instant, elapsed_ms: u64, event: ReplayEvent) {
+        game.set_clock(started + Duration::from_millis(elapsed_ms));
+        recorder.record(elapsed_ms, event);
+        let _ = event.apply(game);
+    }
+
+    #[test]
+    fn a_saved_recording_replays_to_the_same_score_and_board() {
+        let mut game = Game::new_seeded(BoardConfig::default(), 7);
+        game.set_mode(GameMode::Marathon { goal: 40 });
+        game.set_entry_delay(100);
+        game.set_debug(true);
+        let mut recorder = Recorder::new(&game);
+        let started = Instant::now();
+        let mut elapsed_ms = 0;
+        for i in 0..40 {
+            let action = [Action::MoveLeft, Action::RotateCW, Action::SoftDrop, Action::HardDrop][i % 4];
+            let events = [ReplayEvent::Action(action), ReplayEvent::Tick, ReplayEvent::Tick, ReplayEvent::LockCheck];
+            for event in events {
+                elapsed_ms += 40;
+                play(&mut game, &mut recorder, started, elapsed_ms, event);
+            }
+            if i % 10 == 9 {
+                play(&mut game, &mut recorder, started, elapsed_ms, ReplayEvent::Garbage(i as u32 % 10));
+            }
+            if i % 7 == 6 {
+                play(&mut game, &mut recorder, started, elapsed_ms, ReplayEvent::Action(Action::Undo));
+            }
+        }
+        assert!(game.score() > 0);
+
+        let path = std::env::temp_dir().join(format!("tetrust-replay-test-{}", std::process::id()));
+        recorder.save(&path).unwrap();
+        let loaded = Recorder::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, recorder);
+
+        let mut replayed = loaded.new_game(1);
+        let started = Instant::now();
+        for &(elapsed_ms, event) in &loaded.events {
+            replayed.set_clock(started + Duration::from_millis(elapsed_ms));
+            let _ = event.apply(&mut replayed);
+        }
+        assert_eq!(replayed.score(), game.score());
+        assert_eq!(replayed.board_snapshot(), game.board_snapshot());
+    }
+
+
+    #[test]
+    fn every_kind_of_event_reads_back_as_written() {
+        let events = [
+            ReplayEvent::Action(Action::RotateCW),
+            ReplayEvent::Action(Action::Noop),
+            ReplayEvent::Release(Action::MoveLeft),
+            ReplayEvent::Tick,
+            ReplayEvent::Speed(250),
+            ReplayEvent::LockCheck,
+            ReplayEvent::Garbage(3),
+        ];
+        for event in events {
+            assert_eq!(ReplayEvent::from_field(&event.to_field()), Some(event));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::piece::Piece;
+use crate::util::Direction;
+
+/// The rules for turning pieces: where each rotation may kick the piece to when it doesn't fit in place,
+/// and which way pieces face as they come into play.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum RotationSystem {
+    /// The Super Rotation System of the guideline games, with five kicks to try for each turn.
+    #[default]
+    Srs,
+    /// The Arika Rotation System of the TGM games: pieces spawn point down, turned half round from the
+    /// guideline, and stay sat on the bottom of their grid as they turn, kicking a cell right or left at most.
+    Ars,
+    /// No kicks at all, as in the NES game: a turn that doesn't fit in place doesn't happen.
+    Classic,
+}
+
+impl RotationSystem {
+    pub const ALL: [RotationSystem; 3] = [RotationSystem::Srs, RotationSystem::Ars, RotationSystem::Classic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationSystem::Srs => "srs",
+            RotationSystem::Ars => "ars",
+            RotationSystem::Classic => "classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RotationSystem> {
+        RotationSystem::ALL.iter().copied().find(|system| system.name() == name)
+    }
+
+    /// Returns the kicks and spawn orientations of this system.
+    pub(crate) fn kicks(self) -> &'static dyn Kicks {
+        match self {
+            RotationSystem::Srs => &srs::Srs,
+            RotationSystem::Ars => &ars::Ars,
+            RotationSystem::Classic => &classic::Classic,
+        }
+    }
+}
+
+/// What a rotation system decides about a turn. A piece always turns about the centre of its grid; the
+/// offsets move the grid after the turn, so a system can keep pieces sat differently in their grids as well
+/// as kick them off walls and the stack.
+pub(crate) trait Kicks {
+    /// Returns the offsets to try, in order, when turning the piece out of its current rotation state in
+    /// the given direction. The piece goes to the first one it fits at.
+    fn kick_offsets(&self, piece: &Piece, direction: Direction) -> Vec<(i32, i32)>;
+
+    /// Turns a piece coming into play to face the way this system spawns it. Pieces spawn as dealt by
+    /// default.
+    fn orient_for_spawn(&self, _piece: &mut Piece) {}
+}
+
+/// The guideline kicks.
+mod srs {
+    use super::Kicks;
+    use crate::piece::{Piece, PieceType};
+    use crate::util::Direction;
+
+    /// Wall kick offsets tried in order when rotating a J, L, S, T or Z piece clockwise, indexed by the
+    /// rotation state being rotated out of. The first offset is the unkicked rotation. Offsets follow the SRS
+    /// tables but in board coordinates, where positive y points down.
+    const JLSTZ_KICKS_RIGHT: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    ];
+
+    /// Wall kick offsets tried when rotating a J, L, S, T or Z piece counter-clockwise.
+    const JLSTZ_KICKS_LEFT: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    ];
+
+    /// Wall kick offsets tried when rotating an I piece clockwise. The I piece turns about the centre of its
+    /// 4x4 grid rather than about a cell, so its kicks reach two columns sideways to bring it back off a wall.
+    const I_KICKS_RIGHT: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    ];
+
+    /// Wall kick offsets tried when rotating an I piece counter-clockwise.
+    const I_KICKS_LEFT: [[(i32, i32); 5]; 4] = [
+        [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+        [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    ];
+
+    pub(super) struct Srs;
+
+    impl Kicks for Srs {
+        /// The O piece never kicks.
+        fn kick_offsets(&self, piece: &Piece, direction: Direction) -> Vec<(i32, i32)> {
+            let table = match (piece.kind, direction) {
+                (PieceType::O, _) => return vec![(0, 0)],
+                (PieceType::I, Direction::Left) => &I_KICKS_LEFT,
+                (PieceType::I, Direction::Right) => &I_KICKS_RIGHT,
+                (_, Direction::Left) => &JLSTZ_KICKS_LEFT,
+                (_, Direction::Right) => &JLSTZ_KICKS_RIGHT,
+            };
+            table[piece.rotation].to_vec()
+        }
+    }
+}
+
+/// The TGM kicks and spawn orientations.
+mod ars {
+    use super::Kicks;
+    use crate::piece::{Piece, PieceType};
+    use crate::util::Direction;
+
+    pub(super) struct Ars;
+
+    /// Where a piece in the given rotation state sits in its grid under ARS, as an offset from where turning
+    /// the spawn shape puts it. The flat states of the three-wide pieces sit on the bottom row of their grid,
+    /// the upright S and Z use the same two columns whichever way they were turned, and the I lies in the
+    /// second row or stands in the third column.
+    fn alignment(kind: PieceType, rotation: usize) -> (i32, i32) {
+        match (kind, rotation) {
+            (PieceType::T | PieceType::J | PieceType::L | PieceType::S | PieceType::Z, 0) => (0, 1),
+            (PieceType::S, 1) => (-1, 0),
+            (PieceType::Z, 3) => (1, 0),
+            (PieceType::I, 2) => (0, -1),
+            (PieceType::I, 3) => (1, 0),
+            _ => (0, 0),
+        }
+    }
+
+    impl Kicks for Ars {
+        /// A turn is tried in place, then a cell to the right, then a cell to the left. The I and O pieces
+        /// never kick.
+        fn kick_offsets(&self, piece: &Piece, direction: Direction) -> Vec<(i32, i32)> {
+            let turned = match direction {
+                Direction::Left => (piece.rotation + 3) % 4,
+                Direction::Right => (piece.rotation + 1) % 4,
+            };
+            let (from, to) = (alignment(piece.kind, piece.rotation), alignment(piece.kind, turned));
+            let (x, y) = (to.0 - from.0, to.1 - from.1);
+            match piece.kind {
+                PieceType::I | PieceType::O => vec![(x, y)],
+                _ => vec![(x, y), (x + 1, y), (x - 1, y)],
+            }
+        }
+
+        /// The three-wide pieces spawn point down, turned half round from their guideline orientation.
+        /// That sits them on the bottom row of their grid, so they come in a row lower than they would
+        /// otherwise, with room above to turn upright.
+        fn orient_for_spawn(&self, piece: &mut Piece) {
+            let spawn_rotation = match piece.kind {
+                PieceType::I | PieceType::O => 0,
+                _ => 2,
+            };
+            while piece.rotation != spawn_rotation {
+                piece.rotate(Direction::Right);
+            }
+        }
+    }
+}
+
+/// No kicks.
+mod classic {
+    use super::Kicks;
+    use crate::piece::Piece;
+    use crate::util::Direction;
+
+    pub(super) struct Classic;
+
+    impl Kicks for Classic {
+        fn kick_offsets(&self, _piece: &Piece, _direction: Direction) -> Vec<(i32, i32)> {
+            vec![(0, 0)]
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/// Receives the game's sound effects. Every effect does nothing unless a sink implements it, so sinks only
+/// need to provide the sounds they play. Sinks are `Send` so a game can be moved onto another thread.
+pub trait SoundSink: Send {
+    /// A piece locked into the board.
+    fn play_lock(&mut self) {}
+    /// Lines were cleared.
+    fn play_clear(&mut self, _lines: u32) {}
+    /// The falling piece rotated.
+    fn play_rotate(&mut self) {}
+    /// The game was lost.
+    fn play_gameover(&mut self) {}
+}
+
+/// Plays no sound at all.
+pub struct NoSound;
+
+impl SoundSink for NoSound {}
+
+#[cfg(feature = "sound")]
+pub use self::rodio_sink::RodioSound;
+
+#[cfg(feature = "sound")]
+mod rodio_sink {
+    use super::SoundSink;
+    use rodio::source::{SineWave, Source};
+    use rodio::{OutputStream, Sink};
+    use std::sync::mpsc::{self, Sender};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Plays each effect as a short tone through the default audio output. The output can't leave the
+    /// thread that opened it, so it lives on a thread of its own which is sent the tones to play.
+    pub struct RodioSound {
+        tx: Sender<(f32, u64)>,
+    }
+
+    impl RodioSound {
+        /// Opens the default audio output, failing if there is none.
+        pub fn new() -> Result<RodioSound, String> {
+            let (tx, rx) = mpsc::channel::<(f32, u64)>();
+            let (tx_ready, rx_ready) = mpsc::channel();
+
+            thread::spawn(move || {
+                let opened = OutputStream::try_default()
+                    .map_err(|e| e.to_string())
+                    .and_then(|(stream, handle)| Ok((stream, Sink::try_new(&handle).map_err(|e| e.to_string())?)));
+                let (_stream, sink) = match opened {
+                    Ok(opened) => {
+                        let _ = tx_ready.send(Ok(()));
+                        opened
+                    }
+                    Err(err) => {
+                        let _ = tx_ready.send(Err(err));
+                        return;
+                    }
+                };
+
+                for (frequency, millis) in rx {
+                    let tone = SineWave::new(frequency)
+                        .take_duration(Duration::from_millis(millis))
+                        .amplify(0.2);
+                    sink.append(tone);
+                }
+            });
+
+            rx_ready.recv().map_err(|e| e.to_string())??;
+            Ok(RodioSound { tx })
+        }
+
+        fn tone(&self, frequency: f32, millis: u64) {
+            let _ = self.tx.send((frequency, millis));
+        }
+    }
+
+    impl SoundSink for RodioSound {
+        fn play_lock(&mut self) {
+            self.tone(220.0, 30);
+        }
+
+        fn play_clear(&mut self, lines: u32) {
+            // Bigger clears ring higher.
+            self.tone(440.0 + 110.0 * lines as f32, 120);
+        }
+
+        fn play_rotate(&mut self) {
+            self.tone(660.0, 15);
+        }
+
+        fn play_gameover(&mut self) {
+            self.tone(110.0, 600);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::piece::PieceType;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How many of the latest key presses the average input latency is taken over.
+const LATENCY_WINDOW: usize = 60;
+
+/// Counts of the pieces placed so far. How long they took to place is kept by the game's `GameClock`.
+#[derive(Clone)]
+pub struct Stats {
+    counts: [u32; 7],
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats { counts: [0; 7] }
+    }
+
+    /// Counts a piece locked into the board.
+    pub fn record_lock(&mut self, piece_type: PieceType) {
+        self.counts[piece_type as usize] += 1;
+    }
+
+    /// Returns how many of each tetromino have been placed.
+    pub fn counts(&self) -> impl Iterator<Item = (PieceType, u32)> + '_ {
+        PieceType::ALL.iter().copied().zip(self.counts.iter().copied())
+    }
+
+    pub fn total_pieces(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the pieces placed per second over the given time played.
+    pub fn pieces_per_second(&self, elapsed: Duration) -> f64 {
+        let seconds = elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.total_pieces() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+/// How long key presses took to go from being read to being applied to the game: the average over the
+/// latest `LATENCY_WINDOW` and the longest of all.
+pub struct InputLatency {
+    recent: VecDeque<Duration>,
+    max: Duration,
+}
+
+impl InputLatency {
+    pub fn new() -> InputLatency {
+        InputLatency { recent: VecDeque::with_capacity(LATENCY_WINDOW), max: Duration::ZERO }
+    }
+
+    /// Counts a key press that took `latency` to be applied.
+    pub fn record(&mut self, latency: Duration) {
+        if self.recent.len() == LATENCY_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency);
+        self.max = self.max.max(latency);
+    }
+
+    /// Returns the average latency of the latest key presses, or `None` before the first.
+    pub fn average(&self) -> Option<Duration> {
+        let total: Duration = self.recent.iter().sum();
+        (!self.recent.is_empty()).then(|| total / self.recent.len() as u32)
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
+
+impl Default for InputLatency {
+    fn default() -> InputLatency {
+        InputLatency::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_lock_counts_each_piece_type() {
+        let mut stats = Stats::new();
+        for kind in [PieceType::T, PieceType::I, PieceType::T, PieceType::L] {
+            stats.record_lock(kind);
+        }
+        let counts: Vec<_> = stats.counts().filter(|&(_, count)| count > 0).collect();
+        assert_eq!(counts, vec![(PieceType::I, 1), (PieceType::T, 2), (PieceType::L, 1)]);
+        assert_eq!(stats.total_pieces(), 4);
+    }
+
+    #[test]
+    fn pieces_per_second_is_pieces_over_time_played() {
+        let mut stats = Stats::new();
+        assert_eq!(stats.pieces_per_second(Duration::ZERO), 0.0);
+        for _ in 0..5 {
+            stats.record_lock(PieceType::O);
+        }
+        assert_eq!(stats.pieces_per_second(Duration::from_secs(2)), 2.5);
+    }
+}
@@ -1,91 +1,325 @@
-use libc::{c_ulong, c_int, c_uchar};
+#[cfg(unix)]
+use libc::{c_int, c_ulong, c_ushort};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(not(any(windows, feature = "crossterm-input")))]
+pub use self::raw::{set_terminal_raw_mode, KeyReader};
+
+// Windows always uses crossterm, as the raw backend is built on termios.
+#[cfg(any(windows, feature = "crossterm-input"))]
+pub use self::crossterm_backend::{set_terminal_raw_mode, KeyReader};
 
 // While this code is pretty generic, I've pulled much of this code from another Rust Tetris implementation:
 // https://github.com/jankes/tetris1/blob/master/tetris1.rs
 
-// Linux specifc termios structure definition
-//
-// Since we don't actually access any of the fields individually, and instead just
-// pass around termios as a "black box", this will probably work for other platforms
-// as long their struct termios is smaller than Linux's. For example, Mac OS omits the
-// c_line field and only has 20 control characters.
+// Window size as reported by the TIOCGWINSZ ioctl
+#[cfg(unix)]
 #[allow(non_camel_case_types)]
-#[derive(Copy, Clone)]
 #[repr(C)]
-struct termios {
-    c_iflag:  c_ulong,        // input flags
-    c_oflag:  c_ulong,        // output flags
-    c_cflag:  c_ulong,        // control flags
-    c_lflag:  c_ulong,        // local flags
-    c_cc:    [c_uchar; 20],   // control chars
-    c_ispeed: c_ulong,        // input speed
-    c_ospeed: c_ulong,        // output speed
+struct winsize {
+    ws_row:    c_ushort,
+    ws_col:    c_ushort,
+    ws_xpixel: c_ushort,
+    ws_ypixel: c_ushort,
+}
+
+#[cfg(target_os = "linux")]
+const TIOCGWINSZ: c_ulong = 0x5413;
+#[cfg(all(unix, not(target_os = "linux")))]
+const TIOCGWINSZ: c_ulong = 0x40087468;
+
+// SIGWINCH has the same number on Linux, Mac OS and the BSDs
+#[cfg(unix)]
+const SIGWINCH: c_int = 28;
+
+#[cfg(unix)]
+extern "C" {
+    fn ioctl(filedes: c_int, request: c_ulong, ...) -> c_int;
+    fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
 }
 
-extern {
-    fn tcgetattr(filedes: c_int, termptr: *mut termios) -> c_int;
-    fn tcsetattr(filedes: c_int, opt: c_int, termptr: *const termios) -> c_int;
-    fn cfmakeraw(termptr: *mut termios);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_resize(_signum: c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
 }
 
-fn get_terminal_attr() -> (termios, c_int) {
+/// Returns the size of the terminal as (columns, rows), or `None` if standard output isn't a terminal.
+#[cfg(unix)]
+pub fn terminal_size() -> Option<(u32, u32)> {
     unsafe {
-        let ios = &mut termios {
-            c_iflag:  0,
-            c_oflag:  0,
-            c_cflag:  0,
-            c_lflag:  0,
-            c_cc:     [0; 20],
-            c_ispeed: 0,
-            c_ospeed: 0
-        };
+        let size = &mut winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
 
-        // first parameter is file descriptor number, 0 ==> standard input
-        let err = tcgetattr(0, ios as *mut termios);
+        // first parameter is file descriptor number, 1 ==> standard output
+        if ioctl(1, TIOCGWINSZ, size as *mut winsize) != 0 || size.ws_col == 0 {
+            return None;
+        }
 
-        return (*ios, err);
+        Some((size.ws_col as u32, size.ws_row as u32))
     }
 }
 
-fn make_raw(ios: &termios) -> termios {
-    unsafe {
-        let mut ios = *ios;
-        cfmakeraw(&mut ios);
-        return ios;
-    }
+/// Returns the size of the console as (columns, rows), or `None` if standard output isn't a console.
+#[cfg(windows)]
+pub fn terminal_size() -> Option<(u32, u32)> {
+    let (columns, rows) = crossterm::terminal::size().ok()?;
+    Some((columns as u32, rows as u32))
 }
 
-fn set_terminal_attr(ios: &termios) -> c_int {
+/// Starts listening for SIGWINCH, sent whenever the terminal is resized.
+#[cfg(unix)]
+pub fn watch_resize() {
     unsafe {
-        // first paramter is file descriptor number, 0 ==> standard input
-        // second paramter is when to set, 0 ==> now
-        return tcsetattr(0, 0, ios as *const termios);
+        signal(SIGWINCH, on_resize);
     }
 }
 
-pub struct TerminalRestorer {
-    ios: termios
+/// Does nothing: the console reports resizes among the key events, which `KeyReader` watches for.
+#[cfg(windows)]
+pub fn watch_resize() {}
+
+/// Returns true if the terminal has been resized since the last call.
+pub fn take_resize() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
 }
 
-impl Drop for TerminalRestorer {
-    fn drop(&mut self) {
-        set_terminal_attr(&self.ios);
+/// Raw mode and key reading done directly on the terminal, decoding the bytes it sends for each key.
+#[cfg(not(any(windows, feature = "crossterm-input")))]
+mod raw {
+    use libc::{c_ulong, c_int, c_short, c_uchar, c_void, size_t, ssize_t};
+    use std::io;
+    use std::time::Duration;
+    use tetrust::input::{KeyDecoder, KeyInput};
+    use tetrust::TetrustError;
+
+    // Linux specifc termios structure definition
+    //
+    // Since we don't actually access any of the fields individually, and instead just
+    // pass around termios as a "black box", this will probably work for other platforms
+    // as long their struct termios is smaller than Linux's. For example, Mac OS omits the
+    // c_line field and only has 20 control characters.
+    #[allow(non_camel_case_types)]
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    struct termios {
+        c_iflag:  c_ulong,        // input flags
+        c_oflag:  c_ulong,        // output flags
+        c_cflag:  c_ulong,        // control flags
+        c_lflag:  c_ulong,        // local flags
+        c_cc:    [c_uchar; 20],   // control chars
+        c_ispeed: c_ulong,        // input speed
+        c_ospeed: c_ulong,        // output speed
+    }
+
+    // File descriptor to wait on with poll()
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    struct pollfd {
+        fd:      c_int,
+        events:  c_short,
+        revents: c_short,
+    }
+
+    // poll() event for data waiting to be read, the same on Linux, Mac OS and the BSDs
+    const POLLIN: c_short = 0x1;
+
+    extern "C" {
+        fn tcgetattr(filedes: c_int, termptr: *mut termios) -> c_int;
+        fn tcsetattr(filedes: c_int, opt: c_int, termptr: *const termios) -> c_int;
+        fn cfmakeraw(termptr: *mut termios);
+        fn poll(fds: *mut pollfd, nfds: c_ulong, timeout: c_int) -> c_int;
+        fn read(filedes: c_int, buf: *mut c_void, nbyte: size_t) -> ssize_t;
+    }
+
+    fn get_terminal_attr() -> (termios, c_int) {
+        unsafe {
+            let ios = &mut termios {
+                c_iflag:  0,
+                c_oflag:  0,
+                c_cflag:  0,
+                c_lflag:  0,
+                c_cc:     [0; 20],
+                c_ispeed: 0,
+                c_ospeed: 0
+            };
+
+            // first parameter is file descriptor number, 0 ==> standard input
+            let err = tcgetattr(0, ios as *mut termios);
+
+            (*ios, err)
+        }
+    }
+
+    fn make_raw(ios: &termios) -> termios {
+        unsafe {
+            let mut ios = *ios;
+            cfmakeraw(&mut ios);
+            ios
+        }
+    }
+
+    fn set_terminal_attr(ios: &termios) -> c_int {
+        unsafe {
+            // first paramter is file descriptor number, 0 ==> standard input
+            // second paramter is when to set, 0 ==> now
+            tcsetattr(0, 0, ios as *const termios)
+        }
+    }
+
+    pub struct TerminalRestorer {
+        ios: termios
+    }
+
+    impl Drop for TerminalRestorer {
+        fn drop(&mut self) {
+            set_terminal_attr(&self.ios);
+        }
+    }
+
+    /// Switches the terminal to raw mode until the returned restorer is dropped.
+    pub fn set_terminal_raw_mode() -> Result<TerminalRestorer, TetrustError> {
+        let (original_ios, err) = get_terminal_attr();
+        if err != 0 {
+            return Err(TetrustError::Terminal(format!(
+                "failed to get terminal settings: {}",
+                io::Error::last_os_error()
+            )));
+        }
+
+        let raw_ios = make_raw(&original_ios);
+        let err = set_terminal_attr(&raw_ios);
+        if err != 0 {
+            return Err(TetrustError::Terminal(format!(
+                "failed to switch terminal to raw mode: {}",
+                io::Error::last_os_error()
+            )));
+        }
+
+        Ok(TerminalRestorer {
+            ios: original_ios
+        })
+    }
+
+    /// Waits up to `timeout_ms` milliseconds for input on standard input. Returns true if there is some to read.
+    pub fn input_ready(timeout_ms: i32) -> bool {
+        unsafe {
+            // first field is file descriptor number, 0 ==> standard input
+            let fds = &mut pollfd { fd: 0, events: POLLIN, revents: 0 };
+            poll(fds as *mut pollfd, 1, timeout_ms) > 0
+        }
+    }
+
+    /// Reads whatever input is waiting on standard input into `buffer`, blocking until there is some, and
+    /// returns how many bytes were read. This bypasses the standard library's buffering of stdin, which would
+    /// otherwise hide waiting input from `input_ready`.
+    pub fn read_input(buffer: &mut [u8]) -> io::Result<usize> {
+        unsafe {
+            // first parameter is file descriptor number, 0 ==> standard input
+            let read = read(0, buffer.as_mut_ptr() as *mut c_void, buffer.len() as size_t);
+            if read < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(read as usize)
+            }
+        }
+    }
+
+    /// Reads the keys pressed from standard input.
+    pub struct KeyReader {
+        decoder: KeyDecoder,
+        buffer: [u8; 64],
+    }
+
+    impl KeyReader {
+        pub fn new() -> KeyReader {
+            KeyReader { decoder: KeyDecoder::new(), buffer: [0; 64] }
+        }
+
+        /// Waits up to `timeout` for keys to be pressed, returning those that were, or `None` once standard
+        /// input has closed. A raw terminal never says when a key goes up, so these are all presses.
+        pub fn read_keys(&mut self, timeout: Duration) -> io::Result<Option<Vec<KeyInput>>> {
+            if !input_ready(timeout.as_millis() as i32) {
+                return Ok(Some(Vec::new()));
+            }
+            match read_input(&mut self.buffer)? {
+                0 => Ok(None),
+                read => Ok(Some(self.decoder.feed(&self.buffer[..read]).into_iter().map(KeyInput::Press).collect())),
+            }
+        }
     }
 }
 
-pub fn set_terminal_raw_mode() -> TerminalRestorer {
-    let (original_ios, err) = get_terminal_attr();
-    if err != 0 {
-        panic!("failed to get terminal settings");
+/// Raw mode and key reading done through crossterm's events.
+#[cfg(any(windows, feature = "crossterm-input"))]
+mod crossterm_backend {
+    use super::RESIZED;
+    use crossterm::event::{
+        self, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    };
+    use crossterm::{execute, terminal};
+    use std::io;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use tetrust::input::{key_from_event, KeyInput};
+    use tetrust::TetrustError;
+
+    pub struct TerminalRestorer {
+        /// Whether the terminal was asked to report key releases, which has to be undone as well.
+        enhanced: bool,
+    }
+
+    impl Drop for TerminalRestorer {
+        fn drop(&mut self) {
+            if self.enhanced {
+                let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+            }
+            let _ = terminal::disable_raw_mode();
+        }
     }
 
-    let raw_ios = make_raw(&original_ios);
-    let err = set_terminal_attr(&raw_ios);
-    if err != 0 {
-        panic!("failed to switch terminal to raw mode");
+    /// Switches the terminal to raw mode until the returned restorer is dropped, which puts back the mode it
+    /// was in. A terminal that supports the keyboard enhancements is asked to report keys going up as well as
+    /// down, so a held key can be told from one pressed again; the Windows console reports them anyway. On
+    /// Windows this also turns on the console's handling of the escape codes the display draws with, failing
+    /// if it has none.
+    pub fn set_terminal_raw_mode() -> Result<TerminalRestorer, TetrustError> {
+        #[cfg(windows)]
+        if !crossterm::ansi_support::supports_ansi() {
+            return Err(TetrustError::Terminal(String::from("the console doesn't support ANSI escape codes")));
+        }
+        terminal::enable_raw_mode()
+            .map_err(|err| TetrustError::Terminal(format!("failed to switch terminal to raw mode: {}", err)))?;
+        // Event types are only reported for keys sent as escape codes, which disambiguating them turns on.
+        let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        let enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false)
+            && execute!(io::stdout(), PushKeyboardEnhancementFlags(flags)).is_ok();
+        Ok(TerminalRestorer { enhanced })
     }
 
-    TerminalRestorer {
-        ios: original_ios
+    /// Reads the keys pressed from crossterm's events.
+    pub struct KeyReader;
+
+    impl KeyReader {
+        pub fn new() -> KeyReader {
+            KeyReader
+        }
+
+        /// Waits up to `timeout` for keys to be pressed or released, returning those that were. Input never
+        /// closes as far as crossterm is concerned, so this doesn't return `None`.
+        pub fn read_keys(&mut self, timeout: Duration) -> io::Result<Option<Vec<KeyInput>>> {
+            let mut keys = Vec::new();
+            let mut timeout = timeout;
+            // Take every event already waiting, not just the first.
+            while event::poll(timeout)? {
+                match event::read()? {
+                    Event::Key(key_event) => keys.extend(key_from_event(&key_event)),
+                    Event::Resize(..) => RESIZED.store(true, Ordering::SeqCst),
+                    _ => (),
+                }
+                timeout = Duration::ZERO;
+            }
+            Ok(Some(keys))
+        }
     }
 }
@@ -0,0 +1,221 @@
+use crate::util::{parse_color, Color};
+
+/// The colors used to draw the game: one for each tetromino, and the rest for the board and the text
+/// around it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Theme {
+    pub name: &'static str,
+    pub i: Color,
+    pub o: Color,
+    pub t: Color,
+    pub s: Color,
+    pub z: Color,
+    pub j: Color,
+    pub l: Color,
+    pub border: Color,
+    pub text: Color,
+    pub background: Color,
+    /// The faint color of the grid that can be drawn in the board's empty cells.
+    pub grid: Color,
+}
+
+impl Theme {
+    /// The standard tetromino colors.
+    pub const GUIDELINE: Theme = Theme {
+        name: "guideline",
+        i: Color::Cyan,
+        o: Color::Yellow,
+        t: Color::Purple,
+        s: Color::Green,
+        z: Color::Red,
+        j: Color::Blue,
+        l: Color::Orange,
+        border: Color::Red,
+        text: Color::Red,
+        background: Color::Black,
+        grid: Color::DarkGray,
+    };
+
+    /// Shades of grey, for terminals with few colors.
+    pub const MONOCHROME: Theme = Theme {
+        name: "monochrome",
+        i: Color::White,
+        o: Color::LightGray,
+        t: Color::Gray,
+        s: Color::White,
+        z: Color::LightGray,
+        j: Color::Gray,
+        l: Color::White,
+        border: Color::Gray,
+        text: Color::White,
+        background: Color::Black,
+        grid: Color::DarkGray,
+    };
+
+    /// Saturated colors on black with white text.
+    pub const HIGH_CONTRAST: Theme = Theme {
+        name: "high-contrast",
+        i: Color::Cyan,
+        o: Color::Yellow,
+        t: Color::Magenta,
+        s: Color::Green,
+        z: Color::Red,
+        j: Color::Blue,
+        l: Color::Orange,
+        border: Color::White,
+        text: Color::White,
+        background: Color::Black,
+        grid: Color::Gray,
+    };
+
+    /// The Okabe-Ito palette, whose colors stay distinct with red-green color blindness.
+    pub const DEUTERANOPIA: Theme = Theme {
+        name: "deuteranopia",
+        i: Color::SkyBlue,
+        o: Color::Yellow,
+        t: Color::Pink,
+        s: Color::Teal,
+        z: Color::Vermilion,
+        j: Color::Blue,
+        l: Color::Orange,
+        border: Color::Gray,
+        text: Color::White,
+        background: Color::Black,
+        grid: Color::DarkGray,
+    };
+
+    /// The built-in themes, in the order the theme key cycles through them.
+    pub const ALL: [Theme; 4] = [Theme::GUIDELINE, Theme::MONOCHROME, Theme::HIGH_CONTRAST, Theme::DEUTERANOPIA];
+
+    /// Looks up a built-in theme by name.
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::ALL.iter().copied().find(|theme| theme.name == name)
+    }
+
+    /// Returns the built-in theme after this one, wrapping around to the first.
+    pub fn next(&self) -> Theme {
+        let i = Theme::ALL.iter().position(|theme| theme.name == self.name).unwrap_or(0);
+        Theme::ALL[(i + 1) % Theme::ALL.len()]
+    }
+
+    /// Returns the color this theme draws a piece in, given the piece's guideline color.
+    pub fn piece_color(&self, color: Color) -> Color {
+        match color {
+            Color::Cyan => self.i,
+            Color::Yellow => self.o,
+            Color::Purple => self.t,
+            Color::Green => self.s,
+            Color::Red => self.z,
+            Color::Blue => self.j,
+            Color::Orange => self.l,
+            other => other,
+        }
+    }
+}
+
+/// Colors chosen for single pieces, drawn in place of the theme's own whichever theme is in use.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct PieceColors {
+    pub i: Option<Color>,
+    pub o: Option<Color>,
+    pub t: Option<Color>,
+    pub s: Option<Color>,
+    pub z: Option<Color>,
+    pub j: Option<Color>,
+    pub l: Option<Color>,
+}
+
+impl PieceColors {
+    /// Reads the `[piece]` section of the config file, which maps a piece's letter to the name of the color
+    /// to draw it in, e.g. `i = "blue"`. Pieces missing from it are drawn in the theme's colors.
+    pub fn from_config(config: &toml::Table) -> Result<PieceColors, String> {
+        let pieces = match config.get("piece") {
+            Some(toml::Value::Table(pieces)) => pieces,
+            Some(_) => return Err(String::from("'piece' must be a table")),
+            None => return Ok(PieceColors::default()),
+        };
+
+        let mut colors = PieceColors::default();
+        for (piece, value) in pieces {
+            let name = value
+                .as_str()
+                .ok_or_else(|| format!("the color for '{}' in [piece] must be a string", piece))?;
+            let color =
+                parse_color(name).ok_or_else(|| format!("unknown color '{}' for '{}' in [piece]", name, piece))?;
+            let slot = match piece.as_str() {
+                "i" => &mut colors.i,
+                "o" => &mut colors.o,
+                "t" => &mut colors.t,
+                "s" => &mut colors.s,
+                "z" => &mut colors.z,
+                "j" => &mut colors.j,
+                "l" => &mut colors.l,
+                _ => return Err(format!("unknown piece '{}' in [piece]", piece)),
+            };
+            *slot = Some(color);
+        }
+        Ok(colors)
+    }
+
+    /// Returns the theme with these colors in place of its own for the pieces they're set for.
+    pub fn apply(&self, theme: Theme) -> Theme {
+        Theme {
+            i: self.i.unwrap_or(theme.i),
+            o: self.o.unwrap_or(theme.o),
+            t: self.t.unwrap_or(theme.t),
+            s: self.s.unwrap_or(theme.s),
+            z: self.z.unwrap_or(theme.z),
+            j: self.j.unwrap_or(theme.j),
+            l: self.l.unwrap_or(theme.l),
+            ..theme
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::GUIDELINE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece_colors(config: &str) -> Result<PieceColors, String> {
+        PieceColors::from_config(&config.parse().unwrap())
+    }
+
+    #[test]
+    fn piece_colors_replace_only_the_pieces_they_name() {
+        let colors = piece_colors("[piece]\ni = \"blue\"\nz = \"#102030\"").unwrap();
+        let theme = colors.apply(Theme::GUIDELINE);
+        assert_eq!(theme.i, Color::Blue);
+        assert_eq!(theme.z, Color::Rgb(16, 32, 48));
+        assert_eq!(theme.t, Theme::GUIDELINE.t);
+    }
+
+    #[test]
+    fn piece_colors_default_to_the_theme() {
+        assert_eq!(piece_colors("").unwrap().apply(Theme::MONOCHROME), Theme::MONOCHROME);
+    }
+
+    #[test]
+    fn unknown_piece_colors_are_errors() {
+        assert_eq!(piece_colors("[piece]\ni = \"crimson\"").unwrap_err(), "unknown color 'crimson' for 'i' in [piece]");
+        assert_eq!(piece_colors("[piece]\nx = \"red\"").unwrap_err(), "unknown piece 'x' in [piece]");
+        assert!(piece_colors("[piece]\ni = 3").is_err());
+    }
+
+    #[test]
+    fn cycling_goes_through_every_theme_and_wraps_around() {
+        let mut theme = Theme::GUIDELINE;
+        let mut seen = Vec::new();
+        for _ in 0..Theme::ALL.len() {
+            seen.push(theme.name);
+            theme = theme.next();
+        }
+        assert_eq!(theme, Theme::GUIDELINE);
+        assert_eq!(seen, Theme::ALL.map(|theme| theme.name));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::workers::{sleep_while_running, SHUTDOWN_POLL_MS};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// Paces the ticks that drive the game loop. The game only advances when a tick arrives, so swapping the
+/// real clock for ticks fired by hand makes time-dependent behaviour deterministic.
+pub trait TickSource: Send {
+    /// Blocks until the next tick is due. Returns false once no more ticks will come, or as soon as
+    /// `running` is cleared, so the thread waiting on the ticks can stop without one having to come.
+    fn wait(&mut self, running: &AtomicBool) -> bool;
+}
+
+/// Ticks at a fixed interval of real time.
+pub struct RealTicks {
+    interval: Duration,
+}
+
+impl RealTicks {
+    pub fn new(interval: Duration) -> RealTicks {
+        RealTicks { interval }
+    }
+}
+
+impl TickSource for RealTicks {
+    fn wait(&mut self, running: &AtomicBool) -> bool {
+        sleep_while_running(running, self.interval.as_millis() as u64)
+    }
+}
+
+/// Ticks only when its `Ticker` fires them, and stops once the ticker is dropped.
+pub struct ManualTicks {
+    rx: Receiver<()>,
+}
+
+/// Fires ticks for the `ManualTicks` it was created with.
+#[derive(Clone)]
+pub struct Ticker {
+    tx: Sender<()>,
+}
+
+impl ManualTicks {
+    pub fn new() -> (ManualTicks, Ticker) {
+        let (tx, rx) = mpsc::channel();
+        (ManualTicks { rx }, Ticker { tx })
+    }
+}
+
+impl TickSource for ManualTicks {
+    fn wait(&mut self, running: &AtomicBool) -> bool {
+        while running.load(Ordering::Relaxed) {
+            match self.rx.recv_timeout(Duration::from_millis(SHUTDOWN_POLL_MS)) {
+                Ok(()) => return true,
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+        false
+    }
+}
+
+impl Ticker {
+    /// Fires `count` ticks. Returns false if the tick source has gone away.
+    pub fn fire(&self, count: u32) -> bool {
+        (0..count).all(|_| self.tx.send(()).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_ticks_come_only_when_fired() {
+        let running = AtomicBool::new(true);
+        let (mut ticks, ticker) = ManualTicks::new();
+        assert!(ticker.fire(3));
+        assert!((0..3).all(|_| ticks.wait(&running)));
+        drop(ticker);
+        assert!(!ticks.wait(&running));
+    }
+
+    #[test]
+    fn waiting_on_manual_ticks_stops_once_running_is_cleared() {
+        let running = AtomicBool::new(false);
+        let (mut ticks, ticker) = ManualTicks::new();
+        assert!(ticker.fire(1));
+        assert!(!ticks.wait(&running));
+    }
+
+    #[test]
+    fn ticker_fails_once_its_ticks_are_gone() {
+        let (ticks, ticker) = ManualTicks::new();
+        drop(ticks);
+        assert!(!ticker.fire(1));
+    }
+}
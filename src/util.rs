@@ -7,10 +7,170 @@ pub enum Color {
     Red,
     Blue,
     Orange,
+    Yellow,
+    White,
+    LightGray,
+    Gray,
+    Magenta,
+    SkyBlue,
+    Teal,
+    Vermilion,
+    Pink,
+    DarkCyan,
+    DarkPurple,
+    DarkGreen,
+    DarkRed,
+    DarkBlue,
+    DarkOrange,
+    DarkYellow,
+    DarkGray,
+    DarkMagenta,
+    DarkSkyBlue,
+    DarkTeal,
+    DarkVermilion,
+    DarkPink,
+    /// Any other color, given by its red, green and blue components. Terminals without 24-bit color show the
+    /// nearest color they have.
+    Rgb(u8, u8, u8),
 }
 
-#[derive(PartialEq, Copy, Clone)]
+impl Color {
+    /// Returns a darker shade of the color, for things drawn in it that should stand out less, such as the
+    /// ghost piece. White dims to light gray and light gray to gray, and an RGB color to three fifths of its
+    /// brightness; black and the dark shades stay as they are.
+    pub fn dim(self) -> Color {
+        match self {
+            Color::Cyan => Color::DarkCyan,
+            Color::Purple => Color::DarkPurple,
+            Color::Green => Color::DarkGreen,
+            Color::Red => Color::DarkRed,
+            Color::Blue => Color::DarkBlue,
+            Color::Orange => Color::DarkOrange,
+            Color::Yellow => Color::DarkYellow,
+            Color::White => Color::LightGray,
+            Color::LightGray => Color::Gray,
+            Color::Gray => Color::DarkGray,
+            Color::Magenta => Color::DarkMagenta,
+            Color::SkyBlue => Color::DarkSkyBlue,
+            Color::Teal => Color::DarkTeal,
+            Color::Vermilion => Color::DarkVermilion,
+            Color::Pink => Color::DarkPink,
+            Color::Rgb(r, g, b) => {
+                let dim = |c: u8| (c as u16 * 3 / 5) as u8;
+                Color::Rgb(dim(r), dim(g), dim(b))
+            }
+            color => color,
+        }
+    }
+}
+
+/// The names colors go by in the config file.
+const COLOR_NAMES: [(&str, Color); 29] = [
+    ("black", Color::Black),
+    ("cyan", Color::Cyan),
+    ("purple", Color::Purple),
+    ("green", Color::Green),
+    ("red", Color::Red),
+    ("blue", Color::Blue),
+    ("orange", Color::Orange),
+    ("yellow", Color::Yellow),
+    ("white", Color::White),
+    ("light_gray", Color::LightGray),
+    ("gray", Color::Gray),
+    ("magenta", Color::Magenta),
+    ("sky_blue", Color::SkyBlue),
+    ("teal", Color::Teal),
+    ("vermilion", Color::Vermilion),
+    ("pink", Color::Pink),
+    ("dark_cyan", Color::DarkCyan),
+    ("dark_purple", Color::DarkPurple),
+    ("dark_green", Color::DarkGreen),
+    ("dark_red", Color::DarkRed),
+    ("dark_blue", Color::DarkBlue),
+    ("dark_orange", Color::DarkOrange),
+    ("dark_yellow", Color::DarkYellow),
+    ("dark_gray", Color::DarkGray),
+    ("dark_magenta", Color::DarkMagenta),
+    ("dark_sky_blue", Color::DarkSkyBlue),
+    ("dark_teal", Color::DarkTeal),
+    ("dark_vermilion", Color::DarkVermilion),
+    ("dark_pink", Color::DarkPink),
+];
+
+/// Looks up a color by name: the color's name in lower case with its words joined by underscores, such as
+/// `sky_blue` or `dark_red`, or `#rrggbb` for any other color.
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(component(0)?, component(2)?, component(4)?));
+    }
+    COLOR_NAMES.iter().find(|(color_name, _)| *color_name == name).map(|&(_, color)| color)
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Direction {
     Left,
     Right
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bright_colors_dim_to_their_dark_shades() {
+        assert_eq!(Color::Red.dim(), Color::DarkRed);
+        assert_eq!(Color::Cyan.dim(), Color::DarkCyan);
+        assert_eq!(Color::White.dim(), Color::LightGray);
+        assert_eq!(Color::Gray.dim(), Color::DarkGray);
+    }
+
+    #[test]
+    fn black_and_dark_shades_stay_as_they_are() {
+        assert_eq!(Color::Black.dim(), Color::Black);
+        assert_eq!(Color::DarkRed.dim(), Color::DarkRed);
+    }
+
+    #[test]
+    fn rgb_colors_dim_to_three_fifths() {
+        assert_eq!(Color::Rgb(255, 100, 0).dim(), Color::Rgb(153, 60, 0));
+    }
+
+    /// Returns a color's variant name in lower case with underscores between its words, e.g. `dark_red`.
+    fn snake_case(color: Color) -> String {
+        let mut name = String::new();
+        for c in format!("{:?}", color).chars() {
+            if c.is_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+
+    #[test]
+    fn every_named_color_parses_under_its_name() {
+        for (name, color) in COLOR_NAMES {
+            assert_eq!(name, snake_case(color));
+            assert_eq!(parse_color(name), Some(color), "{}", name);
+        }
+        assert_eq!(parse_color("sky_blue"), Some(Color::SkyBlue));
+        assert_eq!(parse_color("dark_vermilion"), Some(Color::DarkVermilion));
+    }
+
+    #[test]
+    fn hex_colors_parse_to_rgb() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::Rgb(255, 128, 0)));
+        assert_eq!(parse_color("#00AAff"), Some(Color::Rgb(0, 170, 255)));
+    }
+
+    #[test]
+    fn unknown_colors_dont_parse() {
+        for name in ["", "Red", "dark red", "crimson", "#ff80", "#ff80000", "#gg0000", "#ééé"] {
+            assert_eq!(parse_color(name), None, "{:?}", name);
+        }
+    }
+}
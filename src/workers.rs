@@ -0,0 +1,249 @@
+//! The threads that feed a game loop its updates, each sending them down a channel for the loop to act on
+//! in turn.
+
+use crate::bindings::Key;
+use crate::error::TetrustError;
+use crate::price::{PriceError, PriceFeed};
+use crate::tick::TickSource;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the threads feeding a game wake up to check whether they should stop.
+pub const SHUTDOWN_POLL_MS: u64 = 100;
+
+/// How long the price thread waits between reads of the price feed, in milliseconds.
+const PRICE_POLL_MS: u64 = 5000;
+
+pub enum GameUpdate {
+    /// A key read from the keyboard, and when it was read.
+    KeyPress(Key, Instant),
+    /// A key the terminal reported going up.
+    KeyRelease(Key),
+    Tick,
+    DurationUpdate(u64),
+    /// A price read from the price feed and the percentage it moved since the last one.
+    Price(f64, f64),
+    /// Why the price feed couldn't be read; the price thread tries again a little later.
+    PriceError(String),
+    Resize,
+    Garbage,
+    /// A thread feeding the game hit an error the game can't go on from.
+    Failed(TetrustError),
+}
+
+/// The threads feeding a game loop. Each is handed a flag that stays set while it should keep running;
+/// dropping the workers clears the flag and waits for every thread to finish.
+pub struct Workers {
+    running: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    pub fn new() -> Workers {
+        Workers {
+            running: Arc::new(AtomicBool::new(true)),
+            handles: Vec::new(),
+        }
+    }
+
+    pub fn spawn<F: FnOnce(Arc<AtomicBool>) + Send + 'static>(&mut self, f: F) {
+        let running = Arc::clone(&self.running);
+        self.handles.push(thread::spawn(move || f(running)));
+    }
+}
+
+impl Default for Workers {
+    fn default() -> Workers {
+        Workers::new()
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Sleeps for `millis` milliseconds, waking early if `running` is cleared. Returns whether it still is set.
+pub fn sleep_while_running(running: &AtomicBool, millis: u64) -> bool {
+    let until = Instant::now() + Duration::from_millis(millis);
+    while running.load(Ordering::Relaxed) {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(Duration::from_millis(SHUTDOWN_POLL_MS)));
+    }
+    false
+}
+
+/// Spawns a thread which sends game ticks to advance the piece as `ticks` paces them. Before each tick it
+/// asks `resized` whether the terminal has changed size, and sends a resize first if it has.
+pub fn spawn_tick_thread(
+    workers: &mut Workers,
+    mut ticks: impl TickSource + 'static,
+    resized: fn() -> bool,
+    tx_event: Sender<GameUpdate>,
+) {
+    workers.spawn(move |running| {
+        while ticks.wait(&running) {
+            if resized() && tx_event.send(GameUpdate::Resize).is_err() {
+                break;
+            }
+            if tx_event.send(GameUpdate::Tick).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Spawns a thread which brings up a row of garbage every `interval` milliseconds
+pub fn spawn_garbage_thread(workers: &mut Workers, interval: u64, tx_event: Sender<GameUpdate>) {
+    workers.spawn(move |running| {
+        while sleep_while_running(&running, interval) {
+            if tx_event.send(GameUpdate::Garbage).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Runs `fetch` on a thread of its own, waiting for the price it reads only while `running` is set. A price
+/// request can take seconds; once the game stops, the thread making it is left to finish on its own rather
+/// than held up for, so stopping the workers never waits on the network. Returns `None` if the game stopped
+/// before the price came.
+fn fetch_while_running<F>(fetch: F, running: &AtomicBool) -> Option<Result<f64, PriceError>>
+where
+    F: FnOnce() -> Result<f64, PriceError> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch());
+    });
+    while running.load(Ordering::Relaxed) {
+        match rx.recv_timeout(Duration::from_millis(SHUTDOWN_POLL_MS)) {
+            Ok(result) => return Some(result),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+    None
+}
+
+/// Spawns a thread which polls the price feed and changes the game speed as the price moves. `duration`
+/// holds the game's current speed, which each price change adjusts.
+pub fn spawn_price_thread(
+    workers: &mut Workers,
+    price_feed: PriceFeed,
+    duration: Arc<AtomicU64>,
+    tx_event: Sender<GameUpdate>,
+) {
+    workers.spawn(move |running| {
+        let mut previous_price: f64 = 0.0;
+        while running.load(Ordering::Relaxed) {
+            let feed = price_feed.clone();
+            let current_price = match fetch_while_running(move || feed.fetch_price(), &running) {
+                None => break,
+                Some(Ok(price)) => price,
+                Some(Err(err)) => {
+                    if tx_event.send(GameUpdate::PriceError(err.to_string())).is_err() {
+                        break;
+                    }
+                    sleep_while_running(&running, PRICE_POLL_MS);
+                    continue;
+                }
+            };
+
+            let mut new_duration = duration.load(Ordering::Relaxed);
+            let mut percentage_change = 0.0;
+            if previous_price != 0.0 {
+                let price_change = current_price - previous_price;
+                percentage_change = (price_change / previous_price) * 100.0;
+                new_duration = price_feed.price_change_to_duration(percentage_change, new_duration);
+            }
+
+            previous_price = current_price;
+            if tx_event.send(GameUpdate::Price(current_price, percentage_change)).is_err() {
+                break;
+            }
+
+            duration.store(new_duration, Ordering::Relaxed);
+            if tx_event.send(GameUpdate::DurationUpdate(new_duration)).is_err() {
+                break;
+            }
+            sleep_while_running(&running, PRICE_POLL_MS);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn dropping_workers_stops_and_joins_their_threads() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let mut workers = Workers::new();
+        for _ in 0..3 {
+            let finished = Arc::clone(&finished);
+            workers.spawn(move |running| {
+                while sleep_while_running(&running, 60_000) {}
+                finished.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        let started = Instant::now();
+        drop(workers);
+        assert_eq!(finished.load(Ordering::Relaxed), 3);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn a_price_request_still_going_when_the_game_stops_is_not_waited_for() {
+        let running = Arc::new(AtomicBool::new(true));
+        let (release, blocked) = mpsc::channel::<()>();
+        let stopper = {
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                running.store(false, Ordering::Relaxed);
+            })
+        };
+        let started = Instant::now();
+        let fetched = fetch_while_running(
+            move || {
+                let _ = blocked.recv();
+                Ok(1.0)
+            },
+            &running,
+        );
+        assert!(fetched.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        stopper.join().unwrap();
+        drop(release);
+    }
+
+    #[test]
+    fn a_price_read_in_time_is_returned() {
+        let running = AtomicBool::new(true);
+        let fetched = fetch_while_running(|| Err(PriceError::Http(String::from("offline"))), &running);
+        assert!(matches!(fetched, Some(Err(PriceError::Http(_)))));
+        assert!(matches!(fetch_while_running(|| Ok(2.5), &running), Some(Ok(price)) if price == 2.5));
+    }
+
+    #[test]
+    fn sleep_wakes_early_once_running_is_cleared() {
+        let running = AtomicBool::new(true);
+        assert!(sleep_while_running(&running, 1));
+        running.store(false, Ordering::Relaxed);
+        let started = Instant::now();
+        assert!(!sleep_while_running(&running, 60_000));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
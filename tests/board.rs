@@ -0,0 +1,33 @@
+use tetrust::bindings::Action;
+use tetrust::{Board, BoardConfig, Game, GameOver, BOARD_HEIGHT};
+
+#[test]
+fn a_board_read_from_text_clears_its_full_rows() {
+    let mut board: Board = "....\n....\n....\n####\n#.##\n####\n".parse().unwrap();
+    assert_eq!(board.full_rows(), vec![3, 5]);
+    assert_eq!(board.clear_lines().len(), 2);
+    assert_eq!(board.to_string(), "....\n....\n....\n....\n....\n#.##\n");
+    assert_eq!(board.column_heights(), vec![1, 0, 1, 1]);
+}
+
+#[test]
+fn a_game_starts_from_the_board_it_is_given() {
+    let mut rows = vec![".........."; BOARD_HEIGHT as usize - 2];
+    rows.extend(["#########.", "#.########"]);
+    let board: Board = rows.join("\n").parse().unwrap();
+
+    let mut game = Game::new_seeded(BoardConfig::default(), 1);
+    game.set_board(board.clone());
+    assert_eq!(game.board(), &board);
+    assert_eq!(game.start_board(), Some(&board));
+    assert_eq!(game.board().garbage_rows(), 2);
+}
+
+#[test]
+fn hard_dropping_every_piece_in_place_ends_a_headless_game() {
+    let mut game = Game::new_seeded(BoardConfig::default(), 1);
+    let game_over = (0..1000).find_map(|_| game.step(Action::HardDrop).game_over);
+    assert!(matches!(game_over, Some(GameOver::TopOut | GameOver::BlockOut | GameOver::LockOut)));
+    assert!(game.score() > 0);
+    assert_eq!(game.lines_cleared(), 0);
+}
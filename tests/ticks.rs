@@ -0,0 +1,88 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tetrust::bindings::Action;
+use tetrust::replay::ReplayEvent;
+use tetrust::tick::ManualTicks;
+use tetrust::workers::{spawn_tick_thread, GameUpdate, Workers};
+use tetrust::{BoardConfig, Game, TICK_MS};
+
+/// Counts the ticks it takes gravity to drop the current piece one row.
+fn ticks_per_row(game: &mut Game) -> u32 {
+    let row = game.current_position().y;
+    let mut ticks = 0;
+    while game.current_position().y == row {
+        ReplayEvent::Tick.apply(game).unwrap();
+        ticks += 1;
+    }
+    ticks
+}
+
+fn game() -> Game {
+    let mut game = Game::new_seeded(BoardConfig::default(), 1);
+    game.set_clock(Instant::now());
+    game
+}
+
+#[test]
+fn a_speed_update_changes_how_many_ticks_a_row_takes() {
+    let mut game = game();
+    ReplayEvent::Speed(800).apply(&mut game).unwrap();
+    assert_eq!(ticks_per_row(&mut game), (800 / TICK_MS) as u32);
+
+    ReplayEvent::Speed(160).apply(&mut game).unwrap();
+    assert_eq!(game.duration(), 160);
+    assert_eq!(ticks_per_row(&mut game), (160 / TICK_MS) as u32);
+}
+
+#[test]
+fn fired_ticks_drive_the_game_through_the_tick_thread() {
+    let mut workers = Workers::new();
+    let (ticks, ticker) = ManualTicks::new();
+    let (tx_event, rx_event) = mpsc::channel();
+    spawn_tick_thread(&mut workers, ticks, || false, tx_event);
+
+    let mut game = game();
+    game.set_duration(800);
+    let row = game.current_position().y;
+    assert!(ticker.fire((800 / TICK_MS) as u32));
+    for _ in 0..800 / TICK_MS {
+        let update = rx_event.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(update, GameUpdate::Tick));
+        ReplayEvent::Tick.apply(&mut game).unwrap();
+    }
+    assert_eq!(game.current_position().y, row + 1);
+    assert!(rx_event.try_recv().is_err());
+}
+
+#[test]
+fn a_game_played_on_fired_ticks_locks_its_pieces_and_ends() {
+    let mut workers = Workers::new();
+    let (ticks, ticker) = ManualTicks::new();
+    let (tx_event, rx_event) = mpsc::channel();
+    spawn_tick_thread(&mut workers, ticks, || false, tx_event);
+
+    // Each tick is a sixteenth of a second into the game, so lock delays run out as the ticks come.
+    let mut game = game();
+    let started = Instant::now();
+    game.set_duration(TICK_MS);
+    let mut tick = 0;
+    let game_over = loop {
+        assert!(ticker.fire(1));
+        assert!(matches!(rx_event.recv_timeout(Duration::from_secs(5)).unwrap(), GameUpdate::Tick));
+        tick += 1;
+        game.set_clock(started + Duration::from_millis(tick * TICK_MS));
+        if tick % 10 == 0 {
+            let _ = game.perform(Action::MoveLeft);
+        }
+        if let Err(reason) = ReplayEvent::Tick.apply(&mut game) {
+            break reason;
+        }
+        assert!(tick < 10_000, "the game should have topped out");
+    };
+    assert!(game.stats().total_pieces() > 1, "{}", game_over);
+
+    // Once the ticker goes, the tick thread stops and its sender with it.
+    drop(ticker);
+    drop(workers);
+    assert!(matches!(rx_event.try_recv(), Err(mpsc::TryRecvError::Disconnected)));
+}